@@ -23,17 +23,24 @@ fn main() -> io::Result<()> {
     "Expr",
     &["error", "token"],
     &[
+      "Array    : Token bracket, Rc<Vec<Rc<Expr>>> elements",
       "Assign   : Token name, Rc<Expr> value",
-      "Binary   : Rc<Expr> left, Token operator, Rc<Expr> right",
-      "Call     : Rc<Expr> callee, Token bracket, Rc<Vec<Rc<Expr>>> arguments",
+      "Binary   : Rc<Expr> left, Token operator, Rc<Expr> right, Span span",
+      "Call     : Rc<Expr> callee, Token bracket, Rc<Vec<Rc<Expr>>> arguments, Span span",
+      "Destructure : Token bracket, Rc<Vec<Rc<Expr>>> targets, Rc<Expr> value",
       "Get      : Rc<Expr> object, Token name",
       "Grouping : Rc<Expr> expression",
+      "If       : Token keyword, Rc<Expr> condition, Rc<Expr> then_branch, Rc<Expr> else_branch",
+      "Index    : Rc<Expr> object, Token bracket, Option<Rc<Expr>> index, Option<Rc<Expr>> end, bool is_slice",
+      "IndexSet : Rc<Expr> object, Token bracket, Rc<Expr> index, Rc<Expr> value",
       "Literal  : Option<Literal> value",
       "Logical  : Rc<Expr> left, Token operator, Rc<Expr> right",
+      "Map      : Token brace, Rc<Vec<Rc<Expr>>> keys, Rc<Vec<Rc<Expr>>> values",
+      "Postfix  : Rc<Expr> target, Token operator",
       "Set      : Rc<Expr> object, Token name, Rc<Expr> value",
       "Super      : Token keyword, Token method",
       "This     : Token keyword",
-      "Unary    : Token operator, Rc<Expr> right",
+      "Unary    : Token operator, Rc<Expr> right, Span span",
       "Variable : Token name",
     ],
   )?;
@@ -44,15 +51,18 @@ fn main() -> io::Result<()> {
     &["error", "expr", "token"],
     &[
       "Block      : Rc<Vec<Rc<Stmt>>> statements",
-      "Break      : Token token",
-      "Class      : Token name, Option<Rc<Expr>> superclass, Rc<Vec<Rc<Stmt>>> methods",
+      "Break      : Token token, Option<Token> label",
+      "Continue   : Token token, Option<Token> label",
+      "Class      : Token name, Option<Rc<Expr>> superclass, Rc<Vec<Rc<Stmt>>> fields, Rc<Vec<Rc<Stmt>>> methods, Rc<Vec<Rc<Stmt>>> static_methods",
+      "Destructure : Token bracket, Vec<Token> names, Rc<Expr> initialiser",
       "Expression : Rc<Expr> expression",
-      "Function   : Token name, Vec<Token> params, Rc<Vec<Rc<Stmt>>> body",
-      "If         : Rc<Expr> condition, Rc<Stmt> then_branch, Option<Rc<Stmt>> else_branch",
+      "Foreach    : Option<Token> label, Token name, Rc<Expr> iterable, Rc<Stmt> body",
+      "Function   : Token name, Vec<Token> params, bool is_variadic, Rc<Vec<Rc<Stmt>>> body",
+      "If         : Token keyword, Rc<Expr> condition, Rc<Stmt> then_branch, Option<Rc<Stmt>> else_branch",
       "Print      : Rc<Expr> expression",
       "Return     : Token keyword, Option<Rc<Expr>> value",
       "Var        : Token name, Option<Rc<Expr>> initialiser",
-      "While      : Rc<Expr> condition, Rc<Stmt> body",
+      "While      : Option<Token> label, Token keyword, Option<Rc<Expr>> condition, Rc<Stmt> body, Option<Rc<Expr>> increment, bool per_iteration",
     ],
   )?;
 
@@ -93,7 +103,7 @@ fn define_ast(
   }
 
   writeln!(file)?;
-  writeln!(file, "#[derive(Debug)]")?;
+  writeln!(file, "#[derive(Debug, Clone)]")?;
   writeln!(file, "pub enum {base_name} {{")?;
   for tree_type in &tree_types {
     writeln!(file, "  {0}(Rc<{0}{1}>),", tree_type.class_name, base_name)?;
@@ -116,7 +126,7 @@ fn define_ast(
       base_name,
       tree_type.class_name,
       base_name.to_lowercase(),
-      tree_type.class_name.to_lowercase(),
+      to_snake_case(&tree_type.class_name),
     )?;
   }
   writeln!(file, "    }}")?;
@@ -146,6 +156,15 @@ fn define_ast(
   writeln!(file, "impl Hash for {base_name} {{")?;
   writeln!(file, "  fn hash<H: Hasher>(&self, state: &mut H) {{")?;
   writeln!(file, "    discriminant(self).hash(state);")?;
+  writeln!(file, "    match self {{")?;
+  for tree_type in &tree_types {
+    writeln!(
+      file,
+      "      {0}::{1}(n0) => Rc::as_ptr(n0).hash(state),",
+      base_name, tree_type.class_name
+    )?;
+  }
+  writeln!(file, "    }}")?;
   writeln!(file, "  }}")?;
   writeln!(file, "}}")?;
 
@@ -165,7 +184,7 @@ fn define_ast(
     writeln!(
       file,
       "  fn visit_{0}_{1}(&self, wrapper: &Rc<{2}>, {1}: &{3}{2}) -> Result<T, LoxError>;",
-      tree_type.class_name.to_lowercase(),
+      to_snake_case(&tree_type.class_name),
       base_name.to_lowercase(),
       base_name,
       tree_type.class_name,
@@ -175,3 +194,16 @@ fn define_ast(
 
   Ok(())
 }
+
+fn to_snake_case(class_name: &str) -> String {
+  let mut snake_case = String::new();
+
+  for (i, c) in class_name.chars().enumerate() {
+    if c.is_ascii_uppercase() && i.ne(&0) {
+      snake_case.push('_');
+    }
+    snake_case.push(c.to_ascii_lowercase());
+  }
+
+  snake_case
+}
@@ -1,13 +1,19 @@
-use std::rc::Rc;
+use std::{fmt, rc::Rc};
 
-use crate::{error::*, expr::*};
+use crate::{error::*, expr::*, stmt::*, token::*};
 
 pub struct AstPrinter;
 
 impl AstPrinter {
-  pub fn print(&self, expr: &Expr) -> String {
+  pub fn print(&self, expr: &Rc<Expr>) -> String {
     expr
-      .accept(self)
+      .accept(expr, self)
+      .unwrap_or("AST PRINTER INTERNAL ERROR.".to_string())
+  }
+
+  pub fn print_stmt(&self, stmt: &Rc<Stmt>) -> String {
+    stmt
+      .accept(stmt, self)
       .unwrap_or("AST PRINTER INTERNAL ERROR.".to_string())
   }
 
@@ -15,32 +21,104 @@ impl AstPrinter {
     let mut builder = format!("({name}");
 
     for expr in exprs {
-      builder = format!("{} {}", builder, expr.accept(self)?);
+      builder = format!("{} {}", builder, expr.accept(expr, self)?);
+    }
+    builder.push(')');
+
+    Ok(builder)
+  }
+
+  fn parenthesise_stmts(&self, name: &str, stmts: &[&Rc<Stmt>]) -> Result<String, LoxError> {
+    let mut builder = format!("({name}");
+
+    for stmt in stmts {
+      builder = format!("{} {}", builder, stmt.accept(stmt, self)?);
     }
     builder.push(')');
 
     Ok(builder)
   }
+
+  fn with_label(name: &str, label: &Option<Token>) -> String {
+    match label {
+      Some(l) => format!("{name} {}", l.get_lexeme()),
+      None => name.to_string(),
+    }
+  }
 }
 
 impl ExprVisitor<String> for AstPrinter {
-  fn visit_assign_expr(&self, _expr: &AssignExpr) -> Result<String, LoxError> {
-    todo!()
+  fn visit_array_expr(&self, _wrapper: &Rc<Expr>, expr: &ArrayExpr) -> Result<String, LoxError> {
+    self.parenthesise("array", &expr.elements.iter().collect::<Vec<&Rc<Expr>>>())
+  }
+
+  fn visit_assign_expr(&self, _wrapper: &Rc<Expr>, expr: &AssignExpr) -> Result<String, LoxError> {
+    self.parenthesise(&format!("= {}", expr.name.get_lexeme()), &[&expr.value])
   }
 
-  fn visit_binary_expr(&self, expr: &BinaryExpr) -> Result<String, LoxError> {
+  fn visit_binary_expr(&self, _wrapper: &Rc<Expr>, expr: &BinaryExpr) -> Result<String, LoxError> {
     self.parenthesise(expr.operator.get_lexeme(), &[&expr.left, &expr.right])
   }
 
-  fn visit_call_expr(&self, _expr: &CallExpr) -> Result<String, LoxError> {
-    todo!()
+  fn visit_call_expr(&self, _wrapper: &Rc<Expr>, expr: &CallExpr) -> Result<String, LoxError> {
+    let mut exprs = vec![&expr.callee];
+    exprs.extend(expr.arguments.iter());
+    self.parenthesise("call", &exprs)
+  }
+
+  fn visit_destructure_expr(
+    &self,
+    _wrapper: &Rc<Expr>,
+    expr: &DestructureExpr,
+  ) -> Result<String, LoxError> {
+    let targets = expr
+      .targets
+      .iter()
+      .map(|t| self.print(t))
+      .collect::<Vec<String>>()
+      .join(" ");
+    self.parenthesise(&format!("= [{targets}]"), &[&expr.value])
   }
 
-  fn visit_grouping_expr(&self, expr: &GroupingExpr) -> Result<String, LoxError> {
+  fn visit_get_expr(&self, _wrapper: &Rc<Expr>, expr: &GetExpr) -> Result<String, LoxError> {
+    self.parenthesise(&format!(". {}", expr.name.get_lexeme()), &[&expr.object])
+  }
+
+  fn visit_grouping_expr(
+    &self,
+    _wrapper: &Rc<Expr>,
+    expr: &GroupingExpr,
+  ) -> Result<String, LoxError> {
     self.parenthesise("group", &[&expr.expression])
   }
 
-  fn visit_literal_expr(&self, expr: &LiteralExpr) -> Result<String, LoxError> {
+  fn visit_if_expr(&self, _wrapper: &Rc<Expr>, expr: &IfExpr) -> Result<String, LoxError> {
+    self.parenthesise(
+      "if",
+      &[&expr.condition, &expr.then_branch, &expr.else_branch],
+    )
+  }
+
+  fn visit_index_expr(&self, _wrapper: &Rc<Expr>, expr: &IndexExpr) -> Result<String, LoxError> {
+    let name = if expr.is_slice { "slice" } else { "index" };
+    let mut exprs = vec![&expr.object];
+    exprs.extend(expr.index.iter().chain(expr.end.iter()));
+    self.parenthesise(name, &exprs)
+  }
+
+  fn visit_index_set_expr(
+    &self,
+    _wrapper: &Rc<Expr>,
+    expr: &IndexSetExpr,
+  ) -> Result<String, LoxError> {
+    self.parenthesise("index=", &[&expr.object, &expr.index, &expr.value])
+  }
+
+  fn visit_literal_expr(
+    &self,
+    _wrapper: &Rc<Expr>,
+    expr: &LiteralExpr,
+  ) -> Result<String, LoxError> {
     if let Some(v) = &expr.value {
       Ok(v.to_string())
     } else {
@@ -48,16 +126,221 @@ impl ExprVisitor<String> for AstPrinter {
     }
   }
 
-  fn visit_logical_expr(&self, _expr: &LogicalExpr) -> Result<String, LoxError> {
-    todo!()
+  fn visit_logical_expr(
+    &self,
+    _wrapper: &Rc<Expr>,
+    expr: &LogicalExpr,
+  ) -> Result<String, LoxError> {
+    self.parenthesise(expr.operator.get_lexeme(), &[&expr.left, &expr.right])
+  }
+
+  fn visit_map_expr(&self, _wrapper: &Rc<Expr>, expr: &MapExpr) -> Result<String, LoxError> {
+    let exprs = expr
+      .keys
+      .iter()
+      .zip(expr.values.iter())
+      .flat_map(|(k, v)| [k, v])
+      .collect::<Vec<&Rc<Expr>>>();
+    self.parenthesise("map", &exprs)
+  }
+
+  fn visit_postfix_expr(
+    &self,
+    _wrapper: &Rc<Expr>,
+    expr: &PostfixExpr,
+  ) -> Result<String, LoxError> {
+    self.parenthesise(
+      &format!("post{}", expr.operator.get_lexeme()),
+      &[&expr.target],
+    )
+  }
+
+  fn visit_set_expr(&self, _wrapper: &Rc<Expr>, expr: &SetExpr) -> Result<String, LoxError> {
+    self.parenthesise(
+      &format!(".= {}", expr.name.get_lexeme()),
+      &[&expr.object, &expr.value],
+    )
   }
 
-  fn visit_unary_expr(&self, expr: &UnaryExpr) -> Result<String, LoxError> {
+  fn visit_super_expr(&self, _wrapper: &Rc<Expr>, expr: &SuperExpr) -> Result<String, LoxError> {
+    Ok(format!("(super {})", expr.method.get_lexeme()))
+  }
+
+  fn visit_this_expr(&self, _wrapper: &Rc<Expr>, _expr: &ThisExpr) -> Result<String, LoxError> {
+    Ok("this".to_string())
+  }
+
+  fn visit_unary_expr(&self, _wrapper: &Rc<Expr>, expr: &UnaryExpr) -> Result<String, LoxError> {
     self.parenthesise(expr.operator.get_lexeme(), &[&expr.right])
   }
 
-  fn visit_variable_expr(&self, _expr: &VariableExpr) -> Result<String, LoxError> {
-    todo!()
+  fn visit_variable_expr(
+    &self,
+    _wrapper: &Rc<Expr>,
+    expr: &VariableExpr,
+  ) -> Result<String, LoxError> {
+    Ok(expr.name.get_lexeme().to_string())
+  }
+}
+
+impl StmtVisitor<String> for AstPrinter {
+  fn visit_block_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &BlockStmt) -> Result<String, LoxError> {
+    self.parenthesise_stmts("block", &stmt.statements.iter().collect::<Vec<&Rc<Stmt>>>())
+  }
+
+  fn visit_break_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &BreakStmt) -> Result<String, LoxError> {
+    Ok(format!(
+      "({})",
+      AstPrinter::with_label("break", &stmt.label)
+    ))
+  }
+
+  fn visit_continue_stmt(
+    &self,
+    _wrapper: &Rc<Stmt>,
+    stmt: &ContinueStmt,
+  ) -> Result<String, LoxError> {
+    Ok(format!(
+      "({})",
+      AstPrinter::with_label("continue", &stmt.label)
+    ))
+  }
+
+  fn visit_class_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &ClassStmt) -> Result<String, LoxError> {
+    let mut name = format!("class {}", stmt.name.get_lexeme());
+    if let Some(s) = &stmt.superclass {
+      name = format!("{name} < {}", self.print(s));
+    }
+
+    let members = stmt
+      .fields
+      .iter()
+      .chain(stmt.methods.iter())
+      .collect::<Vec<&Rc<Stmt>>>();
+    let mut builder = self.parenthesise_stmts(&name, &members)?;
+    builder.pop();
+    for method in stmt.static_methods.iter() {
+      builder = format!("{builder} (static {})", self.print_stmt(method));
+    }
+    builder.push(')');
+
+    Ok(builder)
+  }
+
+  fn visit_destructure_stmt(
+    &self,
+    _wrapper: &Rc<Stmt>,
+    stmt: &DestructureStmt,
+  ) -> Result<String, LoxError> {
+    let names = stmt
+      .names
+      .iter()
+      .map(|n| n.get_lexeme().as_str())
+      .collect::<Vec<&str>>()
+      .join(" ");
+    self.parenthesise(&format!("var [{names}]"), &[&stmt.initialiser])
+  }
+
+  fn visit_expression_stmt(
+    &self,
+    _wrapper: &Rc<Stmt>,
+    stmt: &ExpressionStmt,
+  ) -> Result<String, LoxError> {
+    self.parenthesise(";", &[&stmt.expression])
+  }
+
+  fn visit_foreach_stmt(
+    &self,
+    _wrapper: &Rc<Stmt>,
+    stmt: &ForeachStmt,
+  ) -> Result<String, LoxError> {
+    Ok(format!(
+      "({} {} {} {})",
+      AstPrinter::with_label("foreach", &stmt.label),
+      stmt.name.get_lexeme(),
+      self.print(&stmt.iterable),
+      self.print_stmt(&stmt.body)
+    ))
+  }
+
+  fn visit_function_stmt(
+    &self,
+    _wrapper: &Rc<Stmt>,
+    stmt: &FunctionStmt,
+  ) -> Result<String, LoxError> {
+    let mut params = stmt
+      .params
+      .iter()
+      .map(|p| p.get_lexeme().to_string())
+      .collect::<Vec<String>>();
+    if let (true, Some(rest)) = (stmt.is_variadic, params.last_mut()) {
+      rest.insert_str(0, "...");
+    }
+
+    let name = format!("fun {}({})", stmt.name.get_lexeme(), params.join(" "));
+    self.parenthesise_stmts(&name, &stmt.body.iter().collect::<Vec<&Rc<Stmt>>>())
+  }
+
+  fn visit_if_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &IfStmt) -> Result<String, LoxError> {
+    let mut builder = format!(
+      "(if {} {}",
+      self.print(&stmt.condition),
+      self.print_stmt(&stmt.then_branch)
+    );
+    if let Some(e) = &stmt.else_branch {
+      builder = format!("{builder} {}", self.print_stmt(e));
+    }
+    builder.push(')');
+
+    Ok(builder)
+  }
+
+  fn visit_print_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &PrintStmt) -> Result<String, LoxError> {
+    self.parenthesise("print", &[&stmt.expression])
+  }
+
+  fn visit_return_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &ReturnStmt) -> Result<String, LoxError> {
+    self.parenthesise("return", &stmt.value.iter().collect::<Vec<&Rc<Expr>>>())
+  }
+
+  fn visit_var_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &VarStmt) -> Result<String, LoxError> {
+    self.parenthesise(
+      &format!("var {}", stmt.name.get_lexeme()),
+      &stmt.initialiser.iter().collect::<Vec<&Rc<Expr>>>(),
+    )
+  }
+
+  fn visit_while_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &WhileStmt) -> Result<String, LoxError> {
+    let condition = stmt
+      .condition
+      .as_ref()
+      .map_or_else(|| "true".to_string(), |c| self.print(c));
+    let mut builder = format!(
+      "({} {} {}",
+      AstPrinter::with_label("while", &stmt.label),
+      condition,
+      self.print_stmt(&stmt.body)
+    );
+    if let Some(i) = &stmt.increment {
+      builder = format!("{builder} {}", self.print(i));
+    }
+    builder.push(')');
+
+    Ok(builder)
+  }
+}
+
+// the printer needs the `Rc` that visitors are handed; cloning only bumps
+// the reference count of the node
+impl fmt::Display for Expr {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", AstPrinter.print(&Rc::new(self.clone())))
+  }
+}
+
+impl fmt::Display for Stmt {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", AstPrinter.print_stmt(&Rc::new(self.clone())))
   }
 }
 
@@ -65,16 +348,23 @@ impl ExprVisitor<String> for AstPrinter {
 mod test {
   use super::*;
 
-  use crate::token::*;
+  use crate::{parser::*, scanner::*};
+
+  fn parse(source: &str) -> Result<Vec<Stmt>, LoxError> {
+    let mut scanner = Scanner::new(source);
+    let tokens = scanner.scan_tokens()?;
+    Parser::new(tokens).parse()
+  }
 
   #[test]
   fn test_ast_printer() {
-    let expr = Expr::Binary(Rc::new(BinaryExpr {
+    let expr = Rc::new(Expr::Binary(Rc::new(BinaryExpr {
       left: Rc::new(Expr::Unary(Rc::new(UnaryExpr {
         operator: Token::new(TokenType::Minus, "-", None, 1),
         right: Rc::new(Expr::Literal(Rc::new(LiteralExpr {
           value: Some(Literal::Number(123.0)),
         }))),
+        span: (0, 0),
       }))),
       operator: Token::new(TokenType::Star, "*", None, 1),
       right: Rc::new(Expr::Grouping(Rc::new(GroupingExpr {
@@ -82,10 +372,39 @@ mod test {
           value: Some(Literal::Number(45.67)),
         }))),
       }))),
-    }));
+      span: (0, 0),
+    })));
 
     let expr_string = AstPrinter.print(&expr);
     assert_eq!(expr_string, "(* (- 123) (group 45.67))");
-    println!("{}", expr_string);
+    assert_eq!(expr.to_string(), expr_string);
+  }
+
+  #[test]
+  fn test_display() -> Result<(), LoxError> {
+    let statements = parse(
+      "var a = 1;\n\
+       fun f(x, ...rest) { return x + a; }\n\
+       if (a < 2 and true) print f(a)[0]; else a = nil;\n\
+       outer: while (a) { a++; break outer; }\n\
+       class B < A { m() { return super.m(); } class s() {} }",
+    )?;
+
+    let printed = statements
+      .iter()
+      .map(Stmt::to_string)
+      .collect::<Vec<String>>();
+    assert_eq!(
+      printed,
+      vec![
+        "(var a 1)",
+        "(fun f(x ...rest) (return (+ x a)))",
+        "(if (and (< a 2) true) (print (index (call f a) 0)) (; (= a nil)))",
+        "(while outer a (block (; (post++ a)) (break outer)))",
+        "(class B < A (fun m() (return (call (super m)))) (static (fun s())))",
+      ]
+    );
+
+    Ok(())
   }
 }
@@ -1,8 +1,14 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
 
 use crate::{error::*, token::*};
 
-#[derive(Default, Debug)]
+thread_local! {
+  // the environments being debug-formatted around the current one, as `{:?}`
+  // has no other way to pass state down to a closure's environment
+  static DEBUG_VISITING: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Default, Clone)]
 pub struct Environment {
   values: HashMap<String, Literal>,
   enclosing: Option<Rc<RefCell<Environment>>>,
@@ -62,39 +68,105 @@ impl Environment {
     self.values.insert(name.to_string(), value);
   }
 
-  pub fn get_at(&self, distance: usize, name: &str) -> Result<Literal, LoxError> {
-    if distance.eq(&0) {
-      if let Some(l) = self.values.get(name) {
-        return Ok(l.clone());
-      }
-    }
+  // only this scope, not the enclosing ones
+  pub fn iter_values(&self) -> impl Iterator<Item = (&String, &Literal)> {
+    self.values.iter()
+  }
 
-    if let Some(e) = self.enclosing.as_ref() {
-      return e.borrow().get_at(distance - 1, name);
+  // the resolver guarantees the chain is long enough, so a short one is a bug
+  pub fn ancestor(
+    environment: &Rc<RefCell<Environment>>,
+    distance: usize,
+  ) -> Result<Rc<RefCell<Environment>>, LoxError> {
+    let mut ancestor = environment.clone();
+    for _ in 0..distance {
+      let enclosing = ancestor.borrow().enclosing.clone();
+      ancestor = enclosing.ok_or_else(Environment::internal_error)?;
     }
 
-    unreachable!()
+    Ok(ancestor)
+  }
+
+  pub fn get_at(
+    environment: &Rc<RefCell<Environment>>,
+    distance: usize,
+    name: &str,
+  ) -> Result<Literal, LoxError> {
+    let ancestor = Environment::ancestor(environment, distance)?;
+    let value = ancestor.borrow().values.get(name).cloned();
+    value.ok_or_else(Environment::internal_error)
   }
 
   pub fn assign_at(
-    &mut self,
+    environment: &Rc<RefCell<Environment>>,
     distance: usize,
     name: &Token,
     value: &Literal,
   ) -> Result<(), LoxError> {
-    if distance.eq(&0) {
-      self
-        .values
-        .insert(name.get_lexeme().to_string(), value.clone());
-      return Ok(());
+    Environment::ancestor(environment, distance)?
+      .borrow_mut()
+      .values
+      .insert(name.get_lexeme().to_string(), value.clone());
+    Ok(())
+  }
+
+  fn internal_error() -> LoxError {
+    LoxError::system_error("ENVIRONMENT INTERNAL ERROR.")
+  }
+
+  // one block per scope, innermost first, each indented one level deeper
+  // than the scope it is enclosed by; callables print as their short names
+  pub fn dump(&self) -> String {
+    let mut dump = String::new();
+    self.dump_at(0, &mut dump, &mut Vec::new());
+    dump
+  }
+
+  fn dump_at(&self, depth: usize, dump: &mut String, visited: &mut Vec<usize>) {
+    let indent = "  ".repeat(depth);
+    let address = self as *const Environment as usize;
+    if visited.contains(&address) {
+      dump.push_str(&format!("{indent}<cycle>\n"));
+      return;
     }
+    visited.push(address);
 
-    if let Some(e) = self.enclosing.as_ref() {
-      e.borrow_mut().assign_at(distance - 1, name, value)?;
-      return Ok(());
+    dump.push_str(&format!("{indent}scope {depth}:\n"));
+
+    let mut names = self.values.keys().collect::<Vec<&String>>();
+    names.sort();
+    for name in names {
+      let value = match &self.values[name] {
+        Literal::NativeFunction(f) => format!("<native fn {}>", f.name()),
+        Literal::Class(c) => format!("<class {c}>"),
+        v => v.to_string(),
+      };
+      dump.push_str(&format!("{indent}  {name} = {value}\n"));
     }
 
-    unreachable!()
+    if let Some(e) = &self.enclosing {
+      e.borrow().dump_at(depth + 1, dump, visited);
+    }
+  }
+}
+
+// a function defined in a scope is stored in it and also closes over it, so
+// a derived `Debug` would go round that loop forever
+impl fmt::Debug for Environment {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let address = self as *const Environment as usize;
+    if DEBUG_VISITING.with(|v| v.borrow().contains(&address)) {
+      return write!(f, "<cycle>");
+    }
+
+    DEBUG_VISITING.with(|v| v.borrow_mut().push(address));
+    let res = f
+      .debug_struct("Environment")
+      .field("values", &self.values)
+      .field("enclosing", &self.enclosing)
+      .finish();
+    DEBUG_VISITING.with(|v| v.borrow_mut().pop());
+    res
   }
 }
 
@@ -102,6 +174,8 @@ impl Environment {
 mod test {
   use super::*;
 
+  use crate::{lox_function::*, stmt::*};
+
   #[test]
   fn test_define_variable() {
     let mut env = Environment::new();
@@ -174,4 +248,58 @@ mod test {
     assert!(env.assign(&foo_tok, &Literal::Number(89.5)).is_ok());
     assert!(matches!(env.get(&foo_tok), Ok(Literal::Number(n)) if n == 89.5));
   }
+
+  #[test]
+  fn test_dump() {
+    let enc = Rc::new(RefCell::new(Environment::new()));
+    enc.borrow_mut().define("foo", Literal::Number(1.0));
+    let mut env = Environment::new_with_enclosing(&enc);
+    env.define("bar", Literal::String("Bar".to_string()));
+    env.define("baz", Literal::Nil);
+    assert_eq!(
+      env.dump(),
+      "scope 0:\n  bar = Bar\n  baz = nil\n  scope 1:\n    foo = 1\n"
+    );
+  }
+
+  #[test]
+  fn test_debug_self_referential_closure() {
+    let env = Rc::new(RefCell::new(Environment::new()));
+    let declaration = FunctionStmt {
+      name: Token::new(TokenType::Identifier, "f", None, 1),
+      params: Vec::new(),
+      is_variadic: false,
+      body: Vec::new().into(),
+    };
+    let f = Literal::Function(LoxFunction::new(&env, &declaration, false).into());
+    env.borrow_mut().define("f", f);
+
+    let debug = format!("{:?}", env.borrow());
+    assert!(debug.starts_with("Environment { values: {\"f\": Function("));
+    assert!(debug.contains("closure: RefCell { value: <cycle> }"));
+    assert!(env.borrow().dump().contains("f = <fn f>"));
+
+    // break the cycle so both are freed
+    env.borrow_mut().values.clear();
+  }
+
+  #[test]
+  fn test_ancestor() -> Result<(), LoxError> {
+    let outer = Rc::new(RefCell::new(Environment::new()));
+    outer.borrow_mut().define("foo", Literal::Number(1.0));
+    let middle = Rc::new(RefCell::new(Environment::new_with_enclosing(&outer)));
+    let inner = Rc::new(RefCell::new(Environment::new_with_enclosing(&middle)));
+
+    assert!(Rc::ptr_eq(&Environment::ancestor(&inner, 0)?, &inner));
+    assert!(Rc::ptr_eq(&Environment::ancestor(&inner, 1)?, &middle));
+    assert!(Rc::ptr_eq(&Environment::ancestor(&inner, 2)?, &outer));
+    assert!(Environment::ancestor(&inner, 3).is_err());
+
+    let foo_tok = Token::new(TokenType::Identifier, "foo", None, 0);
+    Environment::assign_at(&inner, 2, &foo_tok, &Literal::Number(2.0))?;
+    assert!(matches!(Environment::get_at(&inner, 2, "foo"), Ok(Literal::Number(n)) if n == 2.0));
+    assert!(Environment::get_at(&inner, 1, "foo").is_err());
+
+    Ok(())
+  }
 }
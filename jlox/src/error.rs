@@ -5,37 +5,106 @@ pub struct LoxError(LoxErrorType);
 
 #[derive(Debug, Clone)]
 enum LoxErrorType {
-  Break,
-  GeneralErr { line: usize, message: String },
-  ParseErr { token: Token, message: String },
-  Return { value: Literal },
-  RuntimeErr { token: Token, message: String },
-  SystemErr { message: String },
+  Break {
+    label: Option<Token>,
+  },
+  Continue {
+    label: Option<Token>,
+  },
+  GeneralErr {
+    line: usize,
+    message: String,
+  },
+  NativeErr {
+    message: String,
+  },
+  ParseErr {
+    token: Token,
+    message: String,
+  },
+  Return {
+    value: Literal,
+  },
+  RuntimeErr {
+    // boxed to keep every `Result` in the interpreter small
+    token: Box<Token>,
+    message: String,
+    // the whole expression at fault, when there is more to it than `token`
+    span: Option<Span>,
+    source_line: Option<String>,
+  },
+  SystemErr {
+    message: String,
+  },
   TypeErr,
 }
 
 impl LoxError {
-  pub fn new_break() -> LoxError {
-    LoxError(LoxErrorType::Break)
+  pub fn new_break(label: &Option<Token>) -> LoxError {
+    LoxError(LoxErrorType::Break {
+      label: label.clone(),
+    })
   }
 
-  pub fn is_break(&self) -> bool {
-    if matches!(self.0, LoxErrorType::Break) {
-      return true;
+  // whether this stops the loop with the given label, an unlabelled
+  // break stopping the innermost loop
+  pub fn is_break_for(&self, loop_label: &Option<Token>) -> bool {
+    if let LoxErrorType::Break { label } = &self.0 {
+      return LoxError::targets(label, loop_label);
     }
 
     false
   }
 
+  pub fn new_continue(label: &Option<Token>) -> LoxError {
+    LoxError(LoxErrorType::Continue {
+      label: label.clone(),
+    })
+  }
+
+  pub fn is_continue_for(&self, loop_label: &Option<Token>) -> bool {
+    if let LoxErrorType::Continue { label } = &self.0 {
+      return LoxError::targets(label, loop_label);
+    }
+
+    false
+  }
+
+  fn targets(label: &Option<Token>, loop_label: &Option<Token>) -> bool {
+    match (label, loop_label) {
+      (None, _) => true,
+      (Some(l), Some(ll)) => l.get_lexeme().eq(ll.get_lexeme()),
+      (Some(_), None) => false,
+    }
+  }
+
   pub fn general_error(line: usize, message: &str) -> LoxError {
-    let err = LoxError(LoxErrorType::GeneralErr {
-      line,
-      message: message.to_string(),
-    });
+    let err = LoxError::new_general_error(line, message);
     err.report();
     err
   }
 
+  pub fn new_general_error(line: usize, message: &str) -> LoxError {
+    LoxError(LoxErrorType::GeneralErr {
+      line,
+      message: message.to_string(),
+    })
+  }
+
+  pub fn new_native_error(message: &str) -> LoxError {
+    LoxError(LoxErrorType::NativeErr {
+      message: message.to_string(),
+    })
+  }
+
+  pub fn at_call_site(self, token: &Token) -> LoxError {
+    if let LoxErrorType::NativeErr { message } = &self.0 {
+      return LoxError::runtime_error(token, message);
+    }
+
+    self
+  }
+
   pub fn parse_error(token: &Token, message: &str) -> LoxError {
     let err = LoxError(LoxErrorType::ParseErr {
       token: token.clone(),
@@ -58,15 +127,72 @@ impl LoxError {
     })
   }
 
+  // runtime errors are only reported once they escape the interpreter,
+  // so that the offending source line can be attached first
   pub fn runtime_error(token: &Token, message: &str) -> LoxError {
-    let err = LoxError(LoxErrorType::RuntimeErr {
-      token: token.clone(),
+    LoxError(LoxErrorType::RuntimeErr {
+      token: token.clone().into(),
       message: message.to_string(),
+      span: None,
+      source_line: None,
+    })
+  }
+
+  // only an error raised at `token` itself is widened, not one passed up
+  // from a sub-expression or from inside a called function
+  pub fn with_span(mut self, token: &Token, span: Span) -> LoxError {
+    if let LoxErrorType::RuntimeErr {
+      token: t,
+      span: s @ None,
+      ..
+    } = &mut self.0
+    {
+      if t.get_offset() == token.get_offset()
+        && t.get_line() == token.get_line()
+        && t.get_column() == token.get_column()
+      {
+        *s = Some(span);
+      }
+    }
+
+    self
+  }
+
+  pub fn report_runtime_error(self, source: &str) -> LoxError {
+    let LoxError(LoxErrorType::RuntimeErr {
+      token,
+      message,
+      span,
+      ..
+    }) = self
+    else {
+      return self;
+    };
+
+    let source_line = source
+      .lines()
+      .nth(token.get_line().saturating_sub(1))
+      .map(str::to_string);
+    let err = LoxError(LoxErrorType::RuntimeErr {
+      token,
+      message,
+      span,
+      source_line,
     });
     err.report();
     err
   }
 
+  // warnings are reported straight away and never stop execution
+  pub fn warning(token: &Token, message: &str) -> String {
+    let line = token.get_line();
+    let lexeme = token.get_lexeme();
+
+    let warning = format!("[line {line}] Warning at '{lexeme}': {message}");
+    eprintln!("{warning}");
+    warning
+  }
+
   pub fn system_error(message: &str) -> LoxError {
     let err = LoxError(LoxErrorType::SystemErr {
       message: message.to_string(),
@@ -95,6 +221,14 @@ impl LoxError {
     false
   }
 
+  pub fn is_system_error(&self) -> bool {
+    if matches!(self.0, LoxErrorType::SystemErr { .. }) {
+      return true;
+    }
+
+    false
+  }
+
   pub fn get_return_value(&self) -> Result<Literal, LoxError> {
     if let LoxError(LoxErrorType::Return { value }) = self {
       return Ok(value.clone());
@@ -103,6 +237,26 @@ impl LoxError {
     Err(self.clone())
   }
 
+  pub fn get_message(&self) -> Option<&str> {
+    match &self.0 {
+      LoxErrorType::GeneralErr { message, .. }
+      | LoxErrorType::NativeErr { message }
+      | LoxErrorType::ParseErr { message, .. }
+      | LoxErrorType::RuntimeErr { message, .. }
+      | LoxErrorType::SystemErr { message } => Some(message),
+      _ => None,
+    }
+  }
+
+  pub fn get_line(&self) -> Option<usize> {
+    match &self.0 {
+      LoxErrorType::GeneralErr { line, .. } => Some(*line),
+      LoxErrorType::ParseErr { token, .. } => Some(token.get_line()),
+      LoxErrorType::RuntimeErr { token, .. } => Some(token.get_line()),
+      _ => None,
+    }
+  }
+
   fn report(&self) {
     match self {
       LoxError(LoxErrorType::GeneralErr { line, message }) => {
@@ -118,14 +272,181 @@ impl LoxError {
 
         eprintln!("[line {line}] Error at {location}: {message}");
       }
-      LoxError(LoxErrorType::RuntimeErr { token, message }) => {
+      LoxError(LoxErrorType::RuntimeErr {
+        token,
+        message,
+        span,
+        source_line,
+      }) => {
         let line = token.get_line();
 
         eprintln!("{message}");
         eprintln!("[line {line}]");
+
+        if let Some(s) = source_line
+          .as_ref()
+          .and_then(|l| LoxError::snippet(token, l, *span))
+        {
+          eprint!("{s}");
+        }
       }
       LoxError(LoxErrorType::SystemErr { message }) => eprintln!("{message}"),
       _ => (),
     }
   }
+
+  // the part of `span` on the token's line, as a 1-based column and a width;
+  // a span that began on an earlier line leaves out the indentation
+  fn underline(token: &Token, source_line: &str, (start, end): Span) -> Option<(usize, usize)> {
+    let line_start = token
+      .get_offset()
+      .checked_sub(token.get_column().checked_sub(1)?)?;
+    let indent = source_line
+      .chars()
+      .take_while(|c| c.is_whitespace())
+      .count();
+
+    let start = start.max(line_start + indent);
+    let end = end.min(line_start + source_line.chars().count());
+    (start < end).then(|| (start - line_start + 1, end - start))
+  }
+
+  fn snippet(token: &Token, source_line: &str, span: Option<Span>) -> Option<String> {
+    let column = token.get_column();
+    let lexeme = token.get_lexeme();
+
+    // a token from an earlier REPL input will not match the current source
+    if column.eq(&0)
+      || !source_line
+        .chars()
+        .skip(column - 1)
+        .collect::<String>()
+        .starts_with(lexeme.as_str())
+    {
+      return None;
+    }
+
+    let line = token.get_line().to_string();
+    let gutter = " ".repeat(line.len());
+    let (column, width) = span
+      .and_then(|s| LoxError::underline(token, source_line, s))
+      .unwrap_or((column, lexeme.chars().count().max(1)));
+    let padding = " ".repeat(column - 1);
+    let carets = "^".repeat(width);
+
+    Some(format!(
+      "{gutter} |\n{line} | {source_line}\n{gutter} | {padding}{carets}\n"
+    ))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::interpreter::{test::run, Interpreter};
+
+  #[test]
+  fn test_snippet() {
+    let token = Token::new_with_column(TokenType::Minus, "-", None, 12, 7);
+    assert_eq!(
+      LoxError::snippet(&token, "print -\"a\";", None),
+      Some("   |\n12 | print -\"a\";\n   |       ^\n".to_string())
+    );
+
+    let token = Token::new_with_column(TokenType::Identifier, "foo", None, 1, 1);
+    assert_eq!(
+      LoxError::snippet(&token, "foo();", None),
+      Some("  |\n1 | foo();\n  | ^^^\n".to_string())
+    );
+  }
+
+  #[test]
+  fn test_snippet_mismatched_source() {
+    let token = Token::new_with_column(TokenType::Identifier, "foo", None, 1, 1);
+    assert_eq!(LoxError::snippet(&token, "bar();", None), None);
+
+    let token = Token::new(TokenType::Identifier, "foo", None, 1);
+    assert_eq!(LoxError::snippet(&token, "foo();", None), None);
+  }
+
+  #[test]
+  fn test_snippet_underlines_span() {
+    // `var a;\nprint "x" * nil;`, where the second line starts at offset 7
+    let token = Token::new_with_column(TokenType::Star, "*", None, 2, 11).with_offset(17);
+    assert_eq!(
+      LoxError::snippet(&token, "print \"x\" * nil;", Some((13, 22))),
+      Some("  |\n2 | print \"x\" * nil;\n  |       ^^^^^^^^^\n".to_string())
+    );
+
+    // `print f(1,\n  2);` only underlines the part on the error's line
+    let bracket = Token::new_with_column(TokenType::RightBracket, ")", None, 2, 4).with_offset(14);
+    assert_eq!(
+      LoxError::underline(&bracket, "  2);", (6, 15)),
+      Some((3, 2))
+    );
+    let bracket = Token::new_with_column(TokenType::LeftBracket, "(", None, 1, 8).with_offset(7);
+    assert_eq!(
+      LoxError::underline(&bracket, "print f(1,", (6, 15)),
+      Some((7, 4))
+    );
+  }
+
+  #[test]
+  fn test_snippet_from_interpreter() {
+    let source = "var a;\nprint \"x\" * nil;";
+    let err = run(&Interpreter::new(), source)
+      .unwrap_err()
+      .report_runtime_error(source);
+
+    let LoxError(LoxErrorType::RuntimeErr {
+      token,
+      span,
+      source_line: Some(line),
+      ..
+    }) = &err
+    else {
+      panic!("expected a runtime error with its source line");
+    };
+    assert_eq!(
+      LoxError::snippet(token, line, *span),
+      Some("  |\n2 | print \"x\" * nil;\n  |       ^^^^^^^^^\n".to_string())
+    );
+  }
+
+  #[test]
+  fn test_with_span() {
+    let operator = Token::new_with_column(TokenType::Star, "*", None, 1, 11).with_offset(10);
+    let other = Token::new_with_column(TokenType::Minus, "-", None, 1, 7).with_offset(6);
+
+    let err = LoxError::runtime_error(&operator, "Operands must be numbers.")
+      .with_span(&operator, (6, 14))
+      .with_span(&operator, (0, 20));
+    assert!(matches!(
+      err,
+      LoxError(LoxErrorType::RuntimeErr {
+        span: Some((6, 14)),
+        ..
+      })
+    ));
+
+    // an error raised elsewhere keeps its own position
+    let err =
+      LoxError::runtime_error(&other, "Operand must be a number.").with_span(&operator, (6, 14));
+    assert!(matches!(
+      err,
+      LoxError(LoxErrorType::RuntimeErr { span: None, .. })
+    ));
+  }
+
+  #[test]
+  fn test_report_runtime_error_attaches_source() {
+    let token = Token::new_with_column(TokenType::Identifier, "b", None, 2, 7);
+    let err = LoxError::runtime_error(&token, "Undefined variable 'b'.")
+      .report_runtime_error("var a;\nprint b;");
+
+    assert!(matches!(
+      err,
+      LoxError(LoxErrorType::RuntimeErr { source_line: Some(s), .. }) if s == "print b;"
+    ));
+  }
 }
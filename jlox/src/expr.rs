@@ -6,15 +6,22 @@ use std::rc::Rc;
 use crate::error::*;
 use crate::token::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Expr {
+  Array(Rc<ArrayExpr>),
   Assign(Rc<AssignExpr>),
   Binary(Rc<BinaryExpr>),
   Call(Rc<CallExpr>),
+  Destructure(Rc<DestructureExpr>),
   Get(Rc<GetExpr>),
   Grouping(Rc<GroupingExpr>),
+  If(Rc<IfExpr>),
+  Index(Rc<IndexExpr>),
+  IndexSet(Rc<IndexSetExpr>),
   Literal(Rc<LiteralExpr>),
   Logical(Rc<LogicalExpr>),
+  Map(Rc<MapExpr>),
+  Postfix(Rc<PostfixExpr>),
   Set(Rc<SetExpr>),
   Super(Rc<SuperExpr>),
   This(Rc<ThisExpr>),
@@ -25,13 +32,20 @@ pub enum Expr {
 impl Expr {
   pub fn accept<T>(&self, wrapper: &Rc<Expr>, expr_visitor: &dyn ExprVisitor<T>) -> Result<T, LoxError> {
     match self {
+      Expr::Array(expr) => expr_visitor.visit_array_expr(wrapper, expr),
       Expr::Assign(expr) => expr_visitor.visit_assign_expr(wrapper, expr),
       Expr::Binary(expr) => expr_visitor.visit_binary_expr(wrapper, expr),
       Expr::Call(expr) => expr_visitor.visit_call_expr(wrapper, expr),
+      Expr::Destructure(expr) => expr_visitor.visit_destructure_expr(wrapper, expr),
       Expr::Get(expr) => expr_visitor.visit_get_expr(wrapper, expr),
       Expr::Grouping(expr) => expr_visitor.visit_grouping_expr(wrapper, expr),
+      Expr::If(expr) => expr_visitor.visit_if_expr(wrapper, expr),
+      Expr::Index(expr) => expr_visitor.visit_index_expr(wrapper, expr),
+      Expr::IndexSet(expr) => expr_visitor.visit_index_set_expr(wrapper, expr),
       Expr::Literal(expr) => expr_visitor.visit_literal_expr(wrapper, expr),
       Expr::Logical(expr) => expr_visitor.visit_logical_expr(wrapper, expr),
+      Expr::Map(expr) => expr_visitor.visit_map_expr(wrapper, expr),
+      Expr::Postfix(expr) => expr_visitor.visit_postfix_expr(wrapper, expr),
       Expr::Set(expr) => expr_visitor.visit_set_expr(wrapper, expr),
       Expr::Super(expr) => expr_visitor.visit_super_expr(wrapper, expr),
       Expr::This(expr) => expr_visitor.visit_this_expr(wrapper, expr),
@@ -44,13 +58,20 @@ impl Expr {
 impl PartialEq for Expr {
   fn eq(&self, other: &Expr) -> bool {
     match (self, other) {
+      (Expr::Array(l0), Expr::Array(r0)) => Rc::ptr_eq(l0, r0),
       (Expr::Assign(l0), Expr::Assign(r0)) => Rc::ptr_eq(l0, r0),
       (Expr::Binary(l0), Expr::Binary(r0)) => Rc::ptr_eq(l0, r0),
       (Expr::Call(l0), Expr::Call(r0)) => Rc::ptr_eq(l0, r0),
+      (Expr::Destructure(l0), Expr::Destructure(r0)) => Rc::ptr_eq(l0, r0),
       (Expr::Get(l0), Expr::Get(r0)) => Rc::ptr_eq(l0, r0),
       (Expr::Grouping(l0), Expr::Grouping(r0)) => Rc::ptr_eq(l0, r0),
+      (Expr::If(l0), Expr::If(r0)) => Rc::ptr_eq(l0, r0),
+      (Expr::Index(l0), Expr::Index(r0)) => Rc::ptr_eq(l0, r0),
+      (Expr::IndexSet(l0), Expr::IndexSet(r0)) => Rc::ptr_eq(l0, r0),
       (Expr::Literal(l0), Expr::Literal(r0)) => Rc::ptr_eq(l0, r0),
       (Expr::Logical(l0), Expr::Logical(r0)) => Rc::ptr_eq(l0, r0),
+      (Expr::Map(l0), Expr::Map(r0)) => Rc::ptr_eq(l0, r0),
+      (Expr::Postfix(l0), Expr::Postfix(r0)) => Rc::ptr_eq(l0, r0),
       (Expr::Set(l0), Expr::Set(r0)) => Rc::ptr_eq(l0, r0),
       (Expr::Super(l0), Expr::Super(r0)) => Rc::ptr_eq(l0, r0),
       (Expr::This(l0), Expr::This(r0)) => Rc::ptr_eq(l0, r0),
@@ -66,9 +87,36 @@ impl Eq for Expr {}
 impl Hash for Expr {
   fn hash<H: Hasher>(&self, state: &mut H) {
     discriminant(self).hash(state);
+    match self {
+      Expr::Array(n0) => Rc::as_ptr(n0).hash(state),
+      Expr::Assign(n0) => Rc::as_ptr(n0).hash(state),
+      Expr::Binary(n0) => Rc::as_ptr(n0).hash(state),
+      Expr::Call(n0) => Rc::as_ptr(n0).hash(state),
+      Expr::Destructure(n0) => Rc::as_ptr(n0).hash(state),
+      Expr::Get(n0) => Rc::as_ptr(n0).hash(state),
+      Expr::Grouping(n0) => Rc::as_ptr(n0).hash(state),
+      Expr::If(n0) => Rc::as_ptr(n0).hash(state),
+      Expr::Index(n0) => Rc::as_ptr(n0).hash(state),
+      Expr::IndexSet(n0) => Rc::as_ptr(n0).hash(state),
+      Expr::Literal(n0) => Rc::as_ptr(n0).hash(state),
+      Expr::Logical(n0) => Rc::as_ptr(n0).hash(state),
+      Expr::Map(n0) => Rc::as_ptr(n0).hash(state),
+      Expr::Postfix(n0) => Rc::as_ptr(n0).hash(state),
+      Expr::Set(n0) => Rc::as_ptr(n0).hash(state),
+      Expr::Super(n0) => Rc::as_ptr(n0).hash(state),
+      Expr::This(n0) => Rc::as_ptr(n0).hash(state),
+      Expr::Unary(n0) => Rc::as_ptr(n0).hash(state),
+      Expr::Variable(n0) => Rc::as_ptr(n0).hash(state),
+    }
   }
 }
 
+#[derive(Debug)]
+pub struct ArrayExpr {
+  pub bracket: Token,
+  pub elements: Rc<Vec<Rc<Expr>>>,
+}
+
 #[derive(Debug)]
 pub struct AssignExpr {
   pub name: Token,
@@ -80,6 +128,7 @@ pub struct BinaryExpr {
   pub left: Rc<Expr>,
   pub operator: Token,
   pub right: Rc<Expr>,
+  pub span: Span,
 }
 
 #[derive(Debug)]
@@ -87,6 +136,14 @@ pub struct CallExpr {
   pub callee: Rc<Expr>,
   pub bracket: Token,
   pub arguments: Rc<Vec<Rc<Expr>>>,
+  pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct DestructureExpr {
+  pub bracket: Token,
+  pub targets: Rc<Vec<Rc<Expr>>>,
+  pub value: Rc<Expr>,
 }
 
 #[derive(Debug)]
@@ -100,6 +157,31 @@ pub struct GroupingExpr {
   pub expression: Rc<Expr>,
 }
 
+#[derive(Debug)]
+pub struct IfExpr {
+  pub keyword: Token,
+  pub condition: Rc<Expr>,
+  pub then_branch: Rc<Expr>,
+  pub else_branch: Rc<Expr>,
+}
+
+#[derive(Debug)]
+pub struct IndexExpr {
+  pub object: Rc<Expr>,
+  pub bracket: Token,
+  pub index: Option<Rc<Expr>>,
+  pub end: Option<Rc<Expr>>,
+  pub is_slice: bool,
+}
+
+#[derive(Debug)]
+pub struct IndexSetExpr {
+  pub object: Rc<Expr>,
+  pub bracket: Token,
+  pub index: Rc<Expr>,
+  pub value: Rc<Expr>,
+}
+
 #[derive(Debug)]
 pub struct LiteralExpr {
   pub value: Option<Literal>,
@@ -112,6 +194,19 @@ pub struct LogicalExpr {
   pub right: Rc<Expr>,
 }
 
+#[derive(Debug)]
+pub struct MapExpr {
+  pub brace: Token,
+  pub keys: Rc<Vec<Rc<Expr>>>,
+  pub values: Rc<Vec<Rc<Expr>>>,
+}
+
+#[derive(Debug)]
+pub struct PostfixExpr {
+  pub target: Rc<Expr>,
+  pub operator: Token,
+}
+
 #[derive(Debug)]
 pub struct SetExpr {
   pub object: Rc<Expr>,
@@ -134,6 +229,7 @@ pub struct ThisExpr {
 pub struct UnaryExpr {
   pub operator: Token,
   pub right: Rc<Expr>,
+  pub span: Span,
 }
 
 #[derive(Debug)]
@@ -142,13 +238,20 @@ pub struct VariableExpr {
 }
 
 pub trait ExprVisitor<T> {
+  fn visit_array_expr(&self, wrapper: &Rc<Expr>, expr: &ArrayExpr) -> Result<T, LoxError>;
   fn visit_assign_expr(&self, wrapper: &Rc<Expr>, expr: &AssignExpr) -> Result<T, LoxError>;
   fn visit_binary_expr(&self, wrapper: &Rc<Expr>, expr: &BinaryExpr) -> Result<T, LoxError>;
   fn visit_call_expr(&self, wrapper: &Rc<Expr>, expr: &CallExpr) -> Result<T, LoxError>;
+  fn visit_destructure_expr(&self, wrapper: &Rc<Expr>, expr: &DestructureExpr) -> Result<T, LoxError>;
   fn visit_get_expr(&self, wrapper: &Rc<Expr>, expr: &GetExpr) -> Result<T, LoxError>;
   fn visit_grouping_expr(&self, wrapper: &Rc<Expr>, expr: &GroupingExpr) -> Result<T, LoxError>;
+  fn visit_if_expr(&self, wrapper: &Rc<Expr>, expr: &IfExpr) -> Result<T, LoxError>;
+  fn visit_index_expr(&self, wrapper: &Rc<Expr>, expr: &IndexExpr) -> Result<T, LoxError>;
+  fn visit_index_set_expr(&self, wrapper: &Rc<Expr>, expr: &IndexSetExpr) -> Result<T, LoxError>;
   fn visit_literal_expr(&self, wrapper: &Rc<Expr>, expr: &LiteralExpr) -> Result<T, LoxError>;
   fn visit_logical_expr(&self, wrapper: &Rc<Expr>, expr: &LogicalExpr) -> Result<T, LoxError>;
+  fn visit_map_expr(&self, wrapper: &Rc<Expr>, expr: &MapExpr) -> Result<T, LoxError>;
+  fn visit_postfix_expr(&self, wrapper: &Rc<Expr>, expr: &PostfixExpr) -> Result<T, LoxError>;
   fn visit_set_expr(&self, wrapper: &Rc<Expr>, expr: &SetExpr) -> Result<T, LoxError>;
   fn visit_super_expr(&self, wrapper: &Rc<Expr>, expr: &SuperExpr) -> Result<T, LoxError>;
   fn visit_this_expr(&self, wrapper: &Rc<Expr>, expr: &ThisExpr) -> Result<T, LoxError>;
@@ -1,73 +1,389 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+  cell::{Cell, RefCell},
+  collections::HashMap,
+  io::{stderr, stdout, BufWriter, Write},
+  rc::Rc,
+  time::{Duration, Instant, SystemTime},
+};
 
 use crate::{
   environment::*, error::*, expr::*, lox_callable::*, lox_class::*, lox_function::*,
-  lox_native_function::*, stmt::*, token::*,
+  lox_native_function::*, resolver::*, stmt::*, token::*,
 };
 
-#[derive(Default, Clone)]
 pub struct Interpreter {
   globals: Rc<RefCell<Environment>>,
   environment: RefCell<Rc<RefCell<Environment>>>,
   locals: RefCell<HashMap<Rc<Expr>, usize>>,
+  output: RefCell<Box<dyn Write>>,
+  error_output: RefCell<Box<dyn Write>>,
+  line_buffered: Cell<bool>,
+  source: RefCell<String>,
+  start: Instant,
+  evaluation_depth: Cell<usize>,
+  call_depth: Cell<usize>,
+  max_call_depth: usize,
+  strict_concat: bool,
+  io_enabled: bool,
+  random_state: RefCell<u64>,
+  symbol_counter: RefCell<usize>,
+}
+
+// deep recursion in Lox code would otherwise overflow the native stack; the
+// default leaves room to spare on an ordinary 8MB thread, even unoptimised
+pub const MAX_CALL_DEPTH: usize = 128;
+
+// expressions and statements nest too, a few levels for every call, so
+// evaluation is limited in proportion to the call depth
+const EVALUATIONS_PER_CALL: usize = 4;
+pub const MAX_EVALUATION_DEPTH: usize = EVALUATIONS_PER_CALL * MAX_CALL_DEPTH;
+
+// a thread with this much stack can take 32 times the default depth
+pub const STACK_SIZE: usize = 256 * 1024 * 1024;
+pub const STACK_SIZE_MAX_CALL_DEPTH: usize = 32 * MAX_CALL_DEPTH;
+
+impl Default for Interpreter {
+  fn default() -> Interpreter {
+    Interpreter::new()
+  }
 }
 
 impl Interpreter {
   pub fn new() -> Interpreter {
     let globals = Rc::new(RefCell::new(Environment::new()));
 
-    globals.borrow_mut().define(
-      "clock",
-      Literal::NativeFunction(
-        LoxNativeFunction {
-          fun: Rc::new(Clock),
-        }
-        .into(),
-      ),
-    );
+    let natives: Vec<(&str, Rc<dyn LoxCallable>)> = vec![
+      ("clock", Rc::new(Clock)),
+      ("clock_millis", Rc::new(ClockMillis)),
+      ("sleep", Rc::new(Sleep)),
+      ("memoize", Rc::new(Memoize)),
+      ("setLineBuffered", Rc::new(SetLineBuffered)),
+      ("assert", Rc::new(Assert)),
+      ("error", Rc::new(Raise)),
+      ("fields", Rc::new(Fields)),
+      ("print_err", Rc::new(PrintErr)),
+      ("fmt_number", Rc::new(FmtNumber)),
+      ("chr", Rc::new(Chr)),
+      ("ord", Rc::new(Ordinal)),
+      ("copy", Rc::new(CopyInstance)),
+      ("approx", Rc::new(Approx)),
+      ("random", Rc::new(Random)),
+      ("random_seed", Rc::new(RandomSeed)),
+      ("gensym", Rc::new(Gensym)),
+      ("min", Rc::new(Min)),
+      ("max", Rc::new(Max)),
+      ("describe", Rc::new(Describe)),
+      ("trim", Rc::new(Trim)),
+      ("upper", Rc::new(Upper)),
+      ("lower", Rc::new(Lower)),
+      ("split", Rc::new(Split)),
+      ("contains", Rc::new(Contains)),
+      ("index_of", Rc::new(IndexOf)),
+      ("array_push", Rc::new(ArrayPush)),
+      ("array_pop", Rc::new(ArrayPop)),
+      ("array_len", Rc::new(ArrayLen)),
+      ("to_json", Rc::new(ToJson)),
+      ("debug", Rc::new(DebugFormat)),
+      ("read_file", Rc::new(ReadFile)),
+      ("write_file", Rc::new(WriteFile)),
+    ];
+    let predicates: [(&str, Accepts); 6] = [
+      ("is_nil", |v| matches!(v, Literal::Nil)),
+      ("is_number", |v| matches!(v, Literal::Number(_))),
+      ("is_string", |v| matches!(v, Literal::String(_))),
+      ("is_bool", |v| matches!(v, Literal::Boolean(_))),
+      ("is_callable", |v| {
+        matches!(
+          v,
+          Literal::Function(_) | Literal::NativeFunction(_) | Literal::Class(_)
+        )
+      }),
+      ("is_instance", |v| matches!(v, Literal::Instance(_))),
+    ];
+    let natives = natives.into_iter().chain(predicates.map(|(name, accepts)| {
+      (
+        name,
+        Rc::new(TypePredicate { name, accepts }) as Rc<dyn LoxCallable>,
+      )
+    }));
+    for (name, fun) in natives {
+      globals.borrow_mut().define(
+        name,
+        Literal::NativeFunction(LoxNativeFunction { fun }.into()),
+      );
+    }
 
     Interpreter {
       globals: globals.clone(),
       environment: RefCell::new(globals),
       locals: RefCell::new(HashMap::new()),
+      output: RefCell::new(Box::new(BufWriter::new(stdout()))),
+      error_output: RefCell::new(Box::new(stderr())),
+      line_buffered: Cell::new(true),
+      source: RefCell::new(String::new()),
+      start: Instant::now(),
+      evaluation_depth: Cell::new(0),
+      call_depth: Cell::new(0),
+      max_call_depth: MAX_CALL_DEPTH,
+      strict_concat: false,
+      io_enabled: false,
+      random_state: RefCell::new(Interpreter::scramble(
+        SystemTime::now()
+          .duration_since(SystemTime::UNIX_EPOCH)
+          .map_or(0, |d| d.as_nanos() as u64),
+      )),
+      symbol_counter: RefCell::new(0),
+    }
+  }
+
+  // the error is raised as a native error so it points at the call site
+  pub fn enter_call(&self) -> Result<(), LoxError> {
+    if self.call_depth.get() >= self.max_call_depth {
+      return Err(LoxError::new_native_error(
+        "Stack overflow: maximum call depth exceeded.",
+      ));
     }
+
+    self.call_depth.set(self.call_depth.get() + 1);
+    Ok(())
+  }
+
+  pub fn exit_call(&self) {
+    self.call_depth.set(self.call_depth.get() - 1);
+  }
+
+  // monotonic time since the interpreter was created
+  pub fn elapsed(&self) -> Duration {
+    self.start.elapsed()
+  }
+
+  // splitmix64, so that nearby seeds still start far apart; xorshift also
+  // needs a state other than zero, which this never returns for any seed
+  fn scramble(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (z ^ (z >> 31)).max(1)
+  }
+
+  pub fn seed_random(&self, seed: u64) {
+    self.random_state.replace(Interpreter::scramble(seed));
+  }
+
+  // xorshift64*, in [0, 1) from the top 53 bits
+  pub fn next_random(&self) -> f64 {
+    let mut state = self.random_state.borrow_mut();
+    *state ^= *state >> 12;
+    *state ^= *state << 25;
+    *state ^= *state >> 27;
+    let bits = state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+    bits as f64 / (1_u64 << 53) as f64
+  }
+
+  pub fn next_symbol(&self) -> String {
+    let mut counter = self.symbol_counter.borrow_mut();
+    *counter += 1;
+    format!("__sym_{}", *counter)
+  }
+
+  pub fn with_output(self, output: impl Write + 'static) -> Interpreter {
+    self.output.replace(Box::new(output));
+    self
+  }
+
+  pub fn with_max_depth(mut self, max_call_depth: usize) -> Interpreter {
+    self.max_call_depth = max_call_depth;
+    self
+  }
+
+  pub fn max_evaluation_depth(&self) -> usize {
+    self.max_call_depth.saturating_mul(EVALUATIONS_PER_CALL)
+  }
+
+  // when strict, `+` no longer turns a number into a string
+  pub fn with_strict_concat(mut self, strict_concat: bool) -> Interpreter {
+    self.strict_concat = strict_concat;
+    self
+  }
+
+  // file natives refuse to run unless this is called
+  pub fn enable_io(mut self) -> Interpreter {
+    self.io_enabled = true;
+    self
+  }
+
+  pub fn is_io_enabled(&self) -> bool {
+    self.io_enabled
+  }
+
+  pub fn with_error_output(self, error_output: impl Write + 'static) -> Interpreter {
+    self.error_output.replace(Box::new(error_output));
+    self
   }
 
   pub fn interpret(&self, statements: &Rc<&[Rc<Stmt>]>) -> Result<(), LoxError> {
-    for statement in statements.iter() {
-      self.execute(statement)?;
+    self.interpret_with_result(statements).map(|_| ())
+  }
+
+  // `interpret` alone only finds globals; anything local needs resolving first
+  pub fn resolve_and_interpret(&self, statements: &Rc<&[Rc<Stmt>]>) -> Result<(), LoxError> {
+    Resolver::new(self).resolve(statements)?;
+    self.interpret(statements)
+  }
+
+  // the value of the final statement, if it is an expression statement
+  pub fn interpret_with_result(
+    &self,
+    statements: &Rc<&[Rc<Stmt>]>,
+  ) -> Result<Option<Literal>, LoxError> {
+    let res = self
+      .hoist_functions(statements)
+      .and_then(|_| match statements.split_last() {
+        Some((last, rest)) => {
+          rest
+            .iter()
+            .try_for_each(|s| self.execute(s))
+            .and_then(|_| match last.as_ref() {
+              Stmt::Expression(e) => self.evaluate(&e.expression).map(Some),
+              _ => self.execute(last).map(|_| None),
+            })
+        }
+        None => Ok(None),
+      });
+
+    self.flush_output()?;
+    res.map_err(|e| e.report_runtime_error(&self.source.borrow()))
+  }
+
+  // the source is only used to show the offending line in runtime errors
+  pub fn set_source(&self, source: &str) {
+    self.source.replace(source.to_string());
+  }
+
+  pub fn set_line_buffered(&self, line_buffered: bool) -> Result<(), LoxError> {
+    self.line_buffered.set(line_buffered);
+
+    if line_buffered {
+      return self.flush_output();
+    }
+
+    Ok(())
+  }
+
+  fn write_output(&self, value: &Literal) -> Result<(), LoxError> {
+    writeln!(self.output.borrow_mut(), "{value}")
+      .map_err(|_| LoxError::system_error("COULD NOT WRITE OUTPUT."))?;
+
+    if self.line_buffered.get() {
+      return self.flush_output();
     }
 
     Ok(())
   }
 
+  // diagnostics are never buffered
+  pub fn write_error(&self, value: &Literal) -> Result<(), LoxError> {
+    let mut error_output = self.error_output.borrow_mut();
+    writeln!(error_output, "{value}")
+      .and_then(|_| error_output.flush())
+      .map_err(|_| LoxError::system_error("COULD NOT WRITE ERROR OUTPUT."))
+  }
+
+  fn flush_output(&self) -> Result<(), LoxError> {
+    self
+      .output
+      .borrow_mut()
+      .flush()
+      .map_err(|_| LoxError::system_error("COULD NOT FLUSH OUTPUT."))
+  }
+
   pub fn get_globals(&self) -> &Rc<RefCell<Environment>> {
     &self.globals
   }
 
-  pub fn print_environment(&self) {
-    dbg!(&self.environment);
+  pub fn globals_snapshot(&self) -> HashMap<String, Literal> {
+    self
+      .globals
+      .borrow()
+      .iter_values()
+      .map(|(name, value)| (name.clone(), value.clone()))
+      .collect()
+  }
+
+  pub fn print_environment(&self) -> Result<(), LoxError> {
+    let dump = self.environment.borrow().borrow().dump();
+    write!(self.output.borrow_mut(), "{dump}")
+      .map_err(|_| LoxError::system_error("COULD NOT WRITE OUTPUT."))?;
+    self.flush_output()
   }
 
   fn evaluate(&self, expr: &Rc<Expr>) -> Result<Literal, LoxError> {
-    expr.accept(expr, self)
+    // a literal never recurses, so it is fine to evaluate one past the limit
+    if self.evaluation_depth.get() >= self.max_evaluation_depth() {
+      if let Some(t) = Interpreter::token_of(expr) {
+        return Err(LoxError::runtime_error(t, "Stack overflow."));
+      }
+    }
+
+    self.evaluation_depth.set(self.evaluation_depth.get() + 1);
+    let value = expr
+      .accept(expr, self)
+      .map_err(|e| Interpreter::with_span(expr, e));
+    self.evaluation_depth.set(self.evaluation_depth.get() - 1);
+    value
   }
 
-  fn execute(&self, stmt: &Rc<Stmt>) -> Result<(), LoxError> {
-    stmt.accept(stmt, self)
+  // errors from an operator or a call are underlined across the whole expression
+  fn with_span(expr: &Expr, err: LoxError) -> LoxError {
+    match expr {
+      Expr::Binary(e) => err.with_span(&e.operator, e.span),
+      Expr::Call(e) => err.with_span(&e.bracket, e.span),
+      Expr::Unary(e) => err.with_span(&e.operator, e.span),
+      _ => err,
+    }
   }
 
-  pub fn resolve(&self, expr: &Rc<Expr>, depth: usize) {
-    self.locals.borrow_mut().insert(expr.clone(), depth);
+  fn token_of(expr: &Expr) -> Option<&Token> {
+    match expr {
+      Expr::Array(e) => Some(&e.bracket),
+      Expr::Assign(e) => Some(&e.name),
+      Expr::Binary(e) => Some(&e.operator),
+      Expr::Call(e) => Some(&e.bracket),
+      Expr::Destructure(e) => Some(&e.bracket),
+      Expr::Get(e) => Some(&e.name),
+      Expr::Grouping(e) => Interpreter::token_of(&e.expression),
+      Expr::If(e) => Some(&e.keyword),
+      Expr::Index(e) => Some(&e.bracket),
+      Expr::IndexSet(e) => Some(&e.bracket),
+      Expr::Literal(_) => None,
+      Expr::Logical(e) => Some(&e.operator),
+      Expr::Map(e) => Some(&e.brace),
+      Expr::Postfix(e) => Some(&e.operator),
+      Expr::Set(e) => Some(&e.name),
+      Expr::Super(e) => Some(&e.keyword),
+      Expr::This(e) => Some(&e.keyword),
+      Expr::Unary(e) => Some(&e.operator),
+      Expr::Variable(e) => Some(&e.name),
+    }
+  }
+
+  // nested statements and blocks recurse on the native stack just as
+  // expressions do, but have no token of their own, so the error points at
+  // the call that got this deep
+  fn execute(&self, stmt: &Rc<Stmt>) -> Result<(), LoxError> {
+    if self.evaluation_depth.get() >= self.max_evaluation_depth() {
+      return Err(LoxError::new_native_error("Stack overflow."));
+    }
+
+    self.evaluation_depth.set(self.evaluation_depth.get() + 1);
+    let res = stmt.accept(stmt, self);
+    self.evaluation_depth.set(self.evaluation_depth.get() - 1);
+    res
   }
 
   fn look_up_variable(&self, name: &Token, expr: &Rc<Expr>) -> Result<Literal, LoxError> {
     if let Some(&distance) = self.locals.borrow().get(expr) {
-      return self
-        .environment
-        .borrow()
-        .borrow()
-        .get_at(distance, name.get_lexeme());
+      return Environment::get_at(&self.environment.borrow(), distance, name.get_lexeme());
     }
 
     self.globals.borrow().get(name)
@@ -79,12 +395,219 @@ impl Interpreter {
     environment: Environment,
   ) -> Result<(), LoxError> {
     let previous = self.environment.replace(RefCell::new(environment).into());
-    let res = statements.iter().try_for_each(|s| self.execute(s));
+    let res = self
+      .hoist_functions(statements)
+      .and_then(|_| statements.iter().try_for_each(|s| self.execute(s)));
 
     self.environment.replace(previous);
     res
   }
 
+  // defines every function in the block up front so that it can be called
+  // before its declaration is reached; when a name is declared more than once,
+  // the last definition is the one seen until the first declaration runs, and
+  // each declaration then runs again in order, taking effect from there on
+  fn hoist_functions(&self, statements: &[Rc<Stmt>]) -> Result<(), LoxError> {
+    statements
+      .iter()
+      .filter(|s| matches!(s.as_ref(), Stmt::Function(_)))
+      .try_for_each(|s| self.execute(s))
+  }
+
+  // whether a loop should keep going after its body, consuming any break
+  // or continue aimed at it and passing the rest on to enclosing loops
+  fn continue_loop(label: &Option<Token>, body: Result<(), LoxError>) -> Result<bool, LoxError> {
+    match body {
+      Err(e) if e.is_break_for(label) => Ok(false),
+      Err(e) if e.is_continue_for(label) => Ok(true),
+      body => body.map(|_| true),
+    }
+  }
+
+  fn map_key(token: &Token, key: &Literal) -> Result<MapKey, LoxError> {
+    MapKey::from_literal(key).ok_or_else(|| {
+      LoxError::runtime_error(token, "Map keys must be numbers, strings or booleans.")
+    })
+  }
+
+  fn slice(&self, expr: &IndexExpr, object: &Literal) -> Result<Literal, LoxError> {
+    let start = expr.index.as_ref().map(|i| self.evaluate(i)).transpose()?;
+    let end = expr.end.as_ref().map(|e| self.evaluate(e)).transpose()?;
+
+    match object {
+      Literal::Array(a) => {
+        let (start, end) =
+          Interpreter::slice_bounds(&expr.bracket, &start, &end, a.borrow().len())?;
+        let elements = a.borrow()[start..end].to_vec();
+        Ok(Literal::Array(RefCell::new(elements).into()))
+      }
+      Literal::String(s) => {
+        let chars = s.chars().collect::<Vec<char>>();
+        let (start, end) = Interpreter::slice_bounds(&expr.bracket, &start, &end, chars.len())?;
+        Ok(Literal::String(chars[start..end].iter().collect()))
+      }
+      _ => Err(LoxError::runtime_error(
+        &expr.bracket,
+        "Only arrays and strings can be sliced.",
+      )),
+    }
+  }
+
+  fn whole_number(token: &Token, value: &Literal, name: &str) -> Result<f64, LoxError> {
+    let &Literal::Number(n) = value else {
+      return Err(LoxError::runtime_error(
+        token,
+        &format!("{name} must be a number."),
+      ));
+    };
+
+    if n.fract().ne(&0.0) {
+      return Err(LoxError::runtime_error(
+        token,
+        &format!("{name} must be a whole number."),
+      ));
+    }
+
+    Ok(n)
+  }
+
+  fn element_index(token: &Token, index: &Literal, len: usize) -> Result<usize, LoxError> {
+    let n = Interpreter::whole_number(token, index, "Index")?;
+
+    if n.lt(&0.0) || n.ge(&(len as f64)) {
+      return Err(LoxError::runtime_error(
+        token,
+        &format!("Index {n} out of range for length {len}."),
+      ));
+    }
+
+    Ok(n as usize)
+  }
+
+  fn slice_bounds(
+    token: &Token,
+    start: &Option<Literal>,
+    end: &Option<Literal>,
+    len: usize,
+  ) -> Result<(usize, usize), LoxError> {
+    let mut bounds = [0, len];
+
+    for (bound, value) in bounds.iter_mut().zip([start, end]) {
+      if let Some(v) = value {
+        let n = Interpreter::whole_number(token, v, "Slice bound")?;
+
+        if n.lt(&0.0) || n.gt(&(len as f64)) {
+          return Err(LoxError::runtime_error(
+            token,
+            &format!("Slice bound {n} out of range for length {len}."),
+          ));
+        }
+
+        *bound = n as usize;
+      }
+    }
+
+    let [start, end] = bounds;
+    if start.gt(&end) {
+      return Err(LoxError::runtime_error(
+        token,
+        &format!("Slice start {start} is greater than end {end}."),
+      ));
+    }
+
+    Ok((start, end))
+  }
+
+  fn arity_error(
+    bracket: &Token,
+    callable: &dyn LoxCallable,
+    expected: &str,
+    got: usize,
+  ) -> LoxError {
+    let callee = match callable.name() {
+      "<anonymous>" => "anonymous function".to_string(),
+      name => format!("'{name}'"),
+    };
+    let message = format!("Expected {expected} arguments but got {got} in call to {callee}.");
+    LoxError::runtime_error(bracket, &message)
+  }
+
+  fn comparison_error(operator: &Token, left: &Literal, right: &Literal) -> LoxError {
+    let message = format!(
+      "Cannot compare {} and {}.",
+      left.type_name(),
+      right.type_name()
+    );
+    LoxError::runtime_error(operator, &message)
+  }
+
+  // the left instance's `equals` method decides, if it has one; otherwise
+  // instances are only equal to themselves, however alike their fields are
+  fn instances_equal(
+    &self,
+    operator: &Token,
+    left: &Literal,
+    right: &Literal,
+  ) -> Result<bool, LoxError> {
+    let Literal::Instance(l) = left else {
+      return Ok(false);
+    };
+
+    if let Some(Literal::Function(equals)) = l.get_class().find_method("equals") {
+      if equals.min_arity() > 1 || equals.max_arity() < 1 {
+        return Err(LoxError::runtime_error(
+          operator,
+          "Method 'equals' must accept one argument.",
+        ));
+      }
+
+      if let Literal::Function(m) = equals.bind(left) {
+        return m
+          .call(self, std::slice::from_ref(right), None)
+          .map(|res| res.is_truthy())
+          .map_err(|e| e.at_call_site(operator));
+      }
+    }
+
+    Ok(matches!(right, Literal::Instance(r) if Rc::ptr_eq(l, r)))
+  }
+
+  fn assign_variable(
+    &self,
+    expr: &Rc<Expr>,
+    name: &Token,
+    value: &Literal,
+  ) -> Result<(), LoxError> {
+    if let Some(&distance) = self.locals.borrow().get(expr) {
+      return Environment::assign_at(&self.environment.borrow(), distance, name, value);
+    }
+
+    self.globals.borrow_mut().assign(name, value)
+  }
+
+  // the elements of an array being unpacked into exactly `count` names
+  fn destructure(bracket: &Token, value: &Literal, count: usize) -> Result<Vec<Literal>, LoxError> {
+    let Literal::Array(a) = value else {
+      return Err(LoxError::runtime_error(
+        bracket,
+        &format!("Can only destructure arrays, not {}.", value.type_name()),
+      ));
+    };
+
+    let elements = a.borrow().clone();
+    if elements.len() != count {
+      return Err(LoxError::runtime_error(
+        bracket,
+        &format!(
+          "Expected {count} values to destructure but got {}.",
+          elements.len()
+        ),
+      ));
+    }
+
+    Ok(elements)
+  }
+
   fn internal_error(operator: &Token) -> LoxError {
     LoxError::runtime_error(operator, "INTERPRETER INTERNAL ERROR.")
   }
@@ -93,28 +616,50 @@ impl Interpreter {
     LoxError::runtime_error(operator, "Operand must be a number.")
   }
 
-  fn numbers_error(operator: &Token) -> LoxError {
-    LoxError::runtime_error(operator, "Operands must be numbers.")
+  fn numbers_error(operator: &Token, left: &Literal, right: &Literal) -> LoxError {
+    let message = format!(
+      "Operands must be numbers, not {}.",
+      Interpreter::operand_types(left, right)
+    );
+    LoxError::runtime_error(operator, &message)
+  }
+
+  fn numbers_or_strings_error(operator: &Token, left: &Literal, right: &Literal) -> LoxError {
+    let message = format!(
+      "Operands must be two numbers or two strings, not {}.",
+      Interpreter::operand_types(left, right)
+    );
+    LoxError::runtime_error(operator, &message)
+  }
+
+  fn operand_types(left: &Literal, right: &Literal) -> String {
+    if left.same_type(right) {
+      return format!("both {}", left.type_name());
+    }
+
+    format!("{} and {}", left.type_name(), right.type_name())
   }
+}
 
-  fn numbers_or_strings_error(operator: &Token) -> LoxError {
-    LoxError::runtime_error(operator, "Operands must be two numbers or two strings.")
+impl ResolveSink for Interpreter {
+  fn resolve(&self, expr: &Rc<Expr>, depth: usize) {
+    self.locals.borrow_mut().insert(expr.clone(), depth);
   }
 }
 
 impl ExprVisitor<Literal> for Interpreter {
-  fn visit_assign_expr(&self, wrapper: &Rc<Expr>, expr: &AssignExpr) -> Result<Literal, LoxError> {
-    let value = self.evaluate(&expr.value)?;
-    if let Some(&distance) = self.locals.borrow().get(wrapper) {
-      self
-        .environment
-        .borrow()
-        .borrow_mut()
-        .assign_at(distance, &expr.name, &value)?;
-    } else {
-      self.globals.borrow_mut().assign(&expr.name, &value)?;
+  fn visit_array_expr(&self, _wrapper: &Rc<Expr>, expr: &ArrayExpr) -> Result<Literal, LoxError> {
+    let mut elements = Vec::new();
+    for element in expr.elements.iter() {
+      elements.push(self.evaluate(element)?);
     }
 
+    Ok(Literal::Array(RefCell::new(elements).into()))
+  }
+
+  fn visit_assign_expr(&self, wrapper: &Rc<Expr>, expr: &AssignExpr) -> Result<Literal, LoxError> {
+    let value = self.evaluate(&expr.value)?;
+    self.assign_variable(wrapper, &expr.name, &value)?;
     Ok(value)
   }
 
@@ -123,55 +668,131 @@ impl ExprVisitor<Literal> for Interpreter {
     let right = self.evaluate(&expr.right)?;
     let operator_type = expr.operator.get_type();
 
+    if operator_type.eq(&TokenType::Instanceof) {
+      let Literal::Class(c) = &right else {
+        return Err(LoxError::runtime_error(
+          &expr.operator,
+          "Right operand of 'instanceof' must be a class.",
+        ));
+      };
+
+      return Ok(Literal::Boolean(
+        matches!(&left, Literal::Instance(i) if i.is_instance_of(c)),
+      ));
+    }
+
+    if let (Literal::Instance(_), TokenType::EqualEqual | TokenType::BangEqual) =
+      (&left, operator_type)
+    {
+      let equal = self.instances_equal(&expr.operator, &left, &right)?;
+      return Ok(Literal::Boolean(
+        equal == operator_type.eq(&TokenType::EqualEqual),
+      ));
+    }
+
     if let (Literal::Number(left), Literal::Number(right)) = (&left, &right) {
       return match operator_type {
         TokenType::Plus => Ok(Literal::Number(left + right)),
         TokenType::Minus => Ok(Literal::Number(left - right)),
         TokenType::Star => Ok(Literal::Number(left * right)),
         TokenType::Slash => Ok(Literal::Number(left / right)),
+        TokenType::TildeSlash if right.eq(&0.0) => {
+          Err(LoxError::runtime_error(&expr.operator, "Division by zero."))
+        }
+        TokenType::TildeSlash => Ok(Literal::Number((left / right).floor())),
         TokenType::Greater => Ok(Literal::Boolean(left > right)),
         TokenType::GreaterEqual => Ok(Literal::Boolean(left >= right)),
         TokenType::Less => Ok(Literal::Boolean(left < right)),
         TokenType::LessEqual => Ok(Literal::Boolean(left <= right)),
-        TokenType::BangEqual => Ok(Literal::Boolean((left - right).abs() > f64::EPSILON)),
-        TokenType::EqualEqual => Ok(Literal::Boolean((left - right).abs() < f64::EPSILON)),
+        TokenType::BangEqual => Ok(Literal::Boolean(!Literal::numbers_equal(*left, *right))),
+        TokenType::EqualEqual => Ok(Literal::Boolean(Literal::numbers_equal(*left, *right))),
         _ => Err(Interpreter::internal_error(&expr.operator)),
       };
     }
 
-    if let (Literal::String(left), Literal::String(right)) = (&left, &right) {
+    if let (Literal::String(l), Literal::String(r)) = (&left, &right) {
       return match operator_type {
-        TokenType::Plus => Ok(Literal::String(format!("{left}{right}"))),
-        TokenType::BangEqual => Ok(Literal::Boolean(left != right)),
-        TokenType::EqualEqual => Ok(Literal::Boolean(left == right)),
-        _ => Err(Interpreter::numbers_error(&expr.operator)),
+        TokenType::Plus => Ok(Literal::String(format!("{l}{r}"))),
+        TokenType::BangEqual => Ok(Literal::Boolean(l != r)),
+        TokenType::EqualEqual => Ok(Literal::Boolean(l == r)),
+        _ => Err(Interpreter::numbers_error(&expr.operator, &left, &right)),
       };
     }
 
-    if let (Literal::String(left), Literal::Number(right)) = (&left, &right) {
+    if let (Literal::String(l), Literal::Number(r)) = (&left, &right) {
       return match operator_type {
-        TokenType::Plus => Ok(Literal::String(format!("{left}{right}"))),
+        TokenType::Plus if self.strict_concat => Err(Interpreter::numbers_or_strings_error(
+          &expr.operator,
+          &left,
+          &right,
+        )),
+        TokenType::Plus => Ok(Literal::String(format!(
+          "{l}{}",
+          Literal::format_number(*r)
+        ))),
         TokenType::BangEqual => Ok(Literal::Boolean(true)),
         TokenType::EqualEqual => Ok(Literal::Boolean(false)),
-        _ => Err(Interpreter::numbers_error(&expr.operator)),
+        TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => {
+          Err(Interpreter::comparison_error(&expr.operator, &left, &right))
+        }
+        _ => Err(Interpreter::numbers_error(&expr.operator, &left, &right)),
       };
     }
 
-    if let (Literal::Number(left), Literal::String(right)) = (&left, &right) {
+    if let (Literal::Number(l), Literal::String(r)) = (&left, &right) {
       return match operator_type {
-        TokenType::Plus => Ok(Literal::String(format!("{left}{right}"))),
+        TokenType::Plus if self.strict_concat => Err(Interpreter::numbers_or_strings_error(
+          &expr.operator,
+          &left,
+          &right,
+        )),
+        TokenType::Plus => Ok(Literal::String(format!(
+          "{}{r}",
+          Literal::format_number(*l)
+        ))),
         TokenType::BangEqual => Ok(Literal::Boolean(true)),
         TokenType::EqualEqual => Ok(Literal::Boolean(false)),
-        _ => Err(Interpreter::numbers_error(&expr.operator)),
+        TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => {
+          Err(Interpreter::comparison_error(&expr.operator, &left, &right))
+        }
+        _ => Err(Interpreter::numbers_error(&expr.operator, &left, &right)),
+      };
+    }
+
+    // ordered as false < true, so booleans can be sorted
+    if let (Literal::Boolean(l), Literal::Boolean(r)) = (&left, &right) {
+      return match operator_type {
+        TokenType::Greater => Ok(Literal::Boolean(l > r)),
+        TokenType::GreaterEqual => Ok(Literal::Boolean(l >= r)),
+        TokenType::Less => Ok(Literal::Boolean(l < r)),
+        TokenType::LessEqual => Ok(Literal::Boolean(l <= r)),
+        TokenType::BangEqual => Ok(Literal::Boolean(l != r)),
+        TokenType::EqualEqual => Ok(Literal::Boolean(l == r)),
+        TokenType::Plus => Err(Interpreter::numbers_or_strings_error(
+          &expr.operator,
+          &left,
+          &right,
+        )),
+        _ => Err(Interpreter::numbers_error(&expr.operator, &left, &right)),
       };
     }
 
-    if let (Literal::Boolean(left), Literal::Boolean(right)) = (&left, &right) {
+    // equal when they hold equal entries, which a container always does with itself
+    if let (Literal::Map(_), Literal::Map(_)) | (Literal::Array(_), Literal::Array(_)) =
+      (&left, &right)
+    {
       return match operator_type {
         TokenType::BangEqual => Ok(Literal::Boolean(left != right)),
         TokenType::EqualEqual => Ok(Literal::Boolean(left == right)),
-        TokenType::Plus => Err(Interpreter::numbers_or_strings_error(&expr.operator)),
-        _ => Err(Interpreter::numbers_error(&expr.operator)),
+        TokenType::Plus => Err(Interpreter::numbers_or_strings_error(
+          &expr.operator,
+          &left,
+          &right,
+        )),
+        TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => {
+          Err(Interpreter::comparison_error(&expr.operator, &left, &right))
+        }
+        _ => Err(Interpreter::numbers_error(&expr.operator, &left, &right)),
       };
     }
 
@@ -179,16 +800,27 @@ impl ExprVisitor<Literal> for Interpreter {
       return match operator_type {
         TokenType::BangEqual => Ok(Literal::Boolean(false)),
         TokenType::EqualEqual => Ok(Literal::Boolean(true)),
-        TokenType::Plus => Err(Interpreter::numbers_or_strings_error(&expr.operator)),
-        _ => Err(Interpreter::numbers_error(&expr.operator)),
+        TokenType::Plus => Err(Interpreter::numbers_or_strings_error(
+          &expr.operator,
+          &left,
+          &right,
+        )),
+        _ => Err(Interpreter::numbers_error(&expr.operator, &left, &right)),
       };
     }
 
     match operator_type {
       TokenType::BangEqual => Ok(Literal::Boolean(true)),
       TokenType::EqualEqual => Ok(Literal::Boolean(false)),
-      TokenType::Plus => Err(Interpreter::numbers_or_strings_error(&expr.operator)),
-      _ => Err(Interpreter::numbers_error(&expr.operator)),
+      TokenType::Plus => Err(Interpreter::numbers_or_strings_error(
+        &expr.operator,
+        &left,
+        &right,
+      )),
+      TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => {
+        Err(Interpreter::comparison_error(&expr.operator, &left, &right))
+      }
+      _ => Err(Interpreter::numbers_error(&expr.operator, &left, &right)),
     }
   }
 
@@ -202,23 +834,30 @@ impl ExprVisitor<Literal> for Interpreter {
 
     let (function, class): (Option<Rc<dyn LoxCallable>>, Option<Rc<LoxClass>>) = match callee {
       Literal::Function(f) => (Some(f), None),
+      Literal::NativeFunction(f) => (Some(f.fun.clone()), None),
       Literal::Class(c) => (Some(c.clone()), Some(c.clone())),
       _ => (None, None),
     };
 
     if let Some(callable) = function {
-      if arguments.len().ne(&(callable.arity() as usize)) {
-        return Err(LoxError::runtime_error(
+      let (min_arity, max_arity) = (callable.min_arity(), callable.max_arity());
+      if !(min_arity as usize..=max_arity as usize).contains(&arguments.len()) {
+        let expected = match max_arity {
+          UNBOUNDED_ARITY => format!("at least {min_arity}"),
+          _ if max_arity.eq(&min_arity) => min_arity.to_string(),
+          _ => format!("{min_arity} to {max_arity}"),
+        };
+        return Err(Interpreter::arity_error(
           &expr.bracket,
-          &format!(
-            "Expected {} arguments but got {}.",
-            callable.arity(),
-            arguments.len()
-          ),
+          callable.as_ref(),
+          &expected,
+          arguments.len(),
         ));
       }
 
-      return callable.call(self, &arguments, class);
+      return callable
+        .call(self, &arguments, class)
+        .map_err(|e| e.at_call_site(&expr.bracket));
     }
 
     Err(LoxError::runtime_error(
@@ -227,12 +866,39 @@ impl ExprVisitor<Literal> for Interpreter {
     ))
   }
 
+  // each target is a variable expression, resolved on its own, as the names
+  // may well live in different scopes
+  fn visit_destructure_expr(
+    &self,
+    _wrapper: &Rc<Expr>,
+    expr: &DestructureExpr,
+  ) -> Result<Literal, LoxError> {
+    let value = self.evaluate(&expr.value)?;
+    let elements = Interpreter::destructure(&expr.bracket, &value, expr.targets.len())?;
+    for (target, element) in expr.targets.iter().zip(elements.iter()) {
+      if let Expr::Variable(v) = target.as_ref() {
+        self.assign_variable(target, &v.name, element)?;
+      }
+    }
+
+    Ok(value)
+  }
+
   fn visit_get_expr(&self, _wrapper: &Rc<Expr>, expr: &GetExpr) -> Result<Literal, LoxError> {
     let object = self.evaluate(&expr.object)?;
     if let Literal::Instance(i) = object {
       return i.get(&expr.name, &i);
     }
 
+    if let Literal::Class(c) = object {
+      return c.find_static_method(expr.name.get_lexeme()).ok_or_else(|| {
+        LoxError::runtime_error(
+          &expr.name,
+          &format!("Undefined property '{}'.", expr.name.get_lexeme()),
+        )
+      });
+    }
+
     Err(LoxError::runtime_error(
       &expr.name,
       "Only instances have properties.",
@@ -247,6 +913,78 @@ impl ExprVisitor<Literal> for Interpreter {
     self.evaluate(&expr.expression)
   }
 
+  fn visit_if_expr(&self, _wrapper: &Rc<Expr>, expr: &IfExpr) -> Result<Literal, LoxError> {
+    if self.evaluate(&expr.condition)?.is_truthy() {
+      self.evaluate(&expr.then_branch)
+    } else {
+      self.evaluate(&expr.else_branch)
+    }
+  }
+
+  fn visit_index_expr(&self, _wrapper: &Rc<Expr>, expr: &IndexExpr) -> Result<Literal, LoxError> {
+    let object = self.evaluate(&expr.object)?;
+
+    if expr.is_slice {
+      return self.slice(expr, &object);
+    }
+
+    let Some(index) = &expr.index else {
+      return Err(Interpreter::internal_error(&expr.bracket));
+    };
+    let index = self.evaluate(index)?;
+
+    match object {
+      Literal::Map(m) => {
+        let key = Interpreter::map_key(&expr.bracket, &index)?;
+        let value = m.borrow().get(&key).cloned();
+        value
+          .ok_or_else(|| LoxError::runtime_error(&expr.bracket, &format!("Undefined key '{key}'.")))
+      }
+      Literal::Array(a) => {
+        let index = Interpreter::element_index(&expr.bracket, &index, a.borrow().len())?;
+        let element = a.borrow()[index].clone();
+        Ok(element)
+      }
+      Literal::String(s) => {
+        let chars = s.chars().collect::<Vec<char>>();
+        let index = Interpreter::element_index(&expr.bracket, &index, chars.len())?;
+        Ok(Literal::String(chars[index].to_string()))
+      }
+      _ => Err(LoxError::runtime_error(
+        &expr.bracket,
+        "Only arrays, maps and strings can be indexed.",
+      )),
+    }
+  }
+
+  fn visit_index_set_expr(
+    &self,
+    _wrapper: &Rc<Expr>,
+    expr: &IndexSetExpr,
+  ) -> Result<Literal, LoxError> {
+    let object = self.evaluate(&expr.object)?;
+    let index = self.evaluate(&expr.index)?;
+
+    if let Literal::Map(m) = object {
+      let key = Interpreter::map_key(&expr.bracket, &index)?;
+      let value = self.evaluate(&expr.value)?;
+      m.borrow_mut().insert(key, value.clone());
+      return Ok(value);
+    }
+
+    if let Literal::Array(a) = object {
+      let index = Interpreter::element_index(&expr.bracket, &index, a.borrow().len())?;
+      let value = self.evaluate(&expr.value)?;
+      a.borrow_mut()[index] = value.clone();
+      return Ok(value);
+    }
+
+    Err(LoxError::runtime_error(
+      &expr.bracket,
+      "Only arrays and maps can be indexed.",
+    ))
+  }
+
   fn visit_literal_expr(
     &self,
     _wrapper: &Rc<Expr>,
@@ -266,24 +1004,72 @@ impl ExprVisitor<Literal> for Interpreter {
   ) -> Result<Literal, LoxError> {
     let left = self.evaluate(&expr.left)?;
 
-    if expr.operator.is_type(&TokenType::Or) {
-      if left.is_truthy() {
-        return Ok(left);
-      }
-    } else if !left.is_truthy() {
+    let short_circuits = match expr.operator.get_type() {
+      TokenType::Or => left.is_truthy(),
+      TokenType::QuestionQuestion => !matches!(left, Literal::Nil),
+      _ => !left.is_truthy(),
+    };
+    if short_circuits {
       return Ok(left);
     }
 
     self.evaluate(&expr.right)
   }
 
-  fn visit_set_expr(&self, _wrapper: &Rc<Expr>, expr: &SetExpr) -> Result<Literal, LoxError> {
-    let object = self.evaluate(&expr.object)?;
+  fn visit_map_expr(&self, _wrapper: &Rc<Expr>, expr: &MapExpr) -> Result<Literal, LoxError> {
+    let mut map = HashMap::new();
 
-    if let Literal::Instance(i) = object {
-      let value = self.evaluate(&expr.value)?;
-      i.set(&expr.name, &value);
-      return Ok(value);
+    for (key, value) in expr.keys.iter().zip(expr.values.iter()) {
+      let key = Interpreter::map_key(&expr.brace, &self.evaluate(key)?)?;
+      map.insert(key, self.evaluate(value)?);
+    }
+
+    Ok(Literal::Map(RefCell::new(map).into()))
+  }
+
+  // evaluates to the old value, after storing the stepped one
+  fn visit_postfix_expr(
+    &self,
+    _wrapper: &Rc<Expr>,
+    expr: &PostfixExpr,
+  ) -> Result<Literal, LoxError> {
+    let step = |old: &Literal| match old {
+      Literal::Number(n) if expr.operator.is_type(&TokenType::PlusPlus) => {
+        Ok(Literal::Number(n + 1.0))
+      }
+      Literal::Number(n) => Ok(Literal::Number(n - 1.0)),
+      _ => Err(Interpreter::number_error(&expr.operator)),
+    };
+
+    match expr.target.as_ref() {
+      Expr::Variable(v) => {
+        let old = self.look_up_variable(&v.name, &expr.target)?;
+        self.assign_variable(&expr.target, &v.name, &step(&old)?)?;
+        Ok(old)
+      }
+      Expr::Get(g) => {
+        let Literal::Instance(i) = self.evaluate(&g.object)? else {
+          return Err(LoxError::runtime_error(
+            &g.name,
+            "Only instances have fields.",
+          ));
+        };
+
+        let old = i.get(&g.name, &i)?;
+        i.set(&g.name, &step(&old)?);
+        Ok(old)
+      }
+      _ => Err(Interpreter::internal_error(&expr.operator)),
+    }
+  }
+
+  fn visit_set_expr(&self, _wrapper: &Rc<Expr>, expr: &SetExpr) -> Result<Literal, LoxError> {
+    let object = self.evaluate(&expr.object)?;
+
+    if let Literal::Instance(i) = object {
+      let value = self.evaluate(&expr.value)?;
+      i.set(&expr.name, &value);
+      return Ok(value);
     }
 
     Err(LoxError::runtime_error(
@@ -298,25 +1084,22 @@ impl ExprVisitor<Literal> for Interpreter {
       .borrow()
       .get(wrapper)
       .map_or_else(|| 0_usize, |&v| v);
-    let superclass = self
-      .environment
-      .borrow()
-      .borrow()
-      .get_at(distance, "super")?;
+    let superclass = Environment::get_at(&self.environment.borrow(), distance, "super")?;
     let Literal::Class(superclass) = superclass else {
       panic!()
     };
-    let object = self
-      .environment
-      .borrow()
-      .borrow()
-      .get_at(distance - 1, "this")?;
+    let object = Environment::get_at(&self.environment.borrow(), distance - 1, "this")?;
+    // fields belong to the instance rather than to any class, so even one the
+    // instance has is not found here; `this` already reaches those
     let method = superclass
       .find_method(expr.method.get_lexeme())
       .ok_or_else(|| {
         LoxError::runtime_error(
           &expr.method,
-          &format!("Undefined property '{}'.", expr.method.get_lexeme()),
+          &format!(
+            "Undefined superclass method '{}'; super can only access methods.",
+            expr.method.get_lexeme()
+          ),
         )
       })?;
     if let Literal::Function(f) = method {
@@ -340,6 +1123,12 @@ impl ExprVisitor<Literal> for Interpreter {
           .get_number()
           .map_err(|_| Interpreter::number_error(&expr.operator))?,
       )),
+      // only there for symmetry with `-`, but still insists on a number
+      TokenType::Plus => Ok(Literal::Number(
+        right
+          .get_number()
+          .map_err(|_| Interpreter::number_error(&expr.operator))?,
+      )),
       _ => Err(Interpreter::internal_error(&expr.operator)),
     }
   }
@@ -359,8 +1148,12 @@ impl StmtVisitor<()> for Interpreter {
     self.execute_block(&stmt.statements.as_slice().into(), environment)
   }
 
-  fn visit_break_stmt(&self, _wrapper: &Rc<Stmt>, _stmt: &BreakStmt) -> Result<(), LoxError> {
-    Err(LoxError::new_break())
+  fn visit_break_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &BreakStmt) -> Result<(), LoxError> {
+    Err(LoxError::new_break(&stmt.label))
+  }
+
+  fn visit_continue_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &ContinueStmt) -> Result<(), LoxError> {
+    Err(LoxError::new_continue(&stmt.label))
   }
 
   fn visit_class_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &ClassStmt) -> Result<(), LoxError> {
@@ -386,6 +1179,15 @@ impl StmtVisitor<()> for Interpreter {
       .borrow_mut()
       .define(stmt.name.get_lexeme(), Literal::Nil);
 
+    let mut static_methods = HashMap::new();
+    for method in stmt.static_methods.iter() {
+      if let Stmt::Function(m) = method.as_ref() {
+        let function =
+          Literal::Function(LoxFunction::new(&self.environment.borrow(), m, false).into());
+        static_methods.insert(m.name.get_lexeme().to_string(), function);
+      }
+    }
+
     let enclosing = if let Some(s) = superclass.as_ref() {
       let mut environment = Environment::new_with_enclosing(&self.environment.borrow().clone());
       environment.define("super", Literal::Class(s.clone()));
@@ -409,7 +1211,23 @@ impl StmtVisitor<()> for Interpreter {
       }
     }
 
-    let class = Literal::Class(LoxClass::new(stmt.name.get_lexeme(), &superclass, &methods).into());
+    let mut fields = Vec::new();
+    for field in stmt.fields.iter() {
+      if let Stmt::Var(f) = field.as_ref() {
+        let initialiser = LoxFunction::new_field_initialiser(&self.environment.borrow(), f);
+        fields.push((
+          f.name.get_lexeme().to_string(),
+          Literal::Function(initialiser.into()),
+        ));
+      }
+    }
+
+    let class = Literal::Class(
+      LoxClass::new(stmt.name.get_lexeme(), &superclass, &methods)
+        .with_fields(&fields)
+        .with_static_methods(&static_methods)
+        .into(),
+    );
 
     if let Some(e) = enclosing {
       self.environment.replace(e);
@@ -422,6 +1240,22 @@ impl StmtVisitor<()> for Interpreter {
       .assign(&stmt.name, &class)
   }
 
+  fn visit_destructure_stmt(
+    &self,
+    _wrapper: &Rc<Stmt>,
+    stmt: &DestructureStmt,
+  ) -> Result<(), LoxError> {
+    let value = self.evaluate(&stmt.initialiser)?;
+    let elements = Interpreter::destructure(&stmt.bracket, &value, stmt.names.len())?;
+
+    let environment = self.environment.borrow();
+    for (name, element) in stmt.names.iter().zip(elements) {
+      environment.borrow_mut().define(name.get_lexeme(), element);
+    }
+
+    Ok(())
+  }
+
   fn visit_expression_stmt(
     &self,
     _wrapper: &Rc<Stmt>,
@@ -431,6 +1265,29 @@ impl StmtVisitor<()> for Interpreter {
     Ok(())
   }
 
+  fn visit_foreach_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &ForeachStmt) -> Result<(), LoxError> {
+    let Literal::Array(a) = self.evaluate(&stmt.iterable)? else {
+      return Err(LoxError::runtime_error(
+        &stmt.name,
+        "Can only iterate over arrays.",
+      ));
+    };
+
+    // iterate over a snapshot so the body can safely mutate the array
+    let elements = a.borrow().clone();
+    for element in elements {
+      let mut environment = Environment::new_with_enclosing(&self.environment.borrow().clone());
+      environment.define(stmt.name.get_lexeme(), element);
+
+      let body = self.execute_block(&std::slice::from_ref(&stmt.body).into(), environment);
+      if !Interpreter::continue_loop(&stmt.label, body)? {
+        break;
+      }
+    }
+
+    Ok(())
+  }
+
   fn visit_function_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &FunctionStmt) -> Result<(), LoxError> {
     let function = LoxFunction::new(&self.environment.borrow(), stmt, false);
 
@@ -456,8 +1313,7 @@ impl StmtVisitor<()> for Interpreter {
   }
 
   fn visit_print_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &PrintStmt) -> Result<(), LoxError> {
-    println!("{}", self.evaluate(&stmt.expression)?);
-    Ok(())
+    self.write_output(&self.evaluate(&stmt.expression)?)
   }
 
   fn visit_return_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &ReturnStmt) -> Result<(), LoxError> {
@@ -484,13 +1340,27 @@ impl StmtVisitor<()> for Interpreter {
   }
 
   fn visit_while_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &WhileStmt) -> Result<(), LoxError> {
-    while self.evaluate(&stmt.condition)?.is_truthy() {
-      let mut body = self.execute(&stmt.body);
-      if body.as_mut().is_err_and(|e| e.is_break()) {
+    // a `for` loop without a condition runs until it breaks
+    while match &stmt.condition {
+      Some(c) => self.evaluate(c)?.is_truthy(),
+      None => true,
+    } {
+      let body = self.execute(&stmt.body);
+      if !Interpreter::continue_loop(&stmt.label, body)? {
         break;
       }
 
-      body?;
+      // the `for` desugaring runs the loop in a scope of its own, holding just
+      // the loop variable; the increment then updates a copy of it, leaving
+      // whatever this iteration's closures captured alone
+      if stmt.per_iteration {
+        let environment = self.environment.borrow().borrow().clone();
+        self.environment.replace(RefCell::new(environment).into());
+      }
+
+      if let Some(i) = &stmt.increment {
+        self.evaluate(i)?;
+      }
     }
 
     Ok(())
@@ -498,11 +1368,56 @@ impl StmtVisitor<()> for Interpreter {
 }
 
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
   use super::*;
 
   use crate::{parser::*, scanner::*};
 
+  // collects what the interpreter prints, shared with the other modules' tests
+  #[derive(Clone, Default)]
+  pub(crate) struct Capture {
+    output: Rc<RefCell<Vec<u8>>>,
+    flushes: Rc<Cell<usize>>,
+  }
+
+  impl Capture {
+    pub(crate) fn contents(&self) -> String {
+      String::from_utf8_lossy(&self.output.borrow()).to_string()
+    }
+  }
+
+  impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+      self.output.borrow_mut().extend_from_slice(buf);
+      Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+      self.flushes.set(self.flushes.get() + 1);
+      Ok(())
+    }
+  }
+
+  pub(crate) fn run(interpreter: &Interpreter, source: &str) -> Result<(), LoxError> {
+    let mut scanner = Scanner::new(source);
+    let tokens = scanner.scan_tokens()?;
+    let mut parser = Parser::new(tokens);
+    let statements = parser
+      .parse()?
+      .into_iter()
+      .map(Rc::new)
+      .collect::<Vec<Rc<Stmt>>>();
+
+    interpreter.resolve_and_interpret(&statements.as_slice().into())
+  }
+
+  fn get_global(interpreter: &Interpreter, name: &str) -> Result<Literal, LoxError> {
+    interpreter
+      .get_globals()
+      .borrow()
+      .get(&Token::new(TokenType::Identifier, name, None, 0))
+  }
+
   #[test]
   fn test_interpreter() -> Result<(), LoxError> {
     let interpreter = Interpreter::new();
@@ -512,7 +1427,7 @@ mod test {
     let mut parser = Parser::new(tokens);
     let statements = parser.parse()?;
 
-    interpreter.interpret(
+    interpreter.resolve_and_interpret(
       &statements
         .into_iter()
         .map(Rc::new)
@@ -524,6 +1439,70 @@ mod test {
     Ok(())
   }
 
+  #[test]
+  fn test_print_environment() -> Result<(), LoxError> {
+    let capture = Capture::default();
+    let interpreter = Interpreter::new().with_output(capture.clone());
+    run(&interpreter, "var a = 1;")?;
+    capture.flushes.set(0);
+
+    interpreter.print_environment()?;
+    assert!(capture.contents().starts_with("scope 0:\n"));
+    assert!(capture.contents().contains("  a = 1\n"));
+    assert_eq!(capture.flushes.get(), 1);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_auto_print() -> Result<(), LoxError> {
+    let run_with = |auto_print: bool| -> Result<String, LoxError> {
+      let capture = Capture::default();
+      let interpreter = Interpreter::new().with_output(capture.clone());
+      let mut scanner = Scanner::new("2 + 2;\nvar a = 1;\n{ a + 1; }");
+      let tokens = scanner.scan_tokens()?;
+      let statements = Parser::new(tokens)
+        .with_auto_print(auto_print)
+        .parse()?
+        .into_iter()
+        .map(Rc::new)
+        .collect::<Vec<Rc<Stmt>>>();
+
+      interpreter.resolve_and_interpret(&statements.as_slice().into())?;
+      Ok(capture.contents())
+    };
+
+    assert_eq!(run_with(true)?, "4\n");
+    assert_eq!(run_with(false)?, "");
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_resolve_and_interpret() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var a = \"global\";\n\
+                  var seen;\n\
+                  {\n\
+                    fun show() { seen = a; }\n\
+                    var a = \"block\";\n\
+                    show();\n\
+                  }";
+    let mut scanner = Scanner::new(source);
+    let tokens = scanner.scan_tokens()?;
+    let statements = Parser::new(tokens)
+      .parse()?
+      .into_iter()
+      .map(Rc::new)
+      .collect::<Vec<Rc<Stmt>>>();
+
+    // without resolving, `show` would look `a` up dynamically and see the block's
+    interpreter.resolve_and_interpret(&statements.as_slice().into())?;
+    assert!(matches!(get_global(&interpreter, "seen")?, Literal::String(s) if s == "global"));
+
+    Ok(())
+  }
+
   #[test]
   fn test_global_variable() -> Result<(), LoxError> {
     let interpreter = Interpreter::new();
@@ -533,7 +1512,7 @@ mod test {
     let mut parser = Parser::new(tokens);
     let statements = parser.parse()?;
 
-    interpreter.interpret(
+    interpreter.resolve_and_interpret(
       &statements
         .into_iter()
         .map(Rc::new)
@@ -544,4 +1523,1827 @@ mod test {
 
     Ok(())
   }
+
+  #[test]
+  fn test_memoize() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var count = 0;\n\
+                  fun inc(n) { count = count + 1; return n * 2; }\n\
+                  var fast = memoize(inc);\n\
+                  var a = fast(1);\n\
+                  fast(1);\n\
+                  fast(2);\n\
+                  fast(1);";
+    run(&interpreter, source)?;
+
+    assert!(matches!(get_global(&interpreter, "count")?, Literal::Number(n) if n == 2.0));
+    assert!(matches!(get_global(&interpreter, "a")?, Literal::Number(n) if n == 2.0));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_memoize_non_callable() {
+    let interpreter = Interpreter::new();
+    let res = run(&interpreter, "memoize(1);");
+    assert!(res.is_err_and(|e| e.is_runtime_error()));
+  }
+
+  #[test]
+  fn test_map() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var m = {\"a\": 1, 2: \"two\"};\n\
+                  m[\"b\"] = m[\"a\"] + 1;\n\
+                  var a = m[\"a\"];\n\
+                  var b = m[\"b\"];\n\
+                  var two = m[2];";
+    run(&interpreter, source)?;
+
+    assert!(matches!(get_global(&interpreter, "a")?, Literal::Number(n) if n == 1.0));
+    assert!(matches!(get_global(&interpreter, "b")?, Literal::Number(n) if n == 2.0));
+    assert!(matches!(get_global(&interpreter, "two")?, Literal::String(s) if s == "two"));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_map_equality() -> Result<(), LoxError> {
+    let capture = Capture::default();
+    let interpreter = Interpreter::new().with_output(capture.clone());
+    let source = "var m = {\"a\": 1};\n\
+                  print m == m;\n\
+                  print m != m;\n\
+                  print {\"a\": 1} == {\"a\": 1};\n\
+                  print m == {\"a\": 2};\n\
+                  print m == nil;\n\
+                  m[\"self\"] = m;\n\
+                  print m == m;\n\
+                  var n = {\"a\": 1};\n\
+                  n[\"self\"] = n;\n\
+                  print m == n;\n\
+                  n[\"b\"] = 2;\n\
+                  print m == n;";
+    run(&interpreter, source)?;
+    assert_eq!(
+      capture.contents(),
+      "true\nfalse\ntrue\nfalse\nfalse\ntrue\ntrue\nfalse\n"
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_map_missing_key() {
+    let interpreter = Interpreter::new();
+    let res = run(&interpreter, "var m = {};\nm[\"a\"];");
+    assert!(res.is_err_and(|e| e.is_runtime_error()));
+  }
+
+  #[test]
+  fn test_map_unhashable_key() {
+    let interpreter = Interpreter::new();
+    let res = run(&interpreter, "var m = {nil: 1};");
+    assert!(res.is_err_and(|e| e.is_runtime_error()));
+
+    let res = run(&interpreter, "var n = {};\nn[clock] = 1;");
+    assert!(res.is_err_and(|e| e.is_runtime_error()));
+  }
+
+  #[test]
+  fn test_concatenated_numbers_print_alike() -> Result<(), LoxError> {
+    let capture = Capture::default();
+    let interpreter = Interpreter::new().with_output(capture.clone());
+
+    let source = "print \"v=\" + 6.0;\n\
+                  print 6.0;\n\
+                  print 1 / 0 + \" \" + -1 / 0 + \" \" + 0 / 0;\n\
+                  print 1 / 0;";
+    run(&interpreter, source)?;
+    assert_eq!(
+      capture.contents(),
+      "v=6\n6\nInfinity -Infinity NaN\nInfinity\n"
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_set_line_buffered() -> Result<(), LoxError> {
+    let capture = Capture::default();
+    let interpreter = Interpreter::new().with_output(capture.clone());
+
+    run(&interpreter, "print 1;\nprint 2;")?;
+    assert_eq!(capture.contents(), "1\n2\n");
+    // once per print, then once when interpretation finishes
+    assert_eq!(capture.flushes.get(), 3);
+
+    capture.flushes.set(0);
+    run(&interpreter, "setLineBuffered(false);\nprint 3;\nprint 4;")?;
+    assert_eq!(capture.contents(), "1\n2\n3\n4\n");
+    assert_eq!(capture.flushes.get(), 1);
+
+    capture.flushes.set(0);
+    run(&interpreter, "setLineBuffered(true);\nprint 5;")?;
+    assert_eq!(capture.flushes.get(), 3);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_set_line_buffered_non_boolean() {
+    let interpreter = Interpreter::new().with_output(Capture::default());
+    let res = run(&interpreter, "setLineBuffered(1);");
+    assert!(res.is_err_and(|e| e.is_runtime_error()));
+  }
+
+  #[test]
+  fn test_callable_introspection() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    run(&interpreter, "fun foo(a, b) {}\nclass Bar { init(c) {} }")?;
+
+    let Literal::Function(foo) = get_global(&interpreter, "foo")? else {
+      panic!("foo should be a function");
+    };
+    assert_eq!((foo.name(), foo.arity()), ("foo", 2));
+
+    let Literal::Class(bar) = get_global(&interpreter, "Bar")? else {
+      panic!("Bar should be a class");
+    };
+    assert_eq!((bar.name(), bar.arity()), ("Bar", 1));
+
+    let Literal::NativeFunction(clock) = get_global(&interpreter, "clock")? else {
+      panic!("clock should be a native function");
+    };
+    assert_eq!((clock.name(), clock.arity()), ("clock", 0));
+
+    let callable = Callable {
+      fun: clock.fun.clone(),
+    };
+    assert_eq!((callable.name(), callable.arity()), ("clock", 0));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_array() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var a = [1, \"two\", [3]];\n\
+                  a[0] = a[0] + 10;\n\
+                  var first = a[0];\n\
+                  var nested = a[2][0];";
+    run(&interpreter, source)?;
+
+    assert!(matches!(get_global(&interpreter, "first")?, Literal::Number(n) if n == 11.0));
+    assert!(matches!(get_global(&interpreter, "nested")?, Literal::Number(n) if n == 3.0));
+
+    let res = run(&interpreter, "a[3];");
+    assert!(res.is_err_and(|e| e.is_runtime_error()));
+    let res = run(&interpreter, "a[0.5];");
+    assert!(res.is_err_and(|e| e.is_runtime_error()));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_array_equality() -> Result<(), LoxError> {
+    let capture = Capture::default();
+    let interpreter = Interpreter::new().with_output(capture.clone());
+    let source = "var a = [1];\n\
+                  print a == a;\n\
+                  print [1] == [1];\n\
+                  print [1] != [1, 2];\n\
+                  print a == [\"1\"];\n\
+                  array_push(a, a);\n\
+                  print a == a;\n\
+                  var b = [1];\n\
+                  array_push(b, b);\n\
+                  print a == b;\n\
+                  var c = [];\n\
+                  array_push(c, c);\n\
+                  var d = [];\n\
+                  array_push(d, d);\n\
+                  print c == d;\n\
+                  array_push(d, 1);\n\
+                  print c == d;";
+    run(&interpreter, source)?;
+    assert_eq!(
+      capture.contents(),
+      "true\ntrue\ntrue\nfalse\ntrue\ntrue\ntrue\nfalse\n"
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_foreach() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var sum = 0;\n\
+                  foreach (x in [1, 2, 3, 4]) {\n\
+                    if (x > 3) break;\n\
+                    sum = sum + x;\n\
+                  }";
+    run(&interpreter, source)?;
+
+    assert!(matches!(get_global(&interpreter, "sum")?, Literal::Number(n) if n == 6.0));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_foreach_non_array() {
+    let interpreter = Interpreter::new();
+    let res = run(&interpreter, "foreach (x in 1) print x;");
+    assert!(res.is_err_and(|e| e.is_runtime_error()));
+  }
+
+  #[test]
+  fn test_string_index_and_slice() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var s = \"héllo\";\n\
+                  var first = s[0];\n\
+                  var accent = s[1];\n\
+                  var middle = s[1:3];\n\
+                  var head = s[:2];\n\
+                  var tail = s[3:];\n\
+                  var all = s[:];\n\
+                  var sub = [1, 2, 3][1:];";
+    run(&interpreter, source)?;
+
+    assert!(matches!(get_global(&interpreter, "first")?, Literal::String(s) if s == "h"));
+    assert!(matches!(get_global(&interpreter, "accent")?, Literal::String(s) if s == "é"));
+    assert!(matches!(get_global(&interpreter, "middle")?, Literal::String(s) if s == "él"));
+    assert!(matches!(get_global(&interpreter, "head")?, Literal::String(s) if s == "hé"));
+    assert!(matches!(get_global(&interpreter, "tail")?, Literal::String(s) if s == "lo"));
+    assert!(matches!(get_global(&interpreter, "all")?, Literal::String(s) if s == "héllo"));
+    assert!(matches!(get_global(&interpreter, "sub")?, Literal::Array(a) if a.borrow().len() == 2));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_string_index_errors() {
+    let interpreter = Interpreter::new();
+
+    for source in [
+      "\"abc\"[3];",
+      "\"abc\"[-1];",
+      "\"abc\"[2:1];",
+      "\"abc\"[0:4];",
+      "\"abc\"[\"a\"];",
+    ] {
+      let res = run(&interpreter, source);
+      assert!(res.is_err_and(|e| e.is_runtime_error()), "{source}");
+    }
+  }
+
+  #[test]
+  fn test_assert() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    run(
+      &interpreter,
+      "var x = 2;\nvar res = assert(x == 2, \"x should be two\");",
+    )?;
+    assert!(matches!(get_global(&interpreter, "res")?, Literal::Nil));
+
+    let res = run(&interpreter, "assert(x == 3, \"x should be three\");");
+    assert!(res.is_err_and(|e| e.is_runtime_error()));
+
+    let res = run(&interpreter, "assert(nil, \"nil is falsey\");");
+    assert!(res.is_err_and(|e| e.is_runtime_error()));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_error() {
+    let interpreter = Interpreter::new();
+    let source = "fun check(x) { if (x < 0) error(\"x must not be negative\"); return x; }\n\
+                  check(1);\n\
+                  check(-1);";
+    let res = run(&interpreter, source);
+    assert!(res.is_err_and(|e| e.is_runtime_error()
+      && e.get_message() == Some("x must not be negative")
+      && e.get_line() == Some(1)));
+
+    let res = run(&interpreter, "error(1);");
+    assert!(res.is_err_and(
+      |e| e.is_runtime_error() && e.get_message() == Some("Argument must be a string.")
+    ));
+  }
+
+  #[test]
+  fn test_interpret_with_result() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+
+    let result = |source: &str| -> Result<Option<Literal>, LoxError> {
+      let mut scanner = Scanner::new(source);
+      let tokens = scanner.scan_tokens()?;
+      let statements = Parser::new(tokens)
+        .parse()?
+        .into_iter()
+        .map(Rc::new)
+        .collect::<Vec<Rc<Stmt>>>();
+      interpreter.interpret_with_result(&statements.as_slice().into())
+    };
+
+    assert!(matches!(result("var a = 2;\na * 3;")?, Some(Literal::Number(n)) if n == 6.0));
+    assert!(result("var b = 1;")?.is_none());
+    assert!(result("")?.is_none());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_constant_condition_is_not_an_error() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var a = 0;\n\
+                  if (true) a = 1;\n\
+                  while (false) a = 2;\n\
+                  for (;;) { a = a + 1; break; }";
+    run(&interpreter, source)?;
+
+    assert!(matches!(get_global(&interpreter, "a")?, Literal::Number(n) if n == 2.0));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_continue() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var sum = 0;\n\
+                  for (var i = 0; i < 5; i = i + 1) {\n\
+                    if (i == 2) continue;\n\
+                    sum = sum + i;\n\
+                  }\n\
+                  var odd = 0;\n\
+                  foreach (x in [1, 2, 3]) {\n\
+                    if (x == 2) continue;\n\
+                    odd = odd + x;\n\
+                  }";
+    run(&interpreter, source)?;
+
+    assert!(matches!(get_global(&interpreter, "sum")?, Literal::Number(n) if n == 8.0));
+    assert!(matches!(get_global(&interpreter, "odd")?, Literal::Number(n) if n == 4.0));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_break_and_continue_through_nested_blocks() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var increments = 0;\n\
+                  fun step(i) { increments = increments + 1; return i + 1; }\n\
+                  var body = 0;\n\
+                  for (var i = 0; i < 5; i = step(i)) {\n\
+                    var a = i;\n\
+                    if (a == 1) { { var b = a; continue; } }\n\
+                    { if (a == 3) continue; }\n\
+                    body = body + 1;\n\
+                  }\n\
+                  var broke = 0;\n\
+                  while (true) {\n\
+                    var a = 1;\n\
+                    { var a = 2; if (a == 2) { broke = a; break; } }\n\
+                    broke = -1;\n\
+                  }\n\
+                  foreach (x in [1, 2, 3]) { { if (x == 2) break; } }\n\
+                  var after = \"defined in the global scope\";";
+    run(&interpreter, source)?;
+
+    // `continue` still runs the increment, exactly once per iteration
+    assert!(matches!(get_global(&interpreter, "increments")?, Literal::Number(n) if n == 5.0));
+    assert!(matches!(get_global(&interpreter, "body")?, Literal::Number(n) if n == 3.0));
+    assert!(matches!(get_global(&interpreter, "broke")?, Literal::Number(n) if n == 2.0));
+    // leaving blocks early restores the environment they replaced
+    assert!(get_global(&interpreter, "after").is_ok());
+    assert!(Rc::ptr_eq(
+      &interpreter.environment.borrow(),
+      &interpreter.globals
+    ));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_field_initialisers() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var made = 0;\n\
+                  class A {\n\
+                    var x = 1;\n\
+                    var y = this.x + 1;\n\
+                    var count = made = made + 1;\n\
+                  }\n\
+                  class B < A {\n\
+                    var z = this.y * 10;\n\
+                    var empty;\n\
+                    init(w) { this.w = w + this.z; }\n\
+                  }\n\
+                  var a = A();\n\
+                  var b = B(1);\n\
+                  a.x = 5;\n\
+                  var fresh = A();";
+    run(&interpreter, source)?;
+
+    let field = |name: &str, field: &str| -> Result<Literal, LoxError> {
+      let Literal::Instance(i) = get_global(&interpreter, name)? else {
+        panic!("{name} is not an instance");
+      };
+      Ok(
+        i.get_fields()
+          .into_iter()
+          .find(|(f, _)| f == field)
+          .unwrap()
+          .1,
+      )
+    };
+
+    assert!(matches!(field("a", "y")?, Literal::Number(n) if n == 2.0));
+    assert!(matches!(field("b", "z")?, Literal::Number(n) if n == 20.0));
+    assert!(matches!(field("b", "w")?, Literal::Number(n) if n == 21.0));
+    assert!(matches!(field("b", "empty")?, Literal::Nil));
+    // initialisers run again for every instance
+    assert!(matches!(field("fresh", "x")?, Literal::Number(n) if n == 1.0));
+    assert!(matches!(field("fresh", "count")?, Literal::Number(n) if n == 3.0));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_initialiser_body_runs() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "class A {\n\
+                    init(x) { this.x = x; if (x > 1) return; this.small = true; }\n\
+                  }\n\
+                  var a = A(1);\n\
+                  var b = A(2);\n\
+                  var again = a.init(3);";
+    run(&interpreter, source)?;
+
+    let Literal::Instance(a) = get_global(&interpreter, "a")? else {
+      panic!("a is not an instance");
+    };
+    let Literal::Instance(b) = get_global(&interpreter, "b")? else {
+      panic!("b is not an instance");
+    };
+    assert_eq!(a.get_field_names(), vec!["small", "x"]);
+    assert_eq!(b.get_field_names(), vec!["x"]);
+    assert!(
+      matches!(get_global(&interpreter, "again")?, Literal::Instance(i) if Rc::ptr_eq(&i, &a))
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_if_expression() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var calls = 0;\n\
+                  fun touch(x) { calls = calls + 1; return x; }\n\
+                  var a = if (1 < 2) touch(\"yes\") else touch(\"no\");\n\
+                  var b = if (nil) 1 else if (false) 2 else 3;\n\
+                  var c = [if (true) 1 else 2][0] + 1;\n\
+                  if (a == \"yes\") b = b * 10; else b = 0;";
+    run(&interpreter, source)?;
+
+    assert!(matches!(get_global(&interpreter, "a")?, Literal::String(s) if s == "yes"));
+    assert!(matches!(get_global(&interpreter, "b")?, Literal::Number(n) if n == 30.0));
+    assert!(matches!(get_global(&interpreter, "c")?, Literal::Number(n) if n == 2.0));
+    // only the chosen branch is evaluated
+    assert!(matches!(get_global(&interpreter, "calls")?, Literal::Number(n) if n == 1.0));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_super() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "class A { name() { return \"A\"; } }\n\
+                  class B < A {\n\
+                    name() { return \"B of \" + super.name(); }\n\
+                    missing() { return super.missing(); }\n\
+                    field() { this.x = 1; return super.x; }\n\
+                  }\n\
+                  var name = B().name();";
+    run(&interpreter, source)?;
+
+    assert!(matches!(get_global(&interpreter, "name")?, Literal::String(s) if s == "B of A"));
+
+    let message = Some("Undefined superclass method 'missing'; super can only access methods.");
+    let res = run(&interpreter, "B().missing();");
+    assert!(res.is_err_and(|e| e.is_runtime_error() && e.get_message() == message));
+    let message = Some("Undefined superclass method 'x'; super can only access methods.");
+    let res = run(&interpreter, "B().field();");
+    assert!(res.is_err_and(|e| e.is_runtime_error() && e.get_message() == message));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_for_closures_capture_each_iteration() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var fns = [nil, nil, nil];\n\
+                  for (var i = 0; i < 3; i = i + 1) {\n\
+                    fun f() { return i; }\n\
+                    fns[i] = f;\n\
+                    if (i == 1) { i = 10; i = 1; continue; }\n\
+                  }\n\
+                  var captured = [fns[0](), fns[1](), fns[2]()];\n\
+                  var total = 0;\n\
+                  for (var i = 0; i < 3; i = i + 1) total = total + i;";
+    run(&interpreter, source)?;
+
+    assert!(matches!(
+      get_global(&interpreter, "captured")?,
+      Literal::Array(a) if *a.borrow() == vec![Literal::Number(0.0), Literal::Number(1.0), Literal::Number(2.0)]
+    ));
+    assert!(matches!(get_global(&interpreter, "total")?, Literal::Number(n) if n == 3.0));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_destructuring_for_closures_capture_each_iteration() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var fns = [nil, nil, nil];\n\
+                  for (var [i, j] = [0, 10]; i < 3; [i, j] = [i + 1, j + 10]) {\n\
+                    fun f() { return i + j; }\n\
+                    fns[i] = f;\n\
+                  }\n\
+                  var captured = [fns[0](), fns[1](), fns[2]()];";
+    run(&interpreter, source)?;
+
+    assert!(matches!(
+      get_global(&interpreter, "captured")?,
+      Literal::Array(a) if *a.borrow() == vec![Literal::Number(10.0), Literal::Number(21.0), Literal::Number(32.0)]
+    ));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_labelled_break_and_continue() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var pairs = 0;\n\
+                  outer: for (var i = 0; i < 3; i = i + 1) {\n\
+                    for (var j = 0; j < 3; j = j + 1) {\n\
+                      if (j == 1) continue outer;\n\
+                      pairs = pairs + 1;\n\
+                    }\n\
+                  }\n\
+                  var inner = 0;\n\
+                  outer: while (true) {\n\
+                    while (true) {\n\
+                      inner = inner + 1;\n\
+                      break outer;\n\
+                    }\n\
+                  }";
+    run(&interpreter, source)?;
+
+    assert!(matches!(get_global(&interpreter, "pairs")?, Literal::Number(n) if n == 3.0));
+    assert!(matches!(get_global(&interpreter, "inner")?, Literal::Number(n) if n == 1.0));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_undefined_loop_label() {
+    let interpreter = Interpreter::new();
+    let source = "outer: while (true) {\n\
+                    fun f() { while (true) break outer; }\n\
+                    break;\n\
+                  }\n\
+                  while (true) break missing;";
+    assert!(run(&interpreter, source).is_err_and(|e| !e.is_runtime_error()));
+  }
+
+  #[test]
+  fn test_clock_millis() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    run(
+      &interpreter,
+      "var a = clock_millis();\nvar b = clock_millis();",
+    )?;
+
+    let (Literal::Number(a), Literal::Number(b)) = (
+      get_global(&interpreter, "a")?,
+      get_global(&interpreter, "b")?,
+    ) else {
+      panic!("clock_millis should return numbers");
+    };
+    assert!(a >= 0.0 && b >= a);
+
+    assert!(run(&interpreter, "clock_millis(1);").is_err_and(|e| e.is_runtime_error()));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_deep_recursion() -> Result<(), LoxError> {
+    // beyond the defaults, as on the thread the command line starts
+    std::thread::Builder::new()
+      .stack_size(STACK_SIZE)
+      .spawn(|| {
+        let capture = Capture::default();
+        let interpreter = Interpreter::new()
+          .with_output(capture.clone())
+          .with_max_depth(STACK_SIZE_MAX_CALL_DEPTH);
+        let source = format!(
+          "fun f(n) {{ if (n == 0) return 0; return 1 + f(n - 1); }}\n\
+           print f(900);\n\
+           fun g(n) {{ {} if (n > 0) g(n - 1); {} }}\n\
+           g(250);\n\
+           print \"done\";",
+          "{".repeat(60),
+          "}".repeat(60)
+        );
+        assert!(run(&interpreter, &source).is_ok());
+        assert_eq!(capture.contents(), "900\ndone\n");
+      })
+      .map_err(|_| LoxError::system_error("COULD NOT SPAWN THREAD."))?
+      .join()
+      .map_err(|_| LoxError::system_error("THREAD PANICKED."))
+  }
+
+  #[test]
+  fn test_stack_overflow() -> Result<(), LoxError> {
+    // the default limits must hold on a main thread's stack, which is larger
+    // than a test thread's
+    std::thread::Builder::new()
+      .stack_size(8 * 1024 * 1024)
+      .spawn(|| {
+        let interpreter = Interpreter::new();
+        for source in [
+          "fun f(n) { return f(n + 1); }\nf(0);",
+          "fun f() { f(); }\nf();",
+          "fun f() { fun g() { f(); } g(); }\nf();",
+          "class A { init() { A(); } }\nA();",
+        ] {
+          assert!(run(&interpreter, source).is_err_and(|e| e.is_runtime_error()));
+        }
+
+        // statements and blocks count towards the limit as well
+        let source = format!(
+          "fun g(n) {{ {} g(n + 1); {} }}\ng(0);",
+          "{".repeat(60),
+          "}".repeat(60)
+        );
+        assert!(run(&interpreter, &source)
+          .is_err_and(|e| e.is_runtime_error() && e.get_message() == Some("Stack overflow.")));
+
+        let source = format!(
+          "fun h(n) {{ {} h(n + 1); }}\nh(0);",
+          "if (true) ".repeat(60)
+        );
+        assert!(run(&interpreter, &source)
+          .is_err_and(|e| e.is_runtime_error() && e.get_message() == Some("Stack overflow.")));
+
+        let source = format!("{}var a = 1; {}", "{".repeat(200), "}".repeat(200));
+        assert!(run(&interpreter, &source).is_ok());
+      })
+      .map_err(|_| LoxError::system_error("COULD NOT SPAWN THREAD."))?
+      .join()
+      .map_err(|_| LoxError::system_error("THREAD PANICKED."))
+  }
+
+  #[test]
+  fn test_deepest_code_that_parses_runs() -> Result<(), LoxError> {
+    std::thread::Builder::new()
+      .stack_size(8 * 1024 * 1024)
+      .spawn(|| {
+        let nested = |open: &str, inner: &str, close: &str, n: usize| {
+          format!("{}{}{}", open.repeat(n), inner, close.repeat(n))
+        };
+        let sources: [&dyn Fn(usize) -> String; 9] = [
+          &|n| format!("print {};", nested("(", "1", ")", n)),
+          &|n| format!("print 1{};", " + 1".repeat(n)),
+          &|n| format!("print {}1;", "-".repeat(n)),
+          &|n| format!("print {};", nested("[", "1", "]", n)),
+          &|n| format!("fun f(x) {{ return x; }} {};", nested("f(", "1", ")", n)),
+          &|n| nested("{", "print 1;", "}", n),
+          &|n| format!("{}print 1;", "if (true) ".repeat(n)),
+          &|n| format!("{}f();", nested("fun f() {", "print 1;", "}", n)),
+          &|n| format!("var a = 1;\n{}1;", "a = ".repeat(n)),
+        ];
+
+        // the deepest code of every kind that parses either runs or stops at
+        // a runtime error, and never overflows the native stack
+        for source in sources {
+          let parses = |n| {
+            let mut scanner = Scanner::new(&source(n));
+            let tokens = scanner.scan_tokens();
+            tokens.is_ok_and(|t| Parser::new(t).parse().is_ok())
+          };
+          let (mut deepest, mut shallowest_error) = (1, 1_000);
+          while shallowest_error - deepest > 1 {
+            let n = (deepest + shallowest_error) / 2;
+            if parses(n) {
+              deepest = n;
+            } else {
+              shallowest_error = n;
+            }
+          }
+
+          let interpreter = Interpreter::new().with_output(std::io::sink());
+          let res = run(&interpreter, &source(deepest));
+          assert!(res.is_ok() || res.is_err_and(|e| e.is_runtime_error()));
+        }
+      })
+      .map_err(|_| LoxError::system_error("COULD NOT SPAWN THREAD."))?
+      .join()
+      .map_err(|_| LoxError::system_error("THREAD PANICKED."))
+  }
+
+  #[test]
+  fn test_mixed_comparison() {
+    let interpreter = Interpreter::new();
+    let res = run(&interpreter, "print \"a\" < 1;");
+    assert!(res.is_err_and(|e| e.get_message() == Some("Cannot compare string and number.")));
+
+    let res = run(&interpreter, "print 1 >= \"a\";");
+    assert!(res.is_err_and(|e| e.get_message() == Some("Cannot compare number and string.")));
+
+    let res = run(&interpreter, "print true <= nil;");
+    assert!(res.is_err_and(|e| e.get_message() == Some("Cannot compare boolean and nil.")));
+  }
+
+  #[test]
+  fn test_boolean_ordering() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var lt = false < true;\n\
+                  var le = true <= true;\n\
+                  var gt = false > true;\n\
+                  var ge = false >= false;";
+    run(&interpreter, source)?;
+
+    for (name, expected) in [("lt", true), ("le", true), ("gt", false), ("ge", true)] {
+      assert!(matches!(get_global(&interpreter, name)?, Literal::Boolean(b) if b == expected));
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_variadic_function() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "fun f(a, ...rest) { return rest; }\n\
+                  var none = f(1);\n\
+                  var some = f(1, 2, 3);";
+    run(&interpreter, source)?;
+
+    assert!(
+      matches!(get_global(&interpreter, "none")?, Literal::Array(a) if a.borrow().is_empty())
+    );
+    assert!(
+      matches!(get_global(&interpreter, "some")?, Literal::Array(a) if a.borrow().len().eq(&2))
+    );
+
+    let Literal::Function(f) = get_global(&interpreter, "f")? else {
+      panic!("f should be a function");
+    };
+    assert_eq!((f.min_arity(), f.max_arity()), (1, UNBOUNDED_ARITY));
+
+    let res = run(&interpreter, "f();");
+    assert!(res.is_err_and(
+      |e| e.get_message() == Some("Expected at least 1 arguments but got 0 in call to 'f'.")
+    ));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_instanceof() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "class Base {}\n\
+                  class Sub < Base {}\n\
+                  class Other {}\n\
+                  var sub = Sub();\n\
+                  var is_sub = sub instanceof Sub;\n\
+                  var is_base = sub instanceof Base;\n\
+                  var is_other = sub instanceof Other;\n\
+                  var is_base_sub = Base() instanceof Sub;\n\
+                  var is_number = 1 instanceof Base;";
+    run(&interpreter, source)?;
+
+    for (name, expected) in [
+      ("is_sub", true),
+      ("is_base", true),
+      ("is_other", false),
+      ("is_base_sub", false),
+      ("is_number", false),
+    ] {
+      assert!(matches!(get_global(&interpreter, name)?, Literal::Boolean(b) if b == expected));
+    }
+
+    let res = run(&interpreter, "print sub instanceof 1;");
+    assert!(res.is_err_and(|e| e.is_runtime_error()));
+
+    // classes are told apart by identity, not by shape
+    let capture = Capture::default();
+    let interpreter = Interpreter::new().with_output(capture.clone());
+    let source = "class A {}\n\
+                  var X = A;\n\
+                  class A {}\n\
+                  print X() instanceof A;\n\
+                  print X() instanceof X;\n\
+                  print memoize(A)() instanceof A;";
+    run(&interpreter, source)?;
+    assert_eq!(capture.contents(), "false\ntrue\ntrue\n");
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_fields() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "class Point {}\n\
+                  var p = Point();\n\
+                  p.y = 2;\n\
+                  p.x = 1;\n\
+                  var names = fields(p);";
+    run(&interpreter, source)?;
+
+    let Literal::Array(names) = get_global(&interpreter, "names")? else {
+      panic!("fields should return an array");
+    };
+    assert_eq!(
+      names
+        .borrow()
+        .iter()
+        .map(|n| n.to_string())
+        .collect::<Vec<String>>(),
+      vec!["x", "y"]
+    );
+
+    let res = run(&interpreter, "fields(1);");
+    assert!(res.is_err_and(|e| e.is_runtime_error()));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_else_if_chain() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "fun sign(n) {\n\
+                    if (n < 0) return \"negative\";\n\
+                    else if (n == 0) return \"zero\";\n\
+                    else return \"positive\";\n\
+                  }\n\
+                  var a = sign(-1);\n\
+                  var b = sign(0);\n\
+                  var c = sign(1);";
+    run(&interpreter, source)?;
+
+    for (name, expected) in [("a", "negative"), ("b", "zero"), ("c", "positive")] {
+      assert!(matches!(get_global(&interpreter, name)?, Literal::String(s) if s == expected));
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_print_err() -> Result<(), LoxError> {
+    let output = Capture::default();
+    let error_output = Capture::default();
+    let interpreter = Interpreter::new()
+      .with_output(output.clone())
+      .with_error_output(error_output.clone());
+
+    run(&interpreter, "print 1;\nprint_err(\"oops\");\nprint 2;")?;
+    assert_eq!(output.contents(), "1\n2\n");
+    assert_eq!(error_output.contents(), "oops\n");
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_function_hoisting() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var top = is_even(10);\n\
+                  fun is_even(n) { if (n == 0) return true; return is_odd(n - 1); }\n\
+                  fun is_odd(n) { if (n == 0) return false; return is_even(n - 1); }\n\
+                  var local;\n\
+                  {\n\
+                    local = is_small_odd(3);\n\
+                    fun is_small_even(n) { if (n == 0) return true; return is_small_odd(n - 1); }\n\
+                    fun is_small_odd(n) { if (n == 0) return false; return is_small_even(n - 1); }\n\
+                  }";
+    run(&interpreter, source)?;
+
+    assert!(matches!(
+      get_global(&interpreter, "top")?,
+      Literal::Boolean(true)
+    ));
+    assert!(matches!(
+      get_global(&interpreter, "local")?,
+      Literal::Boolean(true)
+    ));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_function_hoisting_redefinition() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var before = f();\n\
+                  fun f() { return 1; }\n\
+                  var between = f();\n\
+                  fun f() { return 2; }\n\
+                  var after = f();";
+    run(&interpreter, source)?;
+
+    assert!(matches!(get_global(&interpreter, "before")?, Literal::Number(n) if n == 2.0));
+    assert!(matches!(get_global(&interpreter, "between")?, Literal::Number(n) if n == 1.0));
+    assert!(matches!(get_global(&interpreter, "after")?, Literal::Number(n) if n == 2.0));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_integer_division() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var a = 7 ~/ 2;\n\
+                  var b = -7 ~/ 2;\n\
+                  var c = 1 + 9 ~/ 3 * 2; // floored, not a comment";
+    run(&interpreter, source)?;
+
+    assert!(matches!(get_global(&interpreter, "a")?, Literal::Number(n) if n == 3.0));
+    assert!(matches!(get_global(&interpreter, "b")?, Literal::Number(n) if n == -4.0));
+    assert!(matches!(get_global(&interpreter, "c")?, Literal::Number(n) if n == 7.0));
+
+    let res = run(&interpreter, "print 1 ~/ 0;");
+    assert!(res.is_err_and(|e| e.get_message() == Some("Division by zero.")));
+
+    let res = run(&interpreter, "print \"a\" ~/ 2;");
+    assert!(res.is_err_and(|e| e.is_runtime_error()));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_max_call_depth() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new().with_max_depth(50);
+    run(
+      &interpreter,
+      "fun count(n) { if (n == 0) return 0; return count(n - 1) + 1; }\nvar a = count(49);",
+    )?;
+    assert!(matches!(get_global(&interpreter, "a")?, Literal::Number(n) if n == 49.0));
+
+    let res = run(&interpreter, "fun forever() { forever(); }\nforever();");
+    assert!(
+      res.is_err_and(|e| e.get_message() == Some("Stack overflow: maximum call depth exceeded."))
+    );
+
+    // the depth unwinds with the error, so later calls still work
+    run(&interpreter, "var b = count(49);")?;
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_nan_and_infinity() -> Result<(), LoxError> {
+    let capture = Capture::default();
+    let interpreter = Interpreter::new().with_output(capture.clone());
+    let source = "var nan = 0 / 0;\n\
+                  print nan;\n\
+                  print 1 / 0;\n\
+                  print -1 / 0;\n\
+                  print nan == nan;\n\
+                  print nan != nan;\n\
+                  print 1 / 0 == 1 / 0;\n\
+                  print 1 / 0 == -1 / 0;\n\
+                  print nan == 1;";
+    run(&interpreter, source)?;
+
+    assert_eq!(
+      capture.contents(),
+      "NaN\nInfinity\n-Infinity\ntrue\nfalse\ntrue\nfalse\nfalse\n"
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_bound_method_reference() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "class Named { name() { return this.n; } }\n\
+                  var first = Named();\n\
+                  first.n = \"first\";\n\
+                  var name = first.name;\n\
+                  var second = Named();\n\
+                  second.n = \"second\";\n\
+                  second.borrowed = name;\n\
+                  var called = name();\n\
+                  var borrowed = second.borrowed();";
+    run(&interpreter, source)?;
+
+    assert!(matches!(get_global(&interpreter, "called")?, Literal::String(s) if s == "first"));
+    assert!(matches!(get_global(&interpreter, "borrowed")?, Literal::String(s) if s == "first"));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_operand_type_errors() {
+    let interpreter = Interpreter::new();
+    let cases = [
+      (
+        "\"a\" - \"b\";",
+        "Operands must be numbers, not both string.",
+      ),
+      ("1 * nil;", "Operands must be numbers, not number and nil."),
+      (
+        "true + false;",
+        "Operands must be two numbers or two strings, not both boolean.",
+      ),
+      (
+        "nil + \"a\";",
+        "Operands must be two numbers or two strings, not nil and string.",
+      ),
+    ];
+
+    for (source, message) in cases {
+      let res = run(&interpreter, source);
+      assert!(
+        res.is_err_and(|e| e.get_message() == Some(message)),
+        "{source}"
+      );
+    }
+  }
+
+  #[test]
+  fn test_strict_concat() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    run(&interpreter, "var a = \"a\" + 1;\nvar b = 1 + \"b\";")?;
+    assert!(matches!(get_global(&interpreter, "a")?, Literal::String(s) if s == "a1"));
+    assert!(matches!(get_global(&interpreter, "b")?, Literal::String(s) if s == "1b"));
+
+    let interpreter = Interpreter::new().with_strict_concat(true);
+    run(&interpreter, "var c = \"c\" + \"d\";")?;
+    assert!(matches!(get_global(&interpreter, "c")?, Literal::String(s) if s == "cd"));
+
+    for source in ["print \"a\" + 1;", "print 1 + \"b\";"] {
+      let res = run(&interpreter, source);
+      assert!(res.is_err_and(|e| e
+        .get_message()
+        .is_some_and(|m| m.starts_with("Operands must be two numbers or two strings, not "))));
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_fmt_number() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    run(
+      &interpreter,
+      "var pi = fmt_number(3.14159, 2);\nvar whole = fmt_number(2, 0);",
+    )?;
+
+    assert!(matches!(get_global(&interpreter, "pi")?, Literal::String(s) if s == "3.14"));
+    assert!(matches!(get_global(&interpreter, "whole")?, Literal::String(s) if s == "2"));
+
+    for source in [
+      "fmt_number(1, -1);",
+      "fmt_number(1, 1.5);",
+      "fmt_number(1, 1000);",
+      "fmt_number(\"1\", 2);",
+    ] {
+      assert!(run(&interpreter, source).is_err_and(|e| e.is_runtime_error()));
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_chr_and_ord() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    run(
+      &interpreter,
+      "var a = ord(\"A\");\nvar b = chr(66);\nvar smile = chr(ord(\"\u{1F600}\"));",
+    )?;
+
+    assert!(matches!(get_global(&interpreter, "a")?, Literal::Number(n) if n == 65.0));
+    assert!(matches!(get_global(&interpreter, "b")?, Literal::String(s) if s == "B"));
+    assert!(matches!(get_global(&interpreter, "smile")?, Literal::String(s) if s == "\u{1F600}"));
+
+    for source in [
+      "ord(\"\");",
+      "ord(\"ab\");",
+      "ord(1);",
+      "chr(65.5);",
+      "chr(-1);",
+      "chr(55296);",
+      "chr(\"A\");",
+    ] {
+      assert!(run(&interpreter, source).is_err_and(|e| e.is_runtime_error()));
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_inherited_static_method() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "class Base {\n\
+                    class square(n) { return n * n; }\n\
+                    class name() { return \"base\"; }\n\
+                  }\n\
+                  class Sub < Base {\n\
+                    class name() { return \"sub\"; }\n\
+                  }\n\
+                  var squared = Sub.square(3);\n\
+                  var sub_name = Sub.name();\n\
+                  var base_name = Base.name();";
+    run(&interpreter, source)?;
+
+    assert!(matches!(get_global(&interpreter, "squared")?, Literal::Number(n) if n == 9.0));
+    assert!(matches!(get_global(&interpreter, "sub_name")?, Literal::String(s) if s == "sub"));
+    assert!(matches!(get_global(&interpreter, "base_name")?, Literal::String(s) if s == "base"));
+
+    let res = run(&interpreter, "Base.missing();");
+    assert!(res.is_err_and(|e| e.get_message() == Some("Undefined property 'missing'.")));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_copy() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "class Point {}\n\
+                  class Tag {}\n\
+                  var original = Point();\n\
+                  original.x = 1;\n\
+                  original.tag = Tag();\n\
+                  var copied = copy(original);\n\
+                  copied.x = 2;\n\
+                  copied.tag.name = \"shared\";\n\
+                  var x = original.x;\n\
+                  var copied_x = copied.x;\n\
+                  var tag_name = original.tag.name;\n\
+                  var is_point = copied instanceof Point;";
+    run(&interpreter, source)?;
+
+    assert!(matches!(get_global(&interpreter, "x")?, Literal::Number(n) if n == 1.0));
+    assert!(matches!(get_global(&interpreter, "copied_x")?, Literal::Number(n) if n == 2.0));
+    assert!(matches!(get_global(&interpreter, "tag_name")?, Literal::String(s) if s == "shared"));
+    assert!(matches!(
+      get_global(&interpreter, "is_point")?,
+      Literal::Boolean(true)
+    ));
+
+    let res = run(&interpreter, "copy(1);");
+    assert!(res.is_err_and(|e| e.get_message() == Some("Can only copy instances.")));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_arity_error_names_callee() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    run(&interpreter, "fun foo(a, b) {}\nclass Point {}")?;
+
+    for (source, message) in [
+      (
+        "foo(1);",
+        "Expected 2 arguments but got 1 in call to 'foo'.",
+      ),
+      (
+        "clock(1);",
+        "Expected 0 arguments but got 1 in call to 'clock'.",
+      ),
+      (
+        "Point(1);",
+        "Expected 0 arguments but got 1 in call to 'Point'.",
+      ),
+    ] {
+      let res = run(&interpreter, source);
+      assert!(res.is_err_and(|e| e.get_message() == Some(message)));
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_globals_snapshot() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    run(&interpreter, "var x = 5;\n{ var y = 6; }")?;
+
+    let globals = interpreter.globals_snapshot();
+    assert!(matches!(globals.get("x"), Some(Literal::Number(n)) if *n == 5.0));
+    assert!(matches!(
+      globals.get("clock"),
+      Some(Literal::NativeFunction(_))
+    ));
+    assert!(!globals.contains_key("y"));
+
+    // later definitions do not leak into an earlier snapshot
+    run(&interpreter, "var z = 7;")?;
+    assert!(!globals.contains_key("z"));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_postfix_increment() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var i = 1;\n\
+                  var old = i++;\n\
+                  var total = 0;\n\
+                  for (var j = 0; j < 3; j++) total = total + j;\n\
+                  class Counter {}\n\
+                  var c = Counter();\n\
+                  c.n = 5;\n\
+                  var old_n = c.n--;\n\
+                  var n = c.n;\n\
+                  fun local() { var k = 10; k--; k--; return k; }\n\
+                  var k = local();";
+    run(&interpreter, source)?;
+
+    for (name, expected) in [
+      ("i", 2.0),
+      ("old", 1.0),
+      ("total", 3.0),
+      ("old_n", 5.0),
+      ("n", 4.0),
+      ("k", 8.0),
+    ] {
+      assert!(matches!(get_global(&interpreter, name)?, Literal::Number(n) if n == expected));
+    }
+
+    let res = run(&interpreter, "var s = \"a\";\ns++;");
+    assert!(res.is_err_and(|e| e.get_message() == Some("Operand must be a number.")));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_doubled_minus_is_not_decrement() -> Result<(), LoxError> {
+    let capture = Capture::default();
+    let interpreter = Interpreter::new().with_output(capture.clone());
+    let source = "var a = 5;\n\
+                  var b = 2;\n\
+                  print a--b;\n\
+                  print --a;\n\
+                  print ++a;\n\
+                  print 1 - -1;\n\
+                  print 1--1;\n\
+                  print a++ + b;\n\
+                  print a;";
+    run(&interpreter, source)?;
+    assert_eq!(capture.contents(), "7\n5\n5\n2\n2\n7\n6\n");
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_approx() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var close = approx(0.1 + 0.2, 0.3);\n\
+                  var far = approx(1, 1.001);\n\
+                  var loose = approx(1, 1.001, 0.01);";
+    run(&interpreter, source)?;
+
+    for (name, expected) in [("close", true), ("far", false), ("loose", true)] {
+      assert!(matches!(get_global(&interpreter, name)?, Literal::Boolean(b) if b == expected));
+    }
+
+    let res = run(&interpreter, "approx(1);");
+    assert!(res.is_err_and(|e| {
+      e.get_message() == Some("Expected 2 to 3 arguments but got 1 in call to 'approx'.")
+    }));
+    let res = run(&interpreter, "approx(1, 2, 3, 4);");
+    assert!(res.is_err_and(|e| e.is_runtime_error()));
+    let res = run(&interpreter, "approx(1, \"1\");");
+    assert!(res.is_err_and(|e| e.get_message() == Some("Arguments must be numbers.")));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_min_and_max_arity() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    run(
+      &interpreter,
+      "class Point { init(x, ...rest) {} }\nvar remembered = memoize(approx);",
+    )?;
+
+    for (name, expected) in [
+      ("clock", (0, 0)),
+      ("approx", (2, 3)),
+      ("remembered", (2, 3)),
+      ("Point", (1, UNBOUNDED_ARITY)),
+    ] {
+      let (min_arity, max_arity) = match get_global(&interpreter, name)? {
+        Literal::NativeFunction(f) => (f.min_arity(), f.max_arity()),
+        Literal::Class(c) => (c.min_arity(), c.max_arity()),
+        _ => panic!("{name} should be callable"),
+      };
+      assert_eq!((min_arity, max_arity), expected);
+    }
+
+    run(&interpreter, "remembered(1, 1);\nremembered(1, 1, 0.1);")?;
+    let res = run(&interpreter, "Point();");
+    assert!(res.is_err_and(|e| {
+      e.get_message() == Some("Expected at least 1 arguments but got 0 in call to 'Point'.")
+    }));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_file_io() -> Result<(), LoxError> {
+    let path = std::env::temp_dir().join(format!("jlox_io_test_{}", std::process::id()));
+    let path = path.to_string_lossy().replace('\\', "/");
+    let source = format!(
+      "var written = write_file(\"{path}\", \"hello\");\n\
+       var read = read_file(\"{path}\");\n\
+       var missing = read_file(\"{path}.missing\");"
+    );
+
+    let interpreter = Interpreter::new();
+    let res = run(&interpreter, &source);
+    assert!(res.is_err_and(|e| e.get_message() == Some("File I/O is disabled.")));
+
+    let interpreter = Interpreter::new().enable_io();
+    let res = run(&interpreter, &source);
+    std::fs::remove_file(&path).ok();
+    res?;
+
+    assert!(matches!(
+      get_global(&interpreter, "written")?,
+      Literal::Boolean(true)
+    ));
+    assert!(matches!(get_global(&interpreter, "read")?, Literal::String(s) if s == "hello"));
+    assert!(matches!(get_global(&interpreter, "missing")?, Literal::Nil));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_nil_coalescing() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var calls = 0;\n\
+                  fun fallback() { calls = calls + 1; return 5; }\n\
+                  var from_nil = nil ?? 5;\n\
+                  var kept = 3 ?? fallback();\n\
+                  var falsy = false ?? fallback();\n\
+                  var chained = nil ?? nil ?? fallback();";
+    run(&interpreter, source)?;
+
+    assert!(matches!(get_global(&interpreter, "from_nil")?, Literal::Number(n) if n == 5.0));
+    assert!(matches!(get_global(&interpreter, "kept")?, Literal::Number(n) if n == 3.0));
+    assert!(matches!(
+      get_global(&interpreter, "falsy")?,
+      Literal::Boolean(false)
+    ));
+    assert!(matches!(get_global(&interpreter, "chained")?, Literal::Number(n) if n == 5.0));
+    assert!(matches!(get_global(&interpreter, "calls")?, Literal::Number(n) if n == 1.0));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_describe() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "class Shape {}\n\
+                  class Square < Shape {\n\
+                    init(side) { this.side = side; }\n\
+                    toString() { return \"square\"; }\n\
+                    area() { return this.side * this.side; }\n\
+                  }\n\
+                  var shape = describe(Shape);\n\
+                  var square = describe(Square);";
+    run(&interpreter, source)?;
+
+    assert!(
+      matches!(get_global(&interpreter, "shape")?, Literal::String(s) if s == "class Shape {}")
+    );
+    assert!(matches!(
+      get_global(&interpreter, "square")?,
+      Literal::String(s) if s == "class Square < Shape { area, init, toString }"
+    ));
+
+    let res = run(&interpreter, "describe(Square(1));");
+    assert!(res.is_err_and(|e| e.get_message() == Some("Can only describe classes.")));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_string_natives() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var trimmed = trim(\"  a b \");\n\
+                  var upper_case = upper(\"aBc\");\n\
+                  var lower_case = lower(\"aBc\");\n\
+                  var parts = split(\"a,b,c\", \",\");\n\
+                  var chars = split(\"ab\", \"\");";
+    run(&interpreter, source)?;
+
+    for (name, expected) in [
+      ("trimmed", "a b"),
+      ("upper_case", "ABC"),
+      ("lower_case", "abc"),
+    ] {
+      assert!(matches!(get_global(&interpreter, name)?, Literal::String(s) if s == expected));
+    }
+
+    for (name, expected) in [("parts", vec!["a", "b", "c"]), ("chars", vec!["a", "b"])] {
+      let Literal::Array(a) = get_global(&interpreter, name)? else {
+        panic!("{name} should be an array");
+      };
+      let strings = a
+        .borrow()
+        .iter()
+        .map(Literal::to_string)
+        .collect::<Vec<String>>();
+      assert_eq!(strings, expected);
+    }
+
+    for source in [
+      "trim(1);",
+      "upper(nil);",
+      "lower(true);",
+      "split(\"a\", 1);",
+    ] {
+      assert!(run(&interpreter, source).is_err_and(|e| e.is_runtime_error()));
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_string_search() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var has = contains(\"hello\", \"ell\");\n\
+                  var lacks = contains(\"hello\", \"xyz\");\n\
+                  var found = index_of(\"hello\", \"llo\");\n\
+                  var missing = index_of(\"hello\", \"xyz\");\n\
+                  var wide = index_of(\"\u{e9}t\u{e9}\", \"t\");";
+    run(&interpreter, source)?;
+
+    assert!(matches!(
+      get_global(&interpreter, "has")?,
+      Literal::Boolean(true)
+    ));
+    assert!(matches!(
+      get_global(&interpreter, "lacks")?,
+      Literal::Boolean(false)
+    ));
+    for (name, expected) in [("found", 2.0), ("missing", -1.0), ("wide", 1.0)] {
+      assert!(matches!(get_global(&interpreter, name)?, Literal::Number(n) if n == expected));
+    }
+
+    let res = run(&interpreter, "contains(\"a\", 1);");
+    assert!(res.is_err_and(|e| e.get_message() == Some("Arguments must be strings.")));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_type_predicates() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    run(&interpreter, "class A {}\nfun f() {}")?;
+
+    let values = [
+      "nil", "1", "\"a\"", "true", "f", "clock", "A", "A()", "[]", "{}",
+    ];
+    let accepted: [(&str, &[&str]); 6] = [
+      ("is_nil", &["nil"]),
+      ("is_number", &["1"]),
+      ("is_string", &["\"a\""]),
+      ("is_bool", &["true"]),
+      ("is_callable", &["f", "clock", "A"]),
+      ("is_instance", &["A()"]),
+    ];
+    for (predicate, accepts) in accepted {
+      for value in values {
+        run(&interpreter, &format!("var res = {predicate}({value});"))?;
+        assert!(
+          matches!(get_global(&interpreter, "res")?, Literal::Boolean(b) if b == accepts.contains(&value)),
+          "{predicate}({value})"
+        );
+      }
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_array_push_pop_len() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var a = [];\n\
+                  var pushed = array_push(a, 1);\n\
+                  array_push(a, \"two\");\n\
+                  array_push(a, nil);\n\
+                  var full = array_len(a);\n\
+                  var last = array_pop(a);\n\
+                  var second = array_pop(a);\n\
+                  var first = array_pop(a);\n\
+                  var empty = array_pop(a);\n\
+                  var len = array_len(a);";
+    run(&interpreter, source)?;
+
+    assert!(matches!(get_global(&interpreter, "pushed")?, Literal::Number(n) if n == 1.0));
+    assert!(matches!(get_global(&interpreter, "full")?, Literal::Number(n) if n == 3.0));
+    assert!(matches!(get_global(&interpreter, "last")?, Literal::Nil));
+    assert!(matches!(get_global(&interpreter, "second")?, Literal::String(s) if s == "two"));
+    assert!(matches!(get_global(&interpreter, "first")?, Literal::Number(n) if n == 1.0));
+    assert!(matches!(get_global(&interpreter, "empty")?, Literal::Nil));
+    assert!(matches!(get_global(&interpreter, "len")?, Literal::Number(n) if n == 0.0));
+
+    let res = run(&interpreter, "array_push(\"a\", 1);");
+    assert!(res.is_err_and(|e| e.get_message() == Some("First argument must be an array.")));
+    let res = run(&interpreter, "array_len(nil);");
+    assert!(res.is_err_and(|e| e.get_message() == Some("Argument must be an array.")));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_min_max() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "var a = min(2, 1);\n\
+                  var b = max(2, 1);\n\
+                  var c = min(3, -1, 4, 1, 5);\n\
+                  var d = max(3, -1, 4, 1, 5);\n\
+                  var e = max(1, 0 / 0, 2);";
+    run(&interpreter, source)?;
+
+    assert!(matches!(get_global(&interpreter, "a")?, Literal::Number(n) if n == 1.0));
+    assert!(matches!(get_global(&interpreter, "b")?, Literal::Number(n) if n == 2.0));
+    assert!(matches!(get_global(&interpreter, "c")?, Literal::Number(n) if n == -1.0));
+    assert!(matches!(get_global(&interpreter, "d")?, Literal::Number(n) if n == 5.0));
+    assert!(matches!(get_global(&interpreter, "e")?, Literal::Number(n) if n.is_nan()));
+
+    let res = run(&interpreter, "min(1, \"2\");");
+    assert!(res.is_err_and(|e| e.get_message() == Some("Arguments must be numbers.")));
+    let res = run(&interpreter, "max(1);");
+    assert!(res.is_err_and(
+      |e| e.get_message() == Some("Expected at least 2 arguments but got 1 in call to 'max'.")
+    ));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_random() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let sequence = |seed: &str| -> Result<Vec<f64>, LoxError> {
+      run(
+        &interpreter,
+        &format!("random_seed({seed});\nvar r = [random(), random(), random(), random()];"),
+      )?;
+      let Literal::Array(a) = get_global(&interpreter, "r")? else {
+        panic!("r is not an array");
+      };
+      let numbers = a.borrow().iter().map(|n| n.get_number()).collect();
+      numbers
+    };
+
+    let first = sequence("42")?;
+    assert_eq!(first, sequence("42")?);
+    assert_ne!(first, sequence("43")?);
+    assert!(sequence("-7")?
+      .iter()
+      .chain(first.iter())
+      .all(|n| (0.0..1.0).contains(n)));
+
+    let res = run(&interpreter, "random_seed(1.5);");
+    assert!(res.is_err_and(|e| e.get_message() == Some("Seed must be a whole number.")));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_sleep() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    run(&interpreter, "var res = sleep(0);")?;
+    assert!(matches!(get_global(&interpreter, "res")?, Literal::Nil));
+
+    let message = Some("Argument must be a non-negative number of seconds.");
+    for source in ["sleep(-1);", "sleep(0 / 0);", "sleep(1 / 0);"] {
+      let res = run(&interpreter, source);
+      assert!(res.is_err_and(|e| e.is_runtime_error() && e.get_message() == message));
+    }
+
+    let res = run(&interpreter, "sleep(\"1\");");
+    assert!(res.is_err_and(|e| e.get_message() == Some("Argument must be a number.")));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_debug() -> Result<(), LoxError> {
+    let capture = Capture::default();
+    let interpreter = Interpreter::new().with_output(capture.clone());
+    let source = "class A {}\n\
+                  print debug(5);\n\
+                  print debug(\"5\");\n\
+                  print debug([nil, true]);\n\
+                  print debug(A);\n\
+                  print debug(A());";
+    run(&interpreter, source)?;
+
+    assert_eq!(
+      capture.contents(),
+      "number(5)\nstring(\"5\")\narray([nil, boolean(true)])\nclass(A)\ninstance(A instance)\n"
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_method_resolution_order() -> Result<(), LoxError> {
+    let capture = Capture::default();
+    let interpreter = Interpreter::new().with_output(capture.clone());
+    let source = "class A {\n\
+                    name() { return \"A\"; }\n\
+                    only_a() { return \"only A\"; }\n\
+                    greet() { return \"hi from \" + this.name(); }\n\
+                  }\n\
+                  class B < A { name() { return \"B\"; } }\n\
+                  class C < B { name() { return \"C \" + super.name(); } }\n\
+                  var c = C();\n\
+                  print c.name();\n\
+                  print c.greet();\n\
+                  print c.only_a();\n\
+                  print B().greet();";
+    run(&interpreter, source)?;
+
+    assert_eq!(capture.contents(), "C B\nhi from C B\nonly A\nhi from B\n");
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_instance_identity() -> Result<(), LoxError> {
+    let capture = Capture::default();
+    let interpreter = Interpreter::new().with_output(capture.clone());
+    let source = "class P { init(x) { this.x = x; } }\n\
+                  var a = P(1);\n\
+                  var b = a;\n\
+                  print a == b;\n\
+                  print a == P(1);\n\
+                  print a != P(1);\n\
+                  print a == nil;\n\
+                  print nil == a;";
+    run(&interpreter, source)?;
+
+    assert_eq!(capture.contents(), "true\nfalse\ntrue\nfalse\nfalse\n");
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_instance_equals_method() -> Result<(), LoxError> {
+    let capture = Capture::default();
+    let interpreter = Interpreter::new().with_output(capture.clone());
+    let source = "class P {\n\
+                    init(x) { this.x = x; }\n\
+                    equals(other) { return other instanceof P and this.x == other.x; }\n\
+                  }\n\
+                  class Q < P {}\n\
+                  print P(1) == P(1);\n\
+                  print P(1) != P(1);\n\
+                  print P(1) == P(2);\n\
+                  print Q(1) == P(1);\n\
+                  print P(1) == 1;";
+    run(&interpreter, source)?;
+
+    assert_eq!(capture.contents(), "true\nfalse\nfalse\ntrue\nfalse\n");
+
+    for method in ["equals()", "equals(a, b)", "equals(a, b, ...rest)"] {
+      let source = format!("class R {{ {} {{ return true; }} }}\nR() == R();", method);
+      assert!(run(&interpreter, &source)
+        .is_err_and(|e| e.get_message() == Some("Method 'equals' must accept one argument.")));
+    }
+
+    let source = "class S { equals(...others) { return array_len(others) == 1; } }\n\
+                  print S() == S();\n\
+                  class T { equals(a, ...rest) { return array_len(rest) == 0; } }\n\
+                  print T() == T();";
+    run(&interpreter, source)?;
+    assert!(capture.contents().ends_with("true\ntrue\n"));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_constant_condition_still_runs() -> Result<(), LoxError> {
+    let capture = Capture::default();
+    let interpreter = Interpreter::new().with_output(capture.clone());
+    run(
+      &interpreter,
+      "if (true) print 1;\nwhile (false) print 2;\nprint 3;",
+    )?;
+    assert_eq!(capture.contents(), "1\n3\n");
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_unary_plus() -> Result<(), LoxError> {
+    let capture = Capture::default();
+    let interpreter = Interpreter::new().with_output(capture.clone());
+    run(
+      &interpreter,
+      "var a = -3;\nprint +5;\nprint +a;\nprint 1 + +2;",
+    )?;
+    assert_eq!(capture.contents(), "5\n-3\n3\n");
+
+    let res = run(&interpreter, "print +\"x\";");
+    assert!(
+      res.is_err_and(
+        |e| e.is_runtime_error() && e.get_message() == Some("Operand must be a number.")
+      )
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_destructuring() -> Result<(), LoxError> {
+    let capture = Capture::default();
+    let interpreter = Interpreter::new().with_output(capture.clone());
+    let source = "fun pair() { return [1, 2]; }\n\
+                  var [a, b] = pair();\n\
+                  print a + b;\n\
+                  [a, b] = [b, a];\n\
+                  print a;\n\
+                  print b;\n\
+                  {\n\
+                    var [c, d] = [\"x\", \"y\"];\n\
+                    [c, a] = [d, c];\n\
+                    print c + a;\n\
+                  }";
+    run(&interpreter, source)?;
+    assert_eq!(capture.contents(), "3\n2\n1\nyx\n");
+
+    let res = run(&interpreter, "var [x, y] = [1];");
+    assert!(res.is_err_and(|e| e.is_runtime_error()
+      && e.get_message() == Some("Expected 2 values to destructure but got 1.")));
+
+    let res = run(&interpreter, "[a, b] = 3;");
+    assert!(res.is_err_and(|e| e.is_runtime_error()
+      && e.get_message() == Some("Can only destructure arrays, not number.")));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_gensym() -> Result<(), LoxError> {
+    let capture = Capture::default();
+    let interpreter = Interpreter::new().with_output(capture.clone());
+    run(
+      &interpreter,
+      "var a = gensym();\nvar b = gensym();\nprint a;\nprint b;\nprint a == b;",
+    )?;
+    assert_eq!(capture.contents(), "__sym_1\n__sym_2\nfalse\n");
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_negative_zero() -> Result<(), LoxError> {
+    let capture = Capture::default();
+    let interpreter = Interpreter::new().with_output(capture.clone());
+    let source = "print -0;\n\
+                  print 0;\n\
+                  print 0 * -1;\n\
+                  print \"x\" + -0;\n\
+                  print -0 == 0;\n\
+                  print -0 < 0;\n\
+                  print 1 / -0;";
+    run(&interpreter, source)?;
+
+    assert_eq!(
+      capture.contents(),
+      "-0\n0\n-0\nx-0\ntrue\nfalse\n-Infinity\n"
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_to_json() -> Result<(), LoxError> {
+    let interpreter = Interpreter::new();
+    let source = "class Point {}\n\
+                  var p = Point();\n\
+                  p.y = [1, [2, \"three\"]];\n\
+                  p.x = nil;\n\
+                  var json = to_json(p);";
+    run(&interpreter, source)?;
+
+    assert!(matches!(
+      get_global(&interpreter, "json")?,
+      Literal::String(s) if s == r#"{"x":null,"y":[1,[2,"three"]]}"#
+    ));
+
+    let res = run(&interpreter, "p.self = p;\nto_json(p);");
+    assert!(res.is_err_and(|e| e.get_message() == Some("Cannot serialize cyclic structure.")));
+    let res = run(&interpreter, "to_json(Point);");
+    assert!(res.is_err_and(|e| e.get_message() == Some("Cannot serialize class.")));
+
+    Ok(())
+  }
 }
@@ -0,0 +1,150 @@
+use std::rc::Rc;
+
+use crate::{error::*, token::*};
+
+// arrays, maps and instances are shared, so a value can contain itself;
+// `visiting` holds the containers on the current path to catch that
+pub fn to_json(value: &Literal) -> Result<String, LoxError> {
+  serialize(value, &mut Vec::new())
+}
+
+fn serialize(value: &Literal, visiting: &mut Vec<usize>) -> Result<String, LoxError> {
+  match value {
+    // JSON has no NaN or Infinity, so these go the same way as in JavaScript
+    Literal::Number(n) if !n.is_finite() => Ok("null".to_string()),
+    Literal::Number(n) => Ok(n.to_string()),
+    Literal::String(s) => Ok(quote(s)),
+    Literal::Boolean(b) => Ok(b.to_string()),
+    Literal::Nil => Ok("null".to_string()),
+    Literal::Array(a) => within(Rc::as_ptr(a) as usize, visiting, |visiting| {
+      let elements = a
+        .borrow()
+        .iter()
+        .map(|e| serialize(e, visiting))
+        .collect::<Result<Vec<String>, LoxError>>()?;
+      Ok(format!("[{}]", elements.join(",")))
+    }),
+    Literal::Map(m) => within(Rc::as_ptr(m) as usize, visiting, |visiting| {
+      let entries = m
+        .borrow()
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.clone()))
+        .collect::<Vec<(String, Literal)>>();
+      object(entries, visiting)
+    }),
+    Literal::Instance(i) => within(Rc::as_ptr(i) as usize, visiting, |visiting| {
+      object(i.get_fields(), visiting)
+    }),
+    _ => Err(LoxError::new_native_error(&format!(
+      "Cannot serialize {}.",
+      value.type_name()
+    ))),
+  }
+}
+
+fn within(
+  address: usize,
+  visiting: &mut Vec<usize>,
+  f: impl FnOnce(&mut Vec<usize>) -> Result<String, LoxError>,
+) -> Result<String, LoxError> {
+  if visiting.contains(&address) {
+    return Err(LoxError::new_native_error(
+      "Cannot serialize cyclic structure.",
+    ));
+  }
+
+  visiting.push(address);
+  let res = f(visiting);
+  visiting.pop();
+  res
+}
+
+// sorted by key so the output does not depend on hash order
+fn object(
+  mut entries: Vec<(String, Literal)>,
+  visiting: &mut Vec<usize>,
+) -> Result<String, LoxError> {
+  entries.sort_by(|(l, _), (r, _)| l.cmp(r));
+
+  let members = entries
+    .iter()
+    .map(|(k, v)| Ok(format!("{}:{}", quote(k), serialize(v, visiting)?)))
+    .collect::<Result<Vec<String>, LoxError>>()?;
+  Ok(format!("{{{}}}", members.join(",")))
+}
+
+fn quote(s: &str) -> String {
+  let mut quoted = String::from('"');
+  for c in s.chars() {
+    match c {
+      '"' => quoted.push_str("\\\""),
+      '\\' => quoted.push_str("\\\\"),
+      '\n' => quoted.push_str("\\n"),
+      '\r' => quoted.push_str("\\r"),
+      '\t' => quoted.push_str("\\t"),
+      c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+      c => quoted.push(c),
+    }
+  }
+  quoted.push('"');
+
+  quoted
+}
+
+#[cfg(test)]
+mod test {
+  use std::{cell::RefCell, collections::HashMap};
+
+  use super::*;
+
+  #[test]
+  fn test_to_json() -> Result<(), LoxError> {
+    let array = Literal::Array(
+      RefCell::new(vec![
+        Literal::Number(1.0),
+        Literal::Number(2.5),
+        Literal::String("a \"b\"\n\u{1}".to_string()),
+        Literal::Boolean(true),
+        Literal::Nil,
+        Literal::Number(f64::NAN),
+      ])
+      .into(),
+    );
+    assert_eq!(
+      to_json(&array)?,
+      r#"[1,2.5,"a \"b\"\n\u0001",true,null,null]"#
+    );
+
+    let mut map = HashMap::new();
+    map.insert(MapKey::String("b".to_string()), array.clone());
+    map.insert(MapKey::Number(1.0), Literal::String("one".to_string()));
+    let map = Literal::Map(RefCell::new(map).into());
+    assert_eq!(
+      to_json(&map)?,
+      r#"{"1":"one","b":[1,2.5,"a \"b\"\n\u0001",true,null,null]}"#
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_shared_is_not_cyclic() -> Result<(), LoxError> {
+    let inner = Literal::Array(RefCell::new(vec![Literal::Number(1.0)]).into());
+    let outer = Literal::Array(RefCell::new(vec![inner.clone(), inner]).into());
+    assert_eq!(to_json(&outer)?, "[[1],[1]]");
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_cyclic() {
+    let array = Rc::new(RefCell::new(Vec::new()));
+    array.borrow_mut().push(Literal::Array(array.clone()));
+
+    let res = to_json(&Literal::Array(array.clone()));
+    assert!(res.is_err_and(|e| e.get_message() == Some("Cannot serialize cyclic structure.")));
+
+    // break the cycle so the array is freed
+    array.borrow_mut().clear();
+  }
+}
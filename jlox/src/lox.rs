@@ -1,81 +1,559 @@
 use std::{
-  fs::read_to_string,
-  io::{self, stdin, stdout, Write},
-  process::exit,
+  cell::RefCell,
+  fmt,
+  fs::{read_to_string, File, OpenOptions},
+  io::{sink, stdin, stdout, BufRead, Write},
+  num::NonZeroUsize,
+  path::{Path, PathBuf},
   rc::Rc,
+  time::{Duration, Instant},
 };
 
-use crate::{error::*, interpreter::*, parser::*, resolver::Resolver, scanner::*, stmt::*};
+use crate::{
+  error::*, interpreter::*, parser::*, resolver::Resolver, scanner::*, stmt::*, token::*,
+};
+
+pub struct BenchSummary {
+  pub runs: usize,
+  pub min: Duration,
+  pub median: Duration,
+  pub mean: Duration,
+}
+
+impl fmt::Display for BenchSummary {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "{:<12}{:>12}", "runs", self.runs)?;
+    for (name, duration) in [
+      ("min", self.min),
+      ("median", self.median),
+      ("mean", self.mean),
+    ] {
+      writeln!(f, "{name:<12}{:>9.3} ms", duration.as_secs_f64() * 1000.0)?;
+    }
+
+    Ok(())
+  }
+}
 
-#[derive(Default)]
 pub struct Lox {
   interpreter: Interpreter,
+  max_call_depth: usize,
+  timings: Option<RefCell<Vec<(&'static str, Duration)>>>,
+  history: Option<PathBuf>,
+  prompt: String,
+  continuation_prompt: String,
+}
+
+impl Default for Lox {
+  fn default() -> Lox {
+    Lox::new()
+  }
 }
 
 impl Lox {
   pub fn new() -> Lox {
     Lox {
       interpreter: Interpreter::new(),
+      max_call_depth: MAX_CALL_DEPTH,
+      timings: None,
+      history: None,
+      prompt: "> ".to_string(),
+      continuation_prompt: "... ".to_string(),
     }
   }
 
-  pub fn run_file(&self, path: &str) -> io::Result<()> {
-    let bytes = read_to_string(path)?;
+  pub fn with_prompt(mut self, prompt: &str) -> Lox {
+    self.prompt = prompt.to_string();
+    self
+  }
 
-    if let Err(e) = self.run(&bytes) {
-      if e.is_runtime_error() {
-        exit(70);
-      }
+  // shown instead of the prompt while a statement spans several lines
+  pub fn with_continuation_prompt(mut self, continuation_prompt: &str) -> Lox {
+    self.continuation_prompt = continuation_prompt.to_string();
+    self
+  }
+
+  // what to show before the next line, given the unfinished input so far
+  fn prompt_for(&self, buffer: &str) -> &str {
+    if buffer.is_empty() {
+      &self.prompt
+    } else {
+      &self.continuation_prompt
+    }
+  }
+
+  // keep every line entered at the prompt in `path`, across sessions
+  pub fn with_history(mut self, path: &Path) -> Lox {
+    self.history = Some(path.to_path_buf());
+    self
+  }
 
-      if !e.is_runtime_error() {
-        exit(65);
+  fn open_history(&self) -> Option<File> {
+    let path = self.history.as_ref()?;
+    let file = OpenOptions::new().create(true).append(true).open(path);
+    if file.is_err() {
+      eprintln!("Could not open history file; history will not be saved.");
+    }
+
+    file.ok()
+  }
+
+  fn record_history(history: &mut Option<File>, line: &str) {
+    if let Some(file) = history {
+      if writeln!(file, "{line}").is_err() {
+        eprintln!("Could not write history file; history will not be saved.");
+        history.take();
       }
     }
+  }
 
-    exit(0)
+  pub fn with_output(mut self, output: impl Write + 'static) -> Lox {
+    self.interpreter = self.interpreter.with_output(output);
+    self
+  }
+
+  // the default suits an ordinary thread; see `STACK_SIZE` for a larger one
+  pub fn with_max_depth(mut self, max_call_depth: usize) -> Lox {
+    self.max_call_depth = max_call_depth;
+    self.interpreter = self.interpreter.with_max_depth(max_call_depth);
+    self
+  }
+
+  pub fn enable_io(mut self) -> Lox {
+    self.interpreter = self.interpreter.enable_io();
+    self
+  }
+
+  // record how long each phase of `run` takes
+  pub fn with_timings(mut self) -> Lox {
+    self.timings = Some(RefCell::new(Vec::new()));
+    self
+  }
+
+  pub fn report_timings(&self) {
+    let Some(timings) = &self.timings else {
+      return;
+    };
+
+    let timings = timings.borrow();
+    let total = timings.iter().map(|(_, d)| *d).sum::<Duration>();
+
+    eprintln!("{:<12}{:>12}", "phase", "time (ms)");
+    for (phase, duration) in timings.iter().chain([("total", total)].iter()) {
+      eprintln!("{phase:<12}{:>12.3}", duration.as_secs_f64() * 1000.0);
+    }
+  }
+
+  fn timed<T>(&self, phase: &'static str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let res = f();
+
+    if let Some(timings) = &self.timings {
+      timings.borrow_mut().push((phase, start.elapsed()));
+    }
+
+    res
+  }
+
+  pub fn run_file(&self, path: &str) -> Result<(), LoxError> {
+    let bytes = read_to_string(path).map_err(|_| LoxError::system_error("ERROR OPENING FILE."))?;
+
+    self.run(&bytes)
   }
 
   pub fn run_prompt(&self) {
+    self.run_prompt_with(&mut stdin().lock(), &mut stdout());
+  }
+
+  fn run_prompt_with(&self, input: &mut impl BufRead, prompt: &mut impl Write) {
+    let mut history = self.open_history();
+    let mut buffer = String::new();
+
     loop {
-      print!("> ");
-      if stdout().flush().is_err() {
+      if write!(prompt, "{}", self.prompt_for(&buffer))
+        .and_then(|_| prompt.flush())
+        .is_err()
+      {
         break;
       }
 
       let mut line = String::new();
-      if stdin().read_line(&mut line).is_err() || line.is_empty() {
+      if input.read_line(&mut line).is_err() || line.is_empty() {
         break;
       }
 
-      self.run(&line).ok();
+      if !line.trim().is_empty() {
+        Lox::record_history(&mut history, line.trim_end());
+      }
+
+      // the commands only count on a line of their own
+      if buffer.is_empty() && line.trim().eq("!") {
+        break;
+      }
+
+      if buffer.is_empty() && line.trim().eq("@") {
+        self.interpreter.print_environment().ok();
+        continue;
+      }
+
+      // a trailing backslash asks for another line, and is dropped
+      if let Some(continued) = line.trim_end().strip_suffix('\\') {
+        buffer.push_str(continued);
+        buffer.push('\n');
+        continue;
+      }
+
+      buffer.push_str(&line);
+      if Lox::is_incomplete(&buffer) {
+        continue;
+      }
+
+      self.run_line(&buffer).ok();
+      buffer.clear();
     }
   }
 
-  pub fn run(&self, source: &str) -> Result<(), LoxError> {
-    if source.trim().eq("!") {
-      exit(0);
-    }
+  // more brackets opened than closed so far, as in the first line of a
+  // function; unterminated strings and other errors are left to `run_line`
+  fn is_incomplete(source: &str) -> bool {
+    // errors are reported once the whole input runs, not for every line of it
+    let mut scanner = Scanner::new(source).with_error_reporting(false);
+    scanner.scan_tokens().ok();
+
+    let depth = scanner
+      .get_tokens()
+      .iter()
+      .fold(0_i32, |depth, t| match t.get_type() {
+        TokenType::LeftBrace | TokenType::LeftBracket | TokenType::LeftSquareBracket => depth + 1,
+        TokenType::RightBrace | TokenType::RightBracket | TokenType::RightSquareBracket => {
+          depth - 1
+        }
+        _ => depth,
+      });
+    depth > 0
+  }
+
+  pub fn check_file(&self, path: &str) -> Result<(), LoxError> {
+    let bytes = read_to_string(path).map_err(|_| LoxError::system_error("ERROR OPENING FILE."))?;
+
+    self.check(&bytes)
+  }
+
+  // scans, parses and resolves without running anything; parsing carries on
+  // after a scan error so that every error is reported in one go
+  pub fn check(&self, source: &str) -> Result<(), LoxError> {
+    let mut scanner = Scanner::new(source);
+    let scanned = scanner.scan_tokens().map(|_| ());
+
+    let statements = self
+      .parser(scanner.get_tokens())
+      .parse()?
+      .into_iter()
+      .map(Rc::new)
+      .collect::<Vec<Rc<Stmt>>>();
+    scanned?;
+
+    Resolver::resolve_to_map(&statements.as_slice().into())?;
+    Ok(())
+  }
+
+  pub fn print_resolved_depths_file(&self, path: &str) -> Result<(), LoxError> {
+    let bytes = read_to_string(path).map_err(|_| LoxError::system_error("ERROR OPENING FILE."))?;
+
+    self.print_resolved_depths(&bytes)
+  }
+
+  // resolves like `check`, then lists the scope depth found for each local
+  pub fn print_resolved_depths(&self, source: &str) -> Result<(), LoxError> {
+    let mut scanner = Scanner::new(source);
+    let statements = self
+      .parser(scanner.scan_tokens()?)
+      .parse()?
+      .into_iter()
+      .map(Rc::new)
+      .collect::<Vec<Rc<Stmt>>>();
 
-    if source.trim().eq("@") {
-      self.interpreter.print_environment();
-      return Ok(());
+    let locals =
+      Resolver::describe_locals(&Resolver::resolve_to_map(&statements.as_slice().into())?);
+    for line in locals {
+      println!("{line}");
     }
 
+    Ok(())
+  }
+
+  pub fn bench_file(&self, path: &str, runs: NonZeroUsize) -> Result<BenchSummary, LoxError> {
+    let bytes = read_to_string(path).map_err(|_| LoxError::system_error("ERROR OPENING FILE."))?;
+
+    self.bench(&bytes, runs)
+  }
+
+  // scans and parses once, then resolves and runs `runs` times, each with a
+  // fresh interpreter so no run sees another's globals; output is discarded
+  pub fn bench(&self, source: &str, runs: NonZeroUsize) -> Result<BenchSummary, LoxError> {
     let mut scanner = Scanner::new(source);
-    let tokens = scanner.scan_tokens()?;
-    
-    let mut parser = Parser::new(tokens);
-    let statements = parser
+    let statements = self
+      .parser(scanner.scan_tokens()?)
       .parse()?
       .into_iter()
       .map(Rc::new)
       .collect::<Vec<Rc<Stmt>>>();
 
+    let mut durations = Vec::new();
+    for _ in 0..runs.get() {
+      let interpreter = Interpreter::new()
+        .with_output(sink())
+        .with_max_depth(self.max_call_depth);
+      interpreter.set_source(source);
+
+      let start = Instant::now();
+      interpreter.resolve_and_interpret(&statements.as_slice().into())?;
+      durations.push(start.elapsed());
+    }
+
+    durations.sort();
+    Ok(BenchSummary {
+      runs: durations.len(),
+      min: durations[0],
+      median: durations[durations.len() / 2],
+      mean: durations.iter().sum::<Duration>() / durations.len() as u32,
+    })
+  }
+
+  // a line typed at the prompt runs a statement at a time, so those before
+  // an error still take effect; functions are not hoisted across statements
+  pub fn run_line(&self, source: &str) -> Result<(), LoxError> {
+    let mut scanner = Scanner::new(source);
+    let tokens = self.timed("scan", || scanner.scan_tokens())?;
+
+    let mut parser = self.parser(tokens);
+    self.interpreter.set_source(source);
+    while let Some(statement) = self.timed("parse", || parser.next_repl_statement()) {
+      let statements = [Rc::new(statement?)];
+
+      let resolver = Resolver::new(&self.interpreter);
+      self.timed("resolve", || {
+        resolver.resolve(&statements.as_slice().into())
+      })?;
+
+      self.timed("interpret", || {
+        self.interpreter.interpret(&statements.as_slice().into())
+      })?;
+    }
+
+    Ok(())
+  }
+
+  // code nested deeper than the interpreter could run is a parse error
+  fn parser<'a>(&self, tokens: &'a [Token]) -> Parser<'a> {
+    Parser::new(tokens).with_max_depth(self.interpreter.max_evaluation_depth())
+  }
+
+  pub fn run(&self, source: &str) -> Result<(), LoxError> {
+    let mut scanner = Scanner::new(source);
+    let tokens = self.timed("scan", || scanner.scan_tokens())?;
+
+    let mut parser = self.parser(tokens);
+    let statements = self
+      .timed("parse", || parser.parse())?
+      .into_iter()
+      .map(Rc::new)
+      .collect::<Vec<Rc<Stmt>>>();
+
     let resolver = Resolver::new(&self.interpreter);
-    resolver.resolve(&statements.as_slice().into())?;
+    self.timed("resolve", || {
+      resolver.resolve(&statements.as_slice().into())
+    })?;
 
-    self.interpreter.interpret(&statements.as_slice().into())?;
+    self.interpreter.set_source(source);
+    self.timed("interpret", || {
+      self.interpreter.interpret(&statements.as_slice().into())
+    })?;
 
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  use crate::interpreter::test::Capture;
+
+  #[test]
+  fn test_run_file_missing() {
+    let lox = Lox::new();
+    assert!(lox
+      .run_file("this/file/does/not/exist.lox")
+      .is_err_and(|e| e.is_system_error()));
+  }
+
+  #[test]
+  fn test_timings() {
+    let lox = Lox::new().with_timings();
+    assert!(lox.run("var a = 1;").is_ok());
+    assert!(lox.run("print ;").is_err());
+
+    let phases = lox
+      .timings
+      .as_ref()
+      .map(|t| t.borrow().iter().map(|(p, _)| *p).collect::<Vec<&str>>());
+    assert_eq!(
+      phases,
+      Some(vec![
+        "scan",
+        "parse",
+        "resolve",
+        "interpret",
+        "scan",
+        "parse"
+      ])
+    );
+  }
+
+  #[test]
+  fn test_history() {
+    let path = std::env::temp_dir().join(format!("jlox_history_test_{}", std::process::id()));
+    std::fs::remove_file(&path).ok();
+
+    // each session appends to what the ones before it left behind
+    for input in ["var a = 1;\n\n@\n", "print a;\n!\n"] {
+      let lox = Lox::new()
+        .with_history(&path)
+        .with_output(Capture::default());
+      lox.run_prompt_with(&mut input.as_bytes(), &mut Vec::new());
+    }
+
+    assert_eq!(
+      read_to_string(&path).ok().as_deref(),
+      Some("var a = 1;\n@\nprint a;\n!\n")
+    );
+    std::fs::remove_file(&path).ok();
+
+    let lox = Lox::new().with_history(Path::new("this/directory/does/not/exist"));
+    assert!(lox.open_history().is_none());
+  }
+
+  #[test]
+  fn test_check() {
+    let lox = Lox::new();
+    assert!(lox.check("var a = 1;\nprint a;").is_ok());
+    // checking never runs the program, so runtime errors go unnoticed
+    assert!(lox.check("print undefined;\nprint -\"a\";").is_ok());
+
+    assert!(lox.check("print ;").is_err());
+    assert!(lox.check("var a = @;").is_err());
+    assert!(lox.check("return 1;").is_err());
+    assert!(lox.check("{ var a = a; }").is_err());
+  }
+
+  #[test]
+  fn test_run_line() {
+    let capture = Capture::default();
+    let lox = Lox::new().with_output(capture.clone());
+
+    // the statement after the error is not run either
+    assert!(lox.run_line("print 1; print ; print 3;").is_err());
+    assert_eq!(capture.contents(), "1\n");
+
+    assert!(lox.run_line("var a = 2; print a; a").is_ok());
+    assert_eq!(capture.contents(), "1\n2\n2\n");
+
+    // a bare expression at the prompt shows its value
+    assert!(lox.run_line("1 + 2").is_ok());
+    assert!(lox.run_line("a = 5;").is_ok());
+    assert_eq!(capture.contents(), "1\n2\n2\n3\n");
+  }
+
+  #[test]
+  fn test_multiline_input() {
+    let capture = Capture::default();
+    let lox = Lox::new().with_output(capture.clone());
+
+    let mut input = "fun add(a,\n\
+                     b) {\n\
+                     return a + b;\n\
+                     }\n\
+                     print add(1, \\\n\
+                     2);\n\
+                     print [1,\n\
+                     2];\n\
+                     print add(1, 1);\n"
+      .as_bytes();
+    let mut prompts = Vec::new();
+    lox.run_prompt_with(&mut input, &mut prompts);
+
+    assert_eq!(capture.contents(), "3\n[1, 2]\n2\n");
+    assert_eq!(
+      String::from_utf8_lossy(&prompts),
+      "> ... ... ... > ... > ... > > "
+    );
+  }
+
+  #[test]
+  fn test_bench() -> Result<(), LoxError> {
+    let lox = Lox::new();
+    let runs = NonZeroUsize::new(3).unwrap();
+    let summary = lox.bench("var a = 0;\nwhile (a < 10) a = a + 1;\nprint a;", runs)?;
+    assert_eq!(summary.runs, 3);
+    assert!(summary.min <= summary.median && summary.min <= summary.mean);
+    assert!(summary
+      .to_string()
+      .starts_with("runs                   3\n"));
+
+    assert!(lox
+      .bench("print -nil;", runs)
+      .is_err_and(|e| e.is_runtime_error()));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_multiline_string_input() {
+    let capture = Capture::default();
+    let lox = Lox::new().with_output(capture.clone());
+
+    let mut input = "fun f() {\n\
+                     var s = \"a\n\
+                     \";\n\
+                     return s;\n\
+                     }\n\
+                     print f();\n"
+      .as_bytes();
+    lox.run_prompt_with(&mut input, &mut Vec::new());
+
+    assert_eq!(capture.contents(), "a\n\n");
+    assert!(Lox::is_incomplete("fun f() {\nvar s = \"a\n"));
+    assert!(!Lox::is_incomplete("print @;"));
+  }
+
+  #[test]
+  fn test_custom_prompt() {
+    let lox = Lox::new();
+    assert_eq!(lox.prompt_for(""), "> ");
+    assert_eq!(lox.prompt_for("fun f() {\n"), "... ");
+
+    let lox = Lox::new()
+      .with_prompt("lox> ")
+      .with_continuation_prompt("   | ")
+      .with_output(Capture::default());
+    assert_eq!(lox.prompt_for(""), "lox> ");
+    assert_eq!(lox.prompt_for("fun f() {\n"), "   | ");
+
+    let mut input = "print (1 +\n2);\n".as_bytes();
+    let mut prompts = Vec::new();
+    lox.run_prompt_with(&mut input, &mut prompts);
+    assert_eq!(String::from_utf8_lossy(&prompts), "lox>    | lox> ");
+  }
+
+  #[test]
+  fn test_run_returns_errors() {
+    let lox = Lox::new();
+    assert!(lox
+      .run("print -\"a\";")
+      .is_err_and(|e| e.is_runtime_error()));
+    assert!(lox
+      .run("print ;")
+      .is_err_and(|e| !e.is_runtime_error() && !e.is_system_error()));
+  }
+}
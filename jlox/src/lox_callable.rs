@@ -2,13 +2,44 @@ use std::{fmt, rc::Rc};
 
 use crate::{error::*, interpreter::*, lox_class::*, token::*};
 
+// calls are capped at 254 arguments, so this many can never be too many
+pub const UNBOUNDED_ARITY: u8 = u8::MAX;
+
 #[derive(Clone)]
 pub struct Callable {
   pub fun: Rc<dyn LoxCallable>,
 }
 
+impl Callable {
+  pub fn arity(&self) -> u8 {
+    self.fun.arity()
+  }
+
+  pub fn name(&self) -> &str {
+    self.fun.name()
+  }
+
+  pub fn min_arity(&self) -> u8 {
+    self.fun.min_arity()
+  }
+
+  pub fn max_arity(&self) -> u8 {
+    self.fun.max_arity()
+  }
+}
+
 pub trait LoxCallable {
   fn arity(&self) -> u8;
+  fn min_arity(&self) -> u8 {
+    self.arity()
+  }
+  // anything past `min_arity` and up to `max_arity` is optional
+  fn max_arity(&self) -> u8 {
+    self.arity()
+  }
+  fn name(&self) -> &str {
+    "<anonymous>"
+  }
   fn call(
     &self,
     interpreter: &Interpreter,
@@ -6,7 +6,12 @@ use crate::{error::*, interpreter::*, lox_callable::*, lox_instance::*, token::*
 pub struct LoxClass {
   superclass: Option<Rc<LoxClass>>,
   name: String,
+  fields: Vec<(String, Literal)>,
   methods: HashMap<String, Literal>,
+  // every method an instance can call, inherited ones included, so lookups
+  // never walk the superclass chain
+  resolved_methods: HashMap<String, Literal>,
+  static_methods: HashMap<String, Literal>,
 }
 
 impl LoxClass {
@@ -15,24 +20,98 @@ impl LoxClass {
     superclass: &Option<Rc<LoxClass>>,
     methods: &HashMap<String, Literal>,
   ) -> LoxClass {
+    // declared methods go in last, so they shadow the inherited ones
+    let mut resolved_methods = superclass
+      .as_ref()
+      .map_or_else(HashMap::new, |s| s.resolved_methods.clone());
+    resolved_methods.extend(methods.iter().map(|(k, v)| (k.clone(), v.clone())));
+
     LoxClass {
       superclass: superclass.clone(),
       name: name.to_string(),
+      fields: Vec::new(),
       methods: methods.clone(),
+      resolved_methods,
+      static_methods: HashMap::new(),
+    }
+  }
+
+  pub fn with_static_methods(mut self, static_methods: &HashMap<String, Literal>) -> LoxClass {
+    self.static_methods = static_methods.clone();
+    self
+  }
+
+  // field initialisers, in declaration order
+  pub fn with_fields(mut self, fields: &[(String, Literal)]) -> LoxClass {
+    self.fields = fields.to_vec();
+    self
+  }
+
+  // inherited fields first, so a subclass's initialisers can see them
+  fn initialise_fields(
+    &self,
+    interpreter: &Interpreter,
+    instance: &Literal,
+  ) -> Result<(), LoxError> {
+    if let Some(s) = &self.superclass {
+      s.initialise_fields(interpreter, instance)?;
     }
+
+    let Literal::Instance(i) = instance else {
+      return Ok(());
+    };
+
+    for (name, initialiser) in self.fields.iter() {
+      if let Literal::Function(f) = initialiser {
+        if let Literal::Function(m) = f.bind(instance) {
+          i.set_field(name, &m.call(interpreter, &[], None)?);
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  pub fn get_superclass(&self) -> Option<&Rc<LoxClass>> {
+    self.superclass.as_ref()
+  }
+
+  // only methods declared on this class, sorted so the result does not
+  // depend on hash order
+  pub fn get_method_names(&self) -> Vec<String> {
+    let mut names = self.methods.keys().cloned().collect::<Vec<String>>();
+    names.sort();
+    names
   }
 
   pub fn find_method(&self, name: &str) -> Option<Literal> {
-    if let Some(m) = self.methods.get(name) {
+    self.resolved_methods.get(name).cloned()
+  }
+
+  pub fn find_static_method(&self, name: &str) -> Option<Literal> {
+    if let Some(m) = self.static_methods.get(name) {
       return Some(m.clone());
     }
 
     if let Some(s) = &self.superclass {
-      return s.find_method(name);
+      return s.find_static_method(name);
     }
 
     None
   }
+
+  // by identity, as two declarations of the same shape are still different classes
+  pub fn is_subclass_of(&self, class: &LoxClass) -> bool {
+    if std::ptr::eq(self, class) {
+      return true;
+    }
+
+    if let Some(s) = &self.superclass {
+      return s.is_subclass_of(class);
+    }
+
+    false
+  }
 }
 
 impl LoxCallable for LoxClass {
@@ -44,13 +123,37 @@ impl LoxCallable for LoxClass {
     0
   }
 
+  fn min_arity(&self) -> u8 {
+    if let Some(Literal::Function(initialiser)) = self.find_method("init") {
+      return initialiser.min_arity();
+    }
+
+    0
+  }
+
+  fn max_arity(&self) -> u8 {
+    if let Some(Literal::Function(initialiser)) = self.find_method("init") {
+      return initialiser.max_arity();
+    }
+
+    0
+  }
+
+  fn name(&self) -> &str {
+    &self.name
+  }
+
   fn call(
     &self,
     interpreter: &Interpreter,
     arguments: &[Literal],
     class: Option<Rc<LoxClass>>,
   ) -> Result<Literal, LoxError> {
-    let instance = Literal::Instance(LoxInstance::new(&self.clone().into()).into());
+    // `class` is this class as it was called, which instances must share so
+    // that `instanceof` can tell classes apart by identity
+    let instance_class = class.clone().unwrap_or_else(|| self.clone().into());
+    let instance = Literal::Instance(LoxInstance::new(&instance_class).into());
+    self.initialise_fields(interpreter, &instance)?;
 
     if let Some(Literal::Function(initialiser)) = self.find_method("init") {
       if let Literal::Function(m) = initialiser.bind(&instance) {
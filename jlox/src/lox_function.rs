@@ -9,6 +9,7 @@ pub struct LoxFunction {
   closure: Rc<RefCell<Environment>>,
   name: Token,
   params: Rc<Vec<Token>>,
+  is_variadic: bool,
   body: Rc<Vec<Rc<Stmt>>>,
   is_initialiser: bool,
 }
@@ -23,11 +24,33 @@ impl LoxFunction {
       closure: closure.clone(),
       name: declaration.name.clone(),
       params: declaration.params.clone().into(),
+      is_variadic: declaration.is_variadic,
       body: declaration.body.clone(),
       is_initialiser,
     }
   }
 
+  // a field initialiser becomes a method returning the field's initial value,
+  // so `this` is bound for it just as for any other method
+  pub fn new_field_initialiser(closure: &Rc<RefCell<Environment>>, field: &VarStmt) -> LoxFunction {
+    let body = Stmt::Return(
+      ReturnStmt {
+        keyword: field.name.clone(),
+        value: field.initialiser.clone(),
+      }
+      .into(),
+    );
+
+    LoxFunction {
+      closure: closure.clone(),
+      name: field.name.clone(),
+      params: Vec::new().into(),
+      is_variadic: false,
+      body: vec![body.into()].into(),
+      is_initialiser: false,
+    }
+  }
+
   pub fn bind(&self, instance: &Literal) -> Literal {
     let environment = RefCell::new(Environment::new_with_enclosing(&self.closure));
     environment.borrow_mut().define("this", instance.clone());
@@ -36,36 +59,42 @@ impl LoxFunction {
         closure: environment.into(),
         name: self.name.clone(),
         params: self.params.clone(),
+        is_variadic: self.is_variadic,
         body: self.body.clone(),
         is_initialiser: self.is_initialiser,
       }
       .into(),
     )
   }
-}
 
-impl LoxCallable for LoxFunction {
-  fn arity(&self) -> u8 {
-    self.params.len() as u8
-  }
-
-  fn call(
-    &self,
-    interpreter: &Interpreter,
-    arguments: &[Literal],
-    _class: Option<Rc<LoxClass>>,
-  ) -> Result<Literal, LoxError> {
+  fn execute(&self, interpreter: &Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
     let mut environment = Environment::new_with_enclosing(&self.closure.clone());
 
     for (param, arg) in self.params.iter().zip(arguments.iter()) {
       environment.define(param.get_lexeme(), arg.clone());
     }
 
+    // the rest parameter collects whatever the fixed parameters did not
+    if let (true, Some(rest)) = (self.is_variadic, self.params.last()) {
+      let overflow = arguments.get(self.params.len() - 1..).unwrap_or_default();
+      environment.define(
+        rest.get_lexeme(),
+        Literal::Array(RefCell::new(overflow.to_vec()).into()),
+      );
+    }
+
+    let res = interpreter.execute_block(&self.body.as_slice().into(), environment);
+
+    // an initialiser hands back the instance however it finishes, even on `return;`
     if self.is_initialiser {
-      return self.closure.borrow().get_at(0, "this");
+      if let Err(e) = res {
+        e.get_return_value()?;
+      }
+
+      return Environment::get_at(&self.closure, 0, "this");
     }
 
-    if let Err(v) = interpreter.execute_block(&self.body.as_slice().into(), environment) {
+    if let Err(v) = res {
       return v.get_return_value();
     }
 
@@ -73,6 +102,44 @@ impl LoxCallable for LoxFunction {
   }
 }
 
+impl LoxCallable for LoxFunction {
+  fn arity(&self) -> u8 {
+    if self.is_variadic {
+      return (self.params.len() - 1) as u8;
+    }
+
+    self.params.len() as u8
+  }
+
+  fn min_arity(&self) -> u8 {
+    self.arity()
+  }
+
+  fn max_arity(&self) -> u8 {
+    if self.is_variadic {
+      return UNBOUNDED_ARITY;
+    }
+
+    self.arity()
+  }
+
+  fn name(&self) -> &str {
+    self.name.get_lexeme()
+  }
+
+  fn call(
+    &self,
+    interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    interpreter.enter_call()?;
+    let res = self.execute(interpreter, arguments);
+    interpreter.exit_call();
+    res
+  }
+}
+
 impl fmt::Display for LoxFunction {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     write!(f, "<fn {}>", self.name.get_lexeme())
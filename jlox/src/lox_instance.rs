@@ -31,11 +31,55 @@ impl LoxInstance {
     ))
   }
 
+  // sorted so the result does not depend on hash order
+  pub fn get_field_names(&self) -> Vec<String> {
+    let mut names = self
+      .fields
+      .borrow()
+      .keys()
+      .cloned()
+      .collect::<Vec<String>>();
+    names.sort();
+    names
+  }
+
+  // sorted by name, like `get_field_names`
+  pub fn get_fields(&self) -> Vec<(String, Literal)> {
+    let mut fields = self
+      .fields
+      .borrow()
+      .iter()
+      .map(|(k, v)| (k.clone(), v.clone()))
+      .collect::<Vec<(String, Literal)>>();
+    fields.sort_by(|(l, _), (r, _)| l.cmp(r));
+    fields
+  }
+
+  // the field map is new, but nested instances are still shared
+  pub fn copy(&self) -> LoxInstance {
+    LoxInstance {
+      class: self.class.clone(),
+      fields: RefCell::new(self.fields.borrow().clone()),
+    }
+  }
+
+  pub fn get_class(&self) -> &Rc<LoxClass> {
+    &self.class
+  }
+
+  pub fn is_instance_of(&self, class: &LoxClass) -> bool {
+    self.class.is_subclass_of(class)
+  }
+
   pub fn set(&self, name: &Token, value: &Literal) {
+    self.set_field(name.get_lexeme(), value);
+  }
+
+  pub fn set_field(&self, name: &str, value: &Literal) {
     self
       .fields
       .borrow_mut()
-      .insert(name.get_lexeme().to_string(), value.clone());
+      .insert(name.to_string(), value.clone());
   }
 }
 
@@ -1,12 +1,38 @@
-use std::{fmt, rc::Rc, time::SystemTime};
+use std::{
+  cell::RefCell,
+  collections::HashMap,
+  fmt,
+  fs::{read_to_string, write},
+  rc::Rc,
+  thread,
+  time::{Duration, SystemTime},
+};
 
-use crate::{error::*, interpreter::*, lox_callable::*, lox_class::*, token::*};
+use crate::{error::*, interpreter::*, json::*, lox_callable::*, lox_class::*, token::*};
 
 #[derive(Clone)]
 pub struct LoxNativeFunction {
   pub fun: Rc<dyn LoxCallable>,
 }
 
+impl LoxNativeFunction {
+  pub fn arity(&self) -> u8 {
+    self.fun.arity()
+  }
+
+  pub fn name(&self) -> &str {
+    self.fun.name()
+  }
+
+  pub fn min_arity(&self) -> u8 {
+    self.fun.min_arity()
+  }
+
+  pub fn max_arity(&self) -> u8 {
+    self.fun.max_arity()
+  }
+}
+
 impl fmt::Debug for LoxNativeFunction {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     write!(f, "<DEBUG NATIVE FN>")
@@ -32,6 +58,18 @@ impl LoxCallable for Clock {
     0
   }
 
+  fn min_arity(&self) -> u8 {
+    0
+  }
+
+  fn max_arity(&self) -> u8 {
+    0
+  }
+
+  fn name(&self) -> &str {
+    "clock"
+  }
+
   fn call(
     &self,
     _interpreter: &Interpreter,
@@ -45,3 +83,1033 @@ impl LoxCallable for Clock {
     Err(LoxError::system_error("SYSTEM CLOCK ERROR."))
   }
 }
+
+pub struct ClockMillis;
+
+impl LoxCallable for ClockMillis {
+  fn arity(&self) -> u8 {
+    0
+  }
+
+  fn name(&self) -> &str {
+    "clock_millis"
+  }
+
+  fn call(
+    &self,
+    interpreter: &Interpreter,
+    _arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    Ok(Literal::Number(
+      interpreter.elapsed().as_secs_f64() * 1000.0,
+    ))
+  }
+}
+
+pub struct Sleep;
+
+impl LoxCallable for Sleep {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    "sleep"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let Some(Literal::Number(seconds)) = arguments.first() else {
+      return Err(LoxError::new_native_error("Argument must be a number."));
+    };
+
+    let duration = Duration::try_from_secs_f64(*seconds).map_err(|_| {
+      LoxError::new_native_error("Argument must be a non-negative number of seconds.")
+    })?;
+    thread::sleep(duration);
+    Ok(Literal::Nil)
+  }
+}
+
+pub struct Memoize;
+
+impl LoxCallable for Memoize {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    "memoize"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let (function, class): (Rc<dyn LoxCallable>, Option<Rc<LoxClass>>) = match arguments.first() {
+      Some(Literal::Function(f)) => (f.clone(), None),
+      Some(Literal::NativeFunction(f)) => (f.fun.clone(), None),
+      Some(Literal::Class(c)) => (c.clone(), Some(c.clone())),
+      _ => {
+        return Err(LoxError::new_native_error(
+          "Can only memoize functions and classes.",
+        ))
+      }
+    };
+
+    Ok(Literal::NativeFunction(
+      LoxNativeFunction {
+        fun: Rc::new(Memoized {
+          function,
+          class,
+          cache: RefCell::new(HashMap::new()),
+        }),
+      }
+      .into(),
+    ))
+  }
+}
+
+struct Memoized {
+  function: Rc<dyn LoxCallable>,
+  class: Option<Rc<LoxClass>>,
+  cache: RefCell<HashMap<Vec<MapKey>, Literal>>,
+}
+
+impl LoxCallable for Memoized {
+  fn arity(&self) -> u8 {
+    self.function.arity()
+  }
+
+  fn name(&self) -> &str {
+    self.function.name()
+  }
+
+  fn min_arity(&self) -> u8 {
+    self.function.min_arity()
+  }
+
+  fn max_arity(&self) -> u8 {
+    self.function.max_arity()
+  }
+
+  fn call(
+    &self,
+    interpreter: &Interpreter,
+    arguments: &[Literal],
+    class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    // arguments that cannot be hashed bypass the cache entirely
+    let key = arguments
+      .iter()
+      .map(MapKey::from_literal)
+      .collect::<Option<Vec<MapKey>>>();
+
+    if let Some(v) = key
+      .as_ref()
+      .and_then(|k| self.cache.borrow().get(k).cloned())
+    {
+      return Ok(v);
+    }
+
+    let value = self
+      .function
+      .call(interpreter, arguments, class.or_else(|| self.class.clone()))?;
+    if let Some(k) = key {
+      self.cache.borrow_mut().insert(k, value.clone());
+    }
+
+    Ok(value)
+  }
+}
+
+pub struct SetLineBuffered;
+
+impl LoxCallable for SetLineBuffered {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    "setLineBuffered"
+  }
+
+  fn call(
+    &self,
+    interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    if let Some(&Literal::Boolean(b)) = arguments.first() {
+      interpreter.set_line_buffered(b)?;
+      return Ok(Literal::Nil);
+    }
+
+    Err(LoxError::new_native_error("Argument must be a boolean."))
+  }
+}
+
+pub struct Random;
+
+impl LoxCallable for Random {
+  fn arity(&self) -> u8 {
+    0
+  }
+
+  fn name(&self) -> &str {
+    "random"
+  }
+
+  fn call(
+    &self,
+    interpreter: &Interpreter,
+    _arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    Ok(Literal::Number(interpreter.next_random()))
+  }
+}
+
+pub struct Gensym;
+
+impl LoxCallable for Gensym {
+  fn arity(&self) -> u8 {
+    0
+  }
+
+  fn name(&self) -> &str {
+    "gensym"
+  }
+
+  fn call(
+    &self,
+    interpreter: &Interpreter,
+    _arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    Ok(Literal::String(interpreter.next_symbol()))
+  }
+}
+
+pub struct RandomSeed;
+
+impl LoxCallable for RandomSeed {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    "random_seed"
+  }
+
+  fn call(
+    &self,
+    interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let Some(Literal::Number(n)) = arguments.first() else {
+      return Err(LoxError::new_native_error("Seed must be a whole number."));
+    };
+
+    if !n.is_finite() || n.fract() != 0.0 {
+      return Err(LoxError::new_native_error("Seed must be a whole number."));
+    }
+
+    // negative seeds wrap around rather than being refused
+    interpreter.seed_random(*n as i64 as u64);
+    Ok(Literal::Nil)
+  }
+}
+
+pub struct Approx;
+
+const DEFAULT_EPSILON: f64 = 1e-9;
+
+impl LoxCallable for Approx {
+  fn arity(&self) -> u8 {
+    2
+  }
+
+  fn min_arity(&self) -> u8 {
+    2
+  }
+
+  fn max_arity(&self) -> u8 {
+    3
+  }
+
+  fn name(&self) -> &str {
+    "approx"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let (a, b, epsilon) = match arguments {
+      [Literal::Number(a), Literal::Number(b)] => (a, b, &DEFAULT_EPSILON),
+      [Literal::Number(a), Literal::Number(b), Literal::Number(e)] => (a, b, e),
+      _ => return Err(LoxError::new_native_error("Arguments must be numbers.")),
+    };
+
+    Ok(Literal::Boolean((a - b).abs().le(epsilon)))
+  }
+}
+
+// unlike `f64::min` and `f64::max`, any NaN makes the result NaN, as in
+// JavaScript, so a bad value is not silently dropped
+fn fold_numbers(arguments: &[Literal], f: fn(f64, f64) -> f64) -> Result<Literal, LoxError> {
+  let numbers = arguments
+    .iter()
+    .map(|a| match a {
+      Literal::Number(n) => Ok(*n),
+      _ => Err(LoxError::new_native_error("Arguments must be numbers.")),
+    })
+    .collect::<Result<Vec<f64>, LoxError>>()?;
+
+  let res = numbers.into_iter().reduce(|acc, n| {
+    if acc.is_nan() || n.is_nan() {
+      f64::NAN
+    } else {
+      f(acc, n)
+    }
+  });
+  Ok(Literal::Number(res.unwrap_or(f64::NAN)))
+}
+
+pub struct Min;
+
+impl LoxCallable for Min {
+  fn arity(&self) -> u8 {
+    2
+  }
+
+  fn min_arity(&self) -> u8 {
+    2
+  }
+
+  fn max_arity(&self) -> u8 {
+    UNBOUNDED_ARITY
+  }
+
+  fn name(&self) -> &str {
+    "min"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    fold_numbers(arguments, f64::min)
+  }
+}
+
+pub struct Max;
+
+impl LoxCallable for Max {
+  fn arity(&self) -> u8 {
+    2
+  }
+
+  fn min_arity(&self) -> u8 {
+    2
+  }
+
+  fn max_arity(&self) -> u8 {
+    UNBOUNDED_ARITY
+  }
+
+  fn name(&self) -> &str {
+    "max"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    fold_numbers(arguments, f64::max)
+  }
+}
+
+pub struct FmtNumber;
+
+// anything longer is almost certainly a mistake, and would allocate a huge string
+const MAX_PLACES: f64 = 100.0;
+
+impl LoxCallable for FmtNumber {
+  fn arity(&self) -> u8 {
+    2
+  }
+
+  fn name(&self) -> &str {
+    "fmt_number"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let [Literal::Number(value), Literal::Number(places)] = arguments else {
+      return Err(LoxError::new_native_error("Arguments must be numbers."));
+    };
+
+    if places.fract().ne(&0.0) || places.lt(&0.0) {
+      return Err(LoxError::new_native_error(
+        "Places must be a non-negative whole number.",
+      ));
+    }
+
+    if places.gt(&MAX_PLACES) {
+      return Err(LoxError::new_native_error(&format!(
+        "Places must be at most {MAX_PLACES}."
+      )));
+    }
+
+    Ok(Literal::String(format!("{:.*}", *places as usize, value)))
+  }
+}
+
+pub struct Chr;
+
+impl LoxCallable for Chr {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    "chr"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let Some(&Literal::Number(n)) = arguments.first() else {
+      return Err(LoxError::new_native_error("Argument must be a number."));
+    };
+
+    if n.fract().ne(&0.0) || n.lt(&0.0) || n.gt(&(u32::MAX as f64)) {
+      return Err(LoxError::new_native_error(
+        "Argument must be a valid code point.",
+      ));
+    }
+
+    char::from_u32(n as u32)
+      .map(|c| Literal::String(c.to_string()))
+      .ok_or_else(|| LoxError::new_native_error("Argument must be a valid code point."))
+  }
+}
+
+pub struct Ordinal;
+
+impl LoxCallable for Ordinal {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    "ord"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let Some(Literal::String(s)) = arguments.first() else {
+      return Err(LoxError::new_native_error("Argument must be a string."));
+    };
+
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+      (Some(c), None) => Ok(Literal::Number(c as u32 as f64)),
+      _ => Err(LoxError::new_native_error(
+        "Argument must be a single character.",
+      )),
+    }
+  }
+}
+
+pub struct PrintErr;
+
+impl LoxCallable for PrintErr {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    "print_err"
+  }
+
+  fn call(
+    &self,
+    interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    if let Some(value) = arguments.first() {
+      interpreter.write_error(value)?;
+    }
+
+    Ok(Literal::Nil)
+  }
+}
+
+pub struct ReadFile;
+
+impl LoxCallable for ReadFile {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    "read_file"
+  }
+
+  fn call(
+    &self,
+    interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    if !interpreter.is_io_enabled() {
+      return Err(LoxError::new_native_error("File I/O is disabled."));
+    }
+
+    let Some(Literal::String(path)) = arguments.first() else {
+      return Err(LoxError::new_native_error("Argument must be a string."));
+    };
+
+    Ok(read_to_string(path).map_or(Literal::Nil, Literal::String))
+  }
+}
+
+pub struct WriteFile;
+
+impl LoxCallable for WriteFile {
+  fn arity(&self) -> u8 {
+    2
+  }
+
+  fn name(&self) -> &str {
+    "write_file"
+  }
+
+  fn call(
+    &self,
+    interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    if !interpreter.is_io_enabled() {
+      return Err(LoxError::new_native_error("File I/O is disabled."));
+    }
+
+    let [Literal::String(path), Literal::String(contents)] = arguments else {
+      return Err(LoxError::new_native_error("Arguments must be strings."));
+    };
+
+    Ok(Literal::Boolean(write(path, contents).is_ok()))
+  }
+}
+
+pub struct Assert;
+
+impl LoxCallable for Assert {
+  fn arity(&self) -> u8 {
+    2
+  }
+
+  fn name(&self) -> &str {
+    "assert"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    if let [condition, message] = arguments {
+      if condition.is_truthy() {
+        return Ok(Literal::Nil);
+      }
+
+      return Err(LoxError::new_native_error(&message.to_string()));
+    }
+
+    Err(LoxError::new_native_error(
+      "NATIVE FUNCTION INTERNAL ERROR.",
+    ))
+  }
+}
+
+pub struct Raise;
+
+impl LoxCallable for Raise {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    "error"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let Some(Literal::String(message)) = arguments.first() else {
+      return Err(LoxError::new_native_error("Argument must be a string."));
+    };
+
+    Err(LoxError::new_native_error(message))
+  }
+}
+
+pub struct CopyInstance;
+
+impl LoxCallable for CopyInstance {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    "copy"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let Some(Literal::Instance(i)) = arguments.first() else {
+      return Err(LoxError::new_native_error("Can only copy instances."));
+    };
+
+    Ok(Literal::Instance(i.copy().into()))
+  }
+}
+
+pub struct Describe;
+
+impl LoxCallable for Describe {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    "describe"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let Some(Literal::Class(c)) = arguments.first() else {
+      return Err(LoxError::new_native_error("Can only describe classes."));
+    };
+
+    let mut description = format!("class {c}");
+    if let Some(s) = c.get_superclass() {
+      description = format!("{description} < {s}");
+    }
+
+    let methods = c.get_method_names();
+    if methods.is_empty() {
+      description.push_str(" {}");
+    } else {
+      description = format!("{description} {{ {} }}", methods.join(", "));
+    }
+
+    Ok(Literal::String(description))
+  }
+}
+
+pub struct Trim;
+
+impl LoxCallable for Trim {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    "trim"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let Some(Literal::String(s)) = arguments.first() else {
+      return Err(LoxError::new_native_error("Argument must be a string."));
+    };
+
+    Ok(Literal::String(s.trim().to_string()))
+  }
+}
+
+pub struct Upper;
+
+impl LoxCallable for Upper {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    "upper"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let Some(Literal::String(s)) = arguments.first() else {
+      return Err(LoxError::new_native_error("Argument must be a string."));
+    };
+
+    Ok(Literal::String(s.to_uppercase()))
+  }
+}
+
+pub struct Lower;
+
+impl LoxCallable for Lower {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    "lower"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let Some(Literal::String(s)) = arguments.first() else {
+      return Err(LoxError::new_native_error("Argument must be a string."));
+    };
+
+    Ok(Literal::String(s.to_lowercase()))
+  }
+}
+
+pub struct Split;
+
+impl LoxCallable for Split {
+  fn arity(&self) -> u8 {
+    2
+  }
+
+  fn name(&self) -> &str {
+    "split"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let [Literal::String(s), Literal::String(separator)] = arguments else {
+      return Err(LoxError::new_native_error("Arguments must be strings."));
+    };
+
+    // an empty separator splits into characters rather than padding with empty strings
+    let parts = if separator.is_empty() {
+      s.chars().map(|c| Literal::String(c.to_string())).collect()
+    } else {
+      s.split(separator.as_str())
+        .map(|p| Literal::String(p.to_string()))
+        .collect::<Vec<Literal>>()
+    };
+
+    Ok(Literal::Array(RefCell::new(parts).into()))
+  }
+}
+
+pub struct Contains;
+
+impl LoxCallable for Contains {
+  fn arity(&self) -> u8 {
+    2
+  }
+
+  fn name(&self) -> &str {
+    "contains"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let [Literal::String(haystack), Literal::String(needle)] = arguments else {
+      return Err(LoxError::new_native_error("Arguments must be strings."));
+    };
+
+    Ok(Literal::Boolean(haystack.contains(needle.as_str())))
+  }
+}
+
+pub struct IndexOf;
+
+impl LoxCallable for IndexOf {
+  fn arity(&self) -> u8 {
+    2
+  }
+
+  fn name(&self) -> &str {
+    "index_of"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let [Literal::String(haystack), Literal::String(needle)] = arguments else {
+      return Err(LoxError::new_native_error("Arguments must be strings."));
+    };
+
+    // counted in characters, like string indexing, rather than bytes
+    let index = haystack
+      .find(needle.as_str())
+      .map_or(-1.0, |i| haystack[..i].chars().count() as f64);
+    Ok(Literal::Number(index))
+  }
+}
+
+pub struct ArrayPush;
+
+impl LoxCallable for ArrayPush {
+  fn arity(&self) -> u8 {
+    2
+  }
+
+  fn name(&self) -> &str {
+    "array_push"
+  }
+
+  // returns the new length, as JavaScript's `push` does
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let [Literal::Array(a), value] = arguments else {
+      return Err(LoxError::new_native_error(
+        "First argument must be an array.",
+      ));
+    };
+
+    a.borrow_mut().push(value.clone());
+    Ok(Literal::Number(a.borrow().len() as f64))
+  }
+}
+
+pub struct ArrayPop;
+
+impl LoxCallable for ArrayPop {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    "array_pop"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let Some(Literal::Array(a)) = arguments.first() else {
+      return Err(LoxError::new_native_error("Argument must be an array."));
+    };
+
+    let popped = a.borrow_mut().pop();
+    Ok(popped.unwrap_or(Literal::Nil))
+  }
+}
+
+pub struct ArrayLen;
+
+impl LoxCallable for ArrayLen {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    "array_len"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let Some(Literal::Array(a)) = arguments.first() else {
+      return Err(LoxError::new_native_error("Argument must be an array."));
+    };
+
+    Ok(Literal::Number(a.borrow().len() as f64))
+  }
+}
+
+pub struct ToJson;
+
+impl LoxCallable for ToJson {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    "to_json"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let Some(value) = arguments.first() else {
+      return Err(LoxError::new_native_error(
+        "NATIVE FUNCTION INTERNAL ERROR.",
+      ));
+    };
+
+    to_json(value).map(Literal::String)
+  }
+}
+
+pub struct DebugFormat;
+
+impl LoxCallable for DebugFormat {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    "debug"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let Some(value) = arguments.first() else {
+      return Err(LoxError::new_native_error(
+        "NATIVE FUNCTION INTERNAL ERROR.",
+      ));
+    };
+
+    Ok(Literal::String(
+      value.formatted(&DebugFormatter).to_string(),
+    ))
+  }
+}
+
+pub type Accepts = fn(&Literal) -> bool;
+
+// one struct backs every `is_*` native, as they differ only in what they accept
+pub struct TypePredicate {
+  pub name: &'static str,
+  pub accepts: Accepts,
+}
+
+impl LoxCallable for TypePredicate {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    self.name
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let Some(value) = arguments.first() else {
+      return Err(LoxError::new_native_error(
+        "NATIVE FUNCTION INTERNAL ERROR.",
+      ));
+    };
+
+    Ok(Literal::Boolean((self.accepts)(value)))
+  }
+}
+
+pub struct Fields;
+
+impl LoxCallable for Fields {
+  fn arity(&self) -> u8 {
+    1
+  }
+
+  fn name(&self) -> &str {
+    "fields"
+  }
+
+  fn call(
+    &self,
+    _interpreter: &Interpreter,
+    arguments: &[Literal],
+    _class: Option<Rc<LoxClass>>,
+  ) -> Result<Literal, LoxError> {
+    let Some(Literal::Instance(i)) = arguments.first() else {
+      return Err(LoxError::new_native_error(
+        "Can only list the fields of instances.",
+      ));
+    };
+
+    let names = i
+      .get_field_names()
+      .into_iter()
+      .map(Literal::String)
+      .collect::<Vec<Literal>>();
+    Ok(Literal::Array(RefCell::new(names).into()))
+  }
+}
@@ -1,19 +1,184 @@
-use std::{env::args, process::exit};
+use std::{
+  env::{args, var_os},
+  num::NonZeroUsize,
+  path::PathBuf,
+  process::exit,
+  thread,
+};
 
-use jlox::lox::Lox;
+use jlox::{
+  error::LoxError,
+  interpreter::{STACK_SIZE, STACK_SIZE_MAX_CALL_DEPTH},
+  lox::Lox,
+};
+
+fn usage() -> ! {
+  eprintln!("Usage: jlox [--time] [--repl-history] [--enable-io] [--check] [--print-resolved-depths] [--bench [--runs=N]] [script]");
+  exit(64);
+}
+
+// the sysexits code for a script that failed to run
+fn exit_code(e: &LoxError) -> i32 {
+  if e.is_runtime_error() {
+    return 70;
+  }
+
+  if e.is_system_error() {
+    return 74;
+  }
+
+  65
+}
+
+// `main` runs everything on a thread of `STACK_SIZE`, with room for code far
+// deeper than the defaults allow
+fn new_lox() -> Lox {
+  Lox::new().with_max_depth(STACK_SIZE_MAX_CALL_DEPTH)
+}
+
+// only scans, parses and resolves, so nothing is defined or run
+fn check(paths: &[&String]) -> ! {
+  let [path] = paths else { usage() };
+
+  match new_lox().check_file(path) {
+    Ok(()) => exit(0),
+    Err(e) => exit(exit_code(&e)),
+  }
+}
+
+// resolves without running, then dumps where each local was found
+fn print_resolved_depths(paths: &[&String]) -> ! {
+  let [path] = paths else { usage() };
+
+  match new_lox().print_resolved_depths_file(path) {
+    Ok(()) => exit(0),
+    Err(e) => exit(exit_code(&e)),
+  }
+}
+
+const DEFAULT_BENCH_RUNS: NonZeroUsize = NonZeroUsize::new(10).unwrap();
+
+// runs the script several times and reports how long a run takes
+fn bench(flags: &[&String], paths: &[&String]) -> ! {
+  let [path] = paths else { usage() };
+
+  let runs = bench_runs(flags).unwrap_or_else(|| usage());
+
+  match new_lox().bench_file(path, runs) {
+    Ok(summary) => {
+      eprint!("{summary}");
+      exit(0)
+    }
+    Err(e) => exit(exit_code(&e)),
+  }
+}
+
+// `--bench` may only be combined with `--runs=N`, for at least one run
+fn bench_runs(flags: &[&String]) -> Option<NonZeroUsize> {
+  let mut runs = DEFAULT_BENCH_RUNS;
+  for flag in flags {
+    match flag.split_once('=') {
+      Some(("--runs", n)) => runs = n.parse().ok()?,
+      _ if flag.as_str().eq("--bench") => (),
+      _ => return None,
+    }
+  }
+
+  Some(runs)
+}
 
 fn main() {
-  let args = args().collect::<Vec<String>>();
-  let lox = Lox::new();
-
-  match args.len() {
-    1 => lox.run_prompt(),
-    2 => lox
-      .run_file(args.get(1).expect("ERROR READING INPUT."))
-      .expect("ERROR OPENING FILE."),
-    _ => {
-      eprintln!("Usage: jlox [script]");
-      exit(64);
+  // Lox recursion is native recursion, so it needs a much larger stack than
+  // the main thread has
+  let jlox = thread::Builder::new().stack_size(STACK_SIZE).spawn(run);
+  if !jlox.is_ok_and(|j| j.join().is_ok()) {
+    exit(70);
+  }
+}
+
+fn run() {
+  let args = args().skip(1).collect::<Vec<String>>();
+  let (flags, paths): (Vec<&String>, Vec<&String>) = args.iter().partition(|a| a.starts_with("--"));
+
+  if flags.iter().any(|f| f.as_str().eq("--check")) {
+    check(&paths);
+  }
+
+  if flags.iter().any(|f| f.as_str().eq("--bench")) {
+    bench(&flags, &paths);
+  }
+
+  if flags
+    .iter()
+    .any(|f| f.as_str().eq("--print-resolved-depths"))
+  {
+    print_resolved_depths(&paths);
+  }
+
+  let mut lox = new_lox();
+  for flag in flags {
+    match flag.as_str() {
+      "--time" => lox = lox.with_timings(),
+      "--enable-io" => lox = lox.enable_io(),
+      "--repl-history" => {
+        if let Some(home) = var_os("HOME") {
+          lox = lox.with_history(&PathBuf::from(home).join(".jlox_history"));
+        }
+      }
+      _ => usage(),
     }
   }
+
+  match paths.as_slice() {
+    [] => {
+      lox.run_prompt();
+      lox.report_timings();
+    }
+    [path] => {
+      let res = lox.run_file(path);
+      lox.report_timings();
+
+      if let Err(e) = res {
+        exit(exit_code(&e));
+      }
+    }
+    _ => usage(),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn flags(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+  }
+
+  #[test]
+  fn test_bench_runs() {
+    let parse = |args: &[&str]| bench_runs(&flags(args).iter().collect::<Vec<&String>>());
+
+    assert_eq!(parse(&["--bench"]), Some(DEFAULT_BENCH_RUNS));
+    assert_eq!(parse(&["--bench", "--runs=3"]), NonZeroUsize::new(3));
+    assert_eq!(parse(&["--runs=3", "--bench"]), NonZeroUsize::new(3));
+    assert_eq!(parse(&["--bench", "--runs=0"]), None);
+    assert_eq!(parse(&["--bench", "--runs=x"]), None);
+    assert_eq!(parse(&["--bench", "--runs=-1"]), None);
+    assert_eq!(parse(&["--bench", "--runs"]), None);
+    assert_eq!(parse(&["--bench", "--time"]), None);
+  }
+
+  #[test]
+  fn test_bench_exit_codes() {
+    let code = |res: Result<_, LoxError>| res.map_err(|e| exit_code(&e)).err();
+    let lox = Lox::new();
+
+    assert_eq!(code(lox.bench("print 1;", NonZeroUsize::MIN)), None);
+    assert_eq!(code(lox.bench("print -nil;", NonZeroUsize::MIN)), Some(70));
+    assert_eq!(code(lox.bench("print ;", NonZeroUsize::MIN)), Some(65));
+    assert_eq!(
+      code(lox.bench_file("no/such/script.lox", NonZeroUsize::MIN)),
+      Some(74)
+    );
+  }
 }
@@ -1,36 +1,62 @@
 use std::rc::Rc;
 
-use crate::{error::*, expr::*, stmt::*, token::*};
+use crate::{error::*, expr::*, interpreter::MAX_EVALUATION_DEPTH, stmt::*, token::*};
 
 pub struct Parser<'a> {
   tokens: &'a [Token],
   current: usize,
   loop_depth: usize,
+  depth: usize,
+  max_depth: usize,
   had_error: bool,
+  repl: bool,
+  auto_print: bool,
+  eof: Token,
 }
 
+// counted as the interpreter counts evaluations, so that the limits agree;
+// deeper code would overflow the stack of the resolver or interpreter
+const MAX_DEPTH: usize = MAX_EVALUATION_DEPTH;
+
+// an expression recurses through every precedence level of the parser, which
+// takes several times the stack of a single operator or statement
+const EXPRESSION_DEPTH: usize = 4;
+
 impl<'a> Parser<'a> {
-  pub fn new(tokens: &[Token]) -> Parser {
+  pub fn new(tokens: &[Token]) -> Parser<'_> {
     Parser {
       tokens,
       current: 0,
       loop_depth: 0,
+      depth: 0,
+      max_depth: MAX_DEPTH,
       had_error: false,
+      repl: false,
+      auto_print: false,
+      eof: Token::new_eof(tokens.last().map_or(1, |t| t.get_line())),
     }
   }
 
+  pub fn with_max_depth(mut self, max_depth: usize) -> Parser<'a> {
+    self.max_depth = max_depth;
+    self
+  }
+
+  // a calculator-like mode, where `2 + 2;` on its own prints 4; only
+  // top-level statements are affected, not those in blocks or functions
+  pub fn with_auto_print(mut self, auto_print: bool) -> Parser<'a> {
+    self.auto_print = auto_print;
+    self
+  }
+
   pub fn parse(&mut self) -> Result<Vec<Stmt>, LoxError> {
     let mut statements = Vec::new();
 
     while !self.is_at_end() {
-      // if self.declaration() returns error, mark self.had_error as true
-      // but do not immediately return
-      if let Ok(s) = self.declaration() {
+      // an error is only recorded here, so parsing carries on after it
+      if let Ok(s) = self.next_declaration() {
         statements.push(s);
-        continue;
       }
-
-      self.had_error = true;
     }
 
     if !self.had_error {
@@ -40,8 +66,69 @@ impl<'a> Parser<'a> {
     Err(LoxError::new_parse_failure())
   }
 
+  fn next_declaration(&mut self) -> Result<Stmt, LoxError> {
+    let start = self.current;
+    let res = self.declaration().map(|s| self.auto_print(s));
+    if res.is_err() {
+      self.had_error = true;
+    }
+
+    // every call must consume something, or malformed input could spin forever
+    if self.current.eq(&start) {
+      self.advance();
+    }
+
+    res
+  }
+
+  // like `parse`, but the last expression may leave out its semicolon, as
+  // typing `1 + 2` at the prompt should just work
+  pub fn parse_repl(&mut self) -> Result<Vec<Stmt>, LoxError> {
+    self.repl = true;
+    self.parse()
+  }
+
+  // one statement at a time, with the leniency of `parse_repl`, so that the
+  // statements before a bad one can run first; `None` once input runs out
+  pub fn next_repl_statement(&mut self) -> Option<Result<Stmt, LoxError>> {
+    self.repl = true;
+    if self.is_at_end() {
+      return None;
+    }
+
+    Some(self.next_declaration())
+  }
+
+  pub fn had_error(&self) -> bool {
+    self.had_error
+  }
+
+  fn auto_print(&self, statement: Stmt) -> Stmt {
+    if !self.auto_print {
+      return statement;
+    }
+
+    Parser::printed(statement)
+  }
+
+  // an expression statement turned into one that shows its value
+  fn printed(statement: Stmt) -> Stmt {
+    match statement {
+      Stmt::Expression(e) => Stmt::Print(
+        PrintStmt {
+          expression: e.expression.clone(),
+        }
+        .into(),
+      ),
+      _ => statement,
+    }
+  }
+
   fn expression(&mut self) -> Result<Expr, LoxError> {
-    self.assignment()
+    self.nest_by(EXPRESSION_DEPTH)?;
+    let expr = self.assignment();
+    self.depth -= EXPRESSION_DEPTH;
+    expr
   }
 
   fn declaration(&mut self) -> Result<Stmt, LoxError> {
@@ -56,6 +143,8 @@ impl<'a> Parser<'a> {
     };
 
     if res.is_err() {
+      // declarations never appear inside expressions
+      self.depth = 0;
       self.synchronise();
     }
 
@@ -81,30 +170,57 @@ impl<'a> Parser<'a> {
 
     self.consume(&TokenType::LeftBrace, "Expect '{' before class body.")?;
 
+    let mut fields = Vec::new();
     let mut methods = Vec::new();
+    let mut static_methods = Vec::new();
     while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
-      methods.push(self.function("method")?);
+      if self.is_match(&[&TokenType::Var]) {
+        if self.check(&TokenType::LeftSquareBracket) {
+          return Err(LoxError::parse_error(
+            self.peek(),
+            "Can't destructure into class fields.",
+          ));
+        }
+        fields.push(Rc::new(self.var_declaration()?));
+      } else if self.is_match(&[&TokenType::Class]) {
+        static_methods.push(Rc::new(self.function("static method")?));
+      } else {
+        methods.push(Rc::new(self.function("method")?));
+      }
     }
-    let methods = methods
-      .into_iter()
-      .map(Rc::new)
-      .collect::<Vec<Rc<Stmt>>>()
-      .into();
 
     self.consume(&TokenType::RightBrace, "Expect '}' after class body.")?;
     Ok(Stmt::Class(
       ClassStmt {
         name,
-        methods,
+        fields: fields.into(),
+        methods: methods.into(),
         superclass,
+        static_methods: static_methods.into(),
       }
       .into(),
     ))
   }
 
+  // the body of an `if` or a loop, which recurses just as a block does
+  fn nested_statement(&mut self) -> Result<Stmt, LoxError> {
+    self.nest()?;
+    let stmt = self.statement()?;
+    self.depth -= 1;
+    Ok(stmt)
+  }
+
   fn statement(&mut self) -> Result<Stmt, LoxError> {
+    if self.check(&TokenType::Identifier) && self.check_next(&TokenType::Colon) {
+      return self.labelled_statement();
+    }
+
     if self.is_match(&[&TokenType::For]) {
-      return self.for_statement();
+      return self.for_statement(None);
+    }
+
+    if self.is_match(&[&TokenType::Foreach]) {
+      return self.foreach_statement(None);
     }
 
     if self.is_match(&[&TokenType::If]) {
@@ -120,7 +236,7 @@ impl<'a> Parser<'a> {
     }
 
     if self.is_match(&[&TokenType::While]) {
-      return self.while_statement();
+      return self.while_statement(None);
     }
 
     if self.is_match(&[&TokenType::LeftBrace]) {
@@ -138,27 +254,66 @@ impl<'a> Parser<'a> {
     }
 
     if self.is_match(&[&TokenType::Break]) {
-      return self.break_statement();
+      let (token, label) = self.jump_statement()?;
+      return Ok(Stmt::Break(BreakStmt { token, label }.into()));
+    }
+
+    if self.is_match(&[&TokenType::Continue]) {
+      let (token, label) = self.jump_statement()?;
+      return Ok(Stmt::Continue(ContinueStmt { token, label }.into()));
     }
 
     self.expression_statement()
   }
 
-  fn break_statement(&mut self) -> Result<Stmt, LoxError> {
+  fn labelled_statement(&mut self) -> Result<Stmt, LoxError> {
+    let label = Some(self.advance().clone());
+    self.advance();
+
+    if self.is_match(&[&TokenType::For]) {
+      return self.for_statement(label);
+    }
+
+    if self.is_match(&[&TokenType::Foreach]) {
+      return self.foreach_statement(label);
+    }
+
+    if self.is_match(&[&TokenType::While]) {
+      return self.while_statement(label);
+    }
+
+    Err(LoxError::parse_error(
+      self.peek(),
+      "Expect loop after label.",
+    ))
+  }
+
+  // shared by `break` and `continue`, which differ only in what they do to the loop
+  fn jump_statement(&mut self) -> Result<(Token, Option<Token>), LoxError> {
+    let token = self.previous().clone();
     if self.loop_depth.eq(&0) {
       self.had_error = true;
-      LoxError::parse_error(self.previous(), "Must be inside a loop to use 'break'.");
+      LoxError::parse_error(
+        &token,
+        &format!("Must be inside a loop to use '{}'.", token.get_lexeme()),
+      );
     }
-    self.consume(&TokenType::Semicolon, "Expect ';' after 'break'.")?;
-    return Ok(Stmt::Break(
-      BreakStmt {
-        token: self.peek().clone(),
-      }
-      .into(),
-    ));
+
+    let label = if self.is_match(&[&TokenType::Identifier]) {
+      Some(self.previous().clone())
+    } else {
+      None
+    };
+
+    self.consume(
+      &TokenType::Semicolon,
+      &format!("Expect ';' after '{}'.", token.get_lexeme()),
+    )?;
+    Ok((token, label))
   }
 
-  fn for_statement(&mut self) -> Result<Stmt, LoxError> {
+  fn for_statement(&mut self, label: Option<Token>) -> Result<Stmt, LoxError> {
+    let keyword = self.previous().clone();
     self.consume(&TokenType::LeftBracket, "Expect '(' after 'for'.")?;
 
     let initialiser = if self.is_match(&[&TokenType::Semicolon]) {
@@ -186,47 +341,24 @@ impl<'a> Parser<'a> {
     self.consume(&TokenType::RightBracket, "Expect ')' after for clauses.")?;
 
     self.loop_depth += 1;
-    let body = self.statement();
+    let body = self.nested_statement();
     if body.is_err() {
       self.loop_depth -= 1;
       return body;
     }
 
-    let mut body = body?;
-    if let Some(i) = increment {
-      body = Stmt::Block(
-        BlockStmt {
-          statements: vec![
-            body.into(),
-            Stmt::Expression(
-              ExpressionStmt {
-                expression: i.into(),
-              }
-              .into(),
-            )
-            .into(),
-          ]
-          .into(),
-        }
-        .into(),
-      );
-    }
-
-    body = Stmt::While(
+    // the increment lives on the loop itself so that `continue` still runs it;
+    // variables declared by the initialiser are rebound for every iteration, so
+    // closures made in the body each keep their own value
+    let per_iteration = matches!(initialiser, Some(Stmt::Var(_) | Stmt::Destructure(_)));
+    let mut body = Stmt::While(
       WhileStmt {
-        condition: condition.map_or_else(
-          || {
-            Expr::Literal(
-              LiteralExpr {
-                value: Some(Literal::Boolean(true)),
-              }
-              .into(),
-            )
-            .into()
-          },
-          |c| c.into(),
-        ),
-        body: body.into(),
+        label,
+        keyword,
+        condition: condition.map(|c| c.into()),
+        body: body?.into(),
+        increment: increment.map(|i| i.into()),
+        per_iteration,
       }
       .into(),
     );
@@ -244,20 +376,49 @@ impl<'a> Parser<'a> {
     Ok(body)
   }
 
+  fn foreach_statement(&mut self, label: Option<Token>) -> Result<Stmt, LoxError> {
+    self.consume(&TokenType::LeftBracket, "Expect '(' after 'foreach'.")?;
+    let name = self
+      .consume(&TokenType::Identifier, "Expect loop variable name.")?
+      .clone();
+    self.consume(&TokenType::In, "Expect 'in' after loop variable.")?;
+    let iterable = self.expression()?;
+    self.consume(
+      &TokenType::RightBracket,
+      "Expect ')' after foreach clauses.",
+    )?;
+
+    self.loop_depth += 1;
+    let body = self.nested_statement();
+    self.loop_depth -= 1;
+
+    Ok(Stmt::Foreach(
+      ForeachStmt {
+        label,
+        name,
+        iterable: iterable.into(),
+        body: body?.into(),
+      }
+      .into(),
+    ))
+  }
+
   fn if_statement(&mut self) -> Result<Stmt, LoxError> {
+    let keyword = self.previous().clone();
     self.consume(&TokenType::LeftBracket, "Expect '(' after 'if'.")?;
     let condition = self.expression()?.into();
     self.consume(&TokenType::RightBracket, "Expect ')' after if condition.")?;
 
-    let then_branch = self.statement()?.into();
+    let then_branch = self.nested_statement()?.into();
     let else_branch = if self.is_match(&[&TokenType::Else]) {
-      Some(self.statement()?.into())
+      Some(self.nested_statement()?.into())
     } else {
       None
     };
 
     Ok(Stmt::If(
       IfStmt {
+        keyword,
         condition,
         then_branch,
         else_branch,
@@ -285,11 +446,20 @@ impl<'a> Parser<'a> {
       Some(self.expression()?.into())
     };
 
-    self.consume(&TokenType::Semicolon, "Expect ';' after return value.")?;
+    let message = if self.check(&TokenType::RightBrace) {
+      "Expect ';' after return value (did you forget the semicolon?)."
+    } else {
+      "Expect ';' after return value."
+    };
+    self.consume(&TokenType::Semicolon, message)?;
     Ok(Stmt::Return(ReturnStmt { keyword, value }.into()))
   }
 
   fn var_declaration(&mut self) -> Result<Stmt, LoxError> {
+    if self.is_match(&[&TokenType::LeftSquareBracket]) {
+      return self.destructure_declaration();
+    }
+
     let name = self
       .consume(&TokenType::Identifier, "Expect variable name.")?
       .clone();
@@ -306,13 +476,52 @@ impl<'a> Parser<'a> {
     Ok(Stmt::Var(VarStmt { name, initialiser }.into()))
   }
 
-  fn while_statement(&mut self) -> Result<Stmt, LoxError> {
+  // `var [a, b] = f();`, which needs an initialiser to take the values from
+  fn destructure_declaration(&mut self) -> Result<Stmt, LoxError> {
+    let bracket = self.previous().clone();
+    let mut names = vec![self
+      .consume(&TokenType::Identifier, "Expect variable name.")?
+      .clone()];
+    while self.is_match(&[&TokenType::Comma]) {
+      names.push(
+        self
+          .consume(&TokenType::Identifier, "Expect variable name.")?
+          .clone(),
+      );
+    }
+
+    self.consume(
+      &TokenType::RightSquareBracket,
+      "Expect ']' after variable names.",
+    )?;
+    self.consume(
+      &TokenType::Assign,
+      "Expect '=' after destructuring pattern.",
+    )?;
+    let initialiser = self.expression()?.into();
+    self.consume(
+      &TokenType::Semicolon,
+      "Expect ';' after variable declaration.",
+    )?;
+
+    Ok(Stmt::Destructure(
+      DestructureStmt {
+        bracket,
+        names,
+        initialiser,
+      }
+      .into(),
+    ))
+  }
+
+  fn while_statement(&mut self, label: Option<Token>) -> Result<Stmt, LoxError> {
+    let keyword = self.previous().clone();
     self.consume(&TokenType::LeftBracket, "Expect '(' after 'while'.")?;
     let condition = self.expression()?;
     self.consume(&TokenType::RightBracket, "Expect ')' after condition.")?;
 
     self.loop_depth += 1;
-    let body = self.statement();
+    let body = self.nested_statement();
     if body.is_err() {
       self.loop_depth -= 1;
       return body;
@@ -321,8 +530,12 @@ impl<'a> Parser<'a> {
     self.loop_depth -= 1;
     Ok(Stmt::While(
       WhileStmt {
-        condition: condition.into(),
+        label,
+        keyword,
+        condition: Some(condition.into()),
         body: body?.into(),
+        increment: None,
+        per_iteration: false,
       }
       .into(),
     ))
@@ -330,13 +543,20 @@ impl<'a> Parser<'a> {
 
   fn expression_statement(&mut self) -> Result<Stmt, LoxError> {
     let expr = self.expression()?;
-    self.consume(&TokenType::Semicolon, "Expect ';' after expression.")?;
-    Ok(Stmt::Expression(
+    let statement = Stmt::Expression(
       ExpressionStmt {
         expression: expr.into(),
       }
       .into(),
-    ))
+    );
+
+    // typed at the prompt without a semicolon, so its value is wanted
+    if self.repl && self.is_at_end() {
+      return Ok(Parser::printed(statement));
+    }
+
+    self.consume(&TokenType::Semicolon, "Expect ';' after expression.")?;
+    Ok(statement)
   }
 
   fn function(&mut self, kind: &str) -> Result<Stmt, LoxError> {
@@ -350,23 +570,30 @@ impl<'a> Parser<'a> {
     )?;
 
     let mut params = Vec::new();
+    let mut is_variadic = false;
     if !self.check(&TokenType::RightBracket) {
-      params.push(
-        self
-          .consume(&TokenType::Identifier, "Expect parameter name.")?
-          .clone(),
-      );
-      while self.is_match(&[&TokenType::Comma]) {
+      loop {
+        if is_variadic {
+          self.had_error = true;
+          LoxError::parse_error(self.peek(), "Rest parameter must be the last parameter.");
+        }
+
         if params.len() >= 255 {
           self.had_error = true;
           LoxError::parse_error(self.peek(), "Can't have more than 255 parameters.");
         }
 
+        is_variadic = self.is_match(&[&TokenType::Ellipsis]);
         params.push(
           self
             .consume(&TokenType::Identifier, "Expect parameter name.")?
             .clone(),
         );
+
+        // allow a trailing comma before the closing bracket
+        if !self.is_match(&[&TokenType::Comma]) || self.check(&TokenType::RightBracket) {
+          break;
+        }
       }
     }
 
@@ -384,10 +611,19 @@ impl<'a> Parser<'a> {
       .collect::<Vec<Rc<Stmt>>>()
       .into();
 
-    Ok(Stmt::Function(FunctionStmt { name, params, body }.into()))
+    Ok(Stmt::Function(
+      FunctionStmt {
+        name,
+        params,
+        is_variadic,
+        body,
+      }
+      .into(),
+    ))
   }
 
   fn block(&mut self) -> Result<Vec<Stmt>, LoxError> {
+    self.nest()?;
     let mut statements = Vec::new();
 
     while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
@@ -395,18 +631,21 @@ impl<'a> Parser<'a> {
     }
 
     self.consume(&TokenType::RightBrace, "Expect '}' after block.")?;
+    self.depth -= 1;
     Ok(statements)
   }
 
   fn assignment(&mut self) -> Result<Expr, LoxError> {
-    let expr = self.or()?;
+    let expr = self.coalesce()?;
 
     if !self.is_match(&[&TokenType::Assign]) {
       return Ok(expr);
     }
 
     let equals = self.previous().clone();
+    self.nest()?;
     let value = self.assignment()?;
+    self.depth -= 1;
 
     if let Expr::Variable(v) = expr {
       return Ok(Expr::Assign(
@@ -429,15 +668,80 @@ impl<'a> Parser<'a> {
       ));
     }
 
+    if let Expr::Index(i) = &expr {
+      if let (Some(index), false) = (&i.index, i.is_slice) {
+        return Ok(Expr::IndexSet(
+          IndexSetExpr {
+            object: i.object.clone(),
+            bracket: i.bracket.clone(),
+            index: index.clone(),
+            value: value.into(),
+          }
+          .into(),
+        ));
+      }
+    }
+
+    // `[a, b] = f();`, where every element must be a plain variable
+    if let Expr::Array(a) = &expr {
+      if !a.elements.is_empty()
+        && a
+          .elements
+          .iter()
+          .all(|e| matches!(e.as_ref(), Expr::Variable(_)))
+      {
+        return Ok(Expr::Destructure(
+          DestructureExpr {
+            bracket: a.bracket.clone(),
+            targets: a.elements.clone(),
+            value: value.into(),
+          }
+          .into(),
+        ));
+      }
+    }
+
+    if let Expr::This(t) = &expr {
+      return Err(LoxError::parse_error(
+        &t.keyword,
+        "Cannot assign to 'this'.",
+      ));
+    }
+
     self.had_error = true;
     LoxError::parse_error(&equals, "Invalid assignment target.");
     Ok(expr)
   }
 
+  // right-associative, so `a ?? b ?? c` is `a ?? (b ?? c)`
+  fn coalesce(&mut self) -> Result<Expr, LoxError> {
+    let expr = self.or()?;
+
+    if !self.is_match(&[&TokenType::QuestionQuestion]) {
+      return Ok(expr);
+    }
+
+    let operator = self.previous().clone();
+    self.nest()?;
+    let right = self.coalesce()?;
+    self.depth -= 1;
+
+    Ok(Expr::Logical(
+      LogicalExpr {
+        left: expr.into(),
+        operator,
+        right: right.into(),
+      }
+      .into(),
+    ))
+  }
+
   fn or(&mut self) -> Result<Expr, LoxError> {
     let mut expr = self.and()?;
+    let depth = self.depth;
 
     while self.is_match(&[&TokenType::Or]) {
+      self.nest()?;
       expr = Expr::Logical(
         LogicalExpr {
           left: expr.into(),
@@ -448,13 +752,16 @@ impl<'a> Parser<'a> {
       );
     }
 
+    self.depth = depth;
     Ok(expr)
   }
 
   fn and(&mut self) -> Result<Expr, LoxError> {
     let mut expr = self.equality()?;
+    let depth = self.depth;
 
     while self.is_match(&[&TokenType::And]) {
+      self.nest()?;
       expr = Expr::Logical(
         LogicalExpr {
           left: expr.into(),
@@ -465,88 +772,127 @@ impl<'a> Parser<'a> {
       );
     }
 
+    self.depth = depth;
     Ok(expr)
   }
 
   fn equality(&mut self) -> Result<Expr, LoxError> {
+    let start = self.peek().get_offset();
     let mut expr = self.comparison()?;
+    let depth = self.depth;
 
     while self.is_match(&[&TokenType::BangEqual, &TokenType::EqualEqual]) {
+      self.nest()?;
       expr = Expr::Binary(
         BinaryExpr {
           left: expr.into(),
           operator: self.previous().clone(),
           right: self.comparison()?.into(),
+          span: (start, self.previous().get_end_offset()),
         }
         .into(),
       );
     }
 
+    self.depth = depth;
     Ok(expr)
   }
 
   fn comparison(&mut self) -> Result<Expr, LoxError> {
+    let start = self.peek().get_offset();
     let mut expr = self.term()?;
+    let depth = self.depth;
+    let mut is_comparison = false;
 
     while self.is_match(&[
       &TokenType::Greater,
       &TokenType::GreaterEqual,
       &TokenType::Less,
       &TokenType::LessEqual,
+      &TokenType::Instanceof,
     ]) {
+      self.nest()?;
+
+      // `a < b < c` would compare a boolean with a number at runtime
+      if is_comparison {
+        return Err(LoxError::parse_error(
+          self.previous(),
+          "Chained comparison is not allowed; use explicit parentheses or `and`.",
+        ));
+      }
+
+      is_comparison = true;
       expr = Expr::Binary(
         BinaryExpr {
           left: expr.into(),
           operator: self.previous().clone(),
           right: self.term()?.into(),
+          span: (start, self.previous().get_end_offset()),
         }
         .into(),
       );
     }
 
+    self.depth = depth;
     Ok(expr)
   }
 
   fn term(&mut self) -> Result<Expr, LoxError> {
+    let start = self.peek().get_offset();
     let mut expr = self.factor()?;
+    let depth = self.depth;
 
     while self.is_match(&[&TokenType::Minus, &TokenType::Plus]) {
+      self.nest()?;
       expr = Expr::Binary(
         BinaryExpr {
           left: expr.into(),
           operator: self.previous().clone(),
-          right: self.term()?.into(),
+          right: self.factor()?.into(),
+          span: (start, self.previous().get_end_offset()),
         }
         .into(),
       );
     }
 
+    self.depth = depth;
     Ok(expr)
   }
 
   fn factor(&mut self) -> Result<Expr, LoxError> {
+    let start = self.peek().get_offset();
     let mut expr = self.unary()?;
+    let depth = self.depth;
 
-    while self.is_match(&[&TokenType::Slash, &TokenType::Star]) {
+    while self.is_match(&[&TokenType::Slash, &TokenType::Star, &TokenType::TildeSlash]) {
+      self.nest()?;
       expr = Expr::Binary(
         BinaryExpr {
           left: expr.into(),
           operator: self.previous().clone(),
           right: self.unary()?.into(),
+          span: (start, self.previous().get_end_offset()),
         }
         .into(),
       );
     }
 
+    self.depth = depth;
     Ok(expr)
   }
 
   fn unary(&mut self) -> Result<Expr, LoxError> {
-    if self.is_match(&[&TokenType::Bang, &TokenType::Minus]) {
+    if self.is_match(&[&TokenType::Bang, &TokenType::Minus, &TokenType::Plus]) {
+      let operator = self.previous().clone();
+      self.nest()?;
+      let right = self.unary()?;
+      self.depth -= 1;
+
       return Ok(Expr::Unary(
         UnaryExpr {
-          operator: self.previous().clone(),
-          right: self.unary()?.into(),
+          span: (operator.get_offset(), self.previous().get_end_offset()),
+          operator,
+          right: right.into(),
         }
         .into(),
       ));
@@ -555,12 +901,17 @@ impl<'a> Parser<'a> {
     self.call()
   }
 
-  fn finish_call(&mut self, callee: Rc<Expr>) -> Result<Expr, LoxError> {
+  fn finish_call(&mut self, callee: Rc<Expr>, start: usize) -> Result<Expr, LoxError> {
     let mut arguments = Vec::new();
 
     if !self.check(&TokenType::RightBracket) {
       arguments.push(self.expression()?);
       while self.is_match(&[&TokenType::Comma]) {
+        // allow a trailing comma before the closing bracket
+        if self.check(&TokenType::RightBracket) {
+          break;
+        }
+
         if arguments.len() >= 255 {
           self.had_error = true;
           return Err(LoxError::parse_error(
@@ -583,17 +934,55 @@ impl<'a> Parser<'a> {
           .map(Rc::new)
           .collect::<Vec<Rc<Expr>>>()
           .into(),
+        span: (start, bracket.get_end_offset()),
+      }
+      .into(),
+    ))
+  }
+
+  fn finish_index(&mut self, object: Rc<Expr>) -> Result<Expr, LoxError> {
+    let index = if self.check(&TokenType::Colon) {
+      None
+    } else {
+      Some(self.expression()?.into())
+    };
+
+    let is_slice = self.is_match(&[&TokenType::Colon]);
+    let end = if is_slice && !self.check(&TokenType::RightSquareBracket) {
+      Some(self.expression()?.into())
+    } else {
+      None
+    };
+
+    let bracket = self.consume(&TokenType::RightSquareBracket, "Expect ']' after index.")?;
+
+    Ok(Expr::Index(
+      IndexExpr {
+        object,
+        bracket: bracket.clone(),
+        index,
+        end,
+        is_slice,
       }
       .into(),
     ))
   }
 
   fn call(&mut self) -> Result<Expr, LoxError> {
+    let start = self.peek().get_offset();
     let mut expr = self.primary()?;
+    let depth = self.depth;
 
     loop {
+      if self.check(&TokenType::LeftBracket)
+        || self.check(&TokenType::Dot)
+        || self.check(&TokenType::LeftSquareBracket)
+      {
+        self.nest()?;
+      }
+
       if self.is_match(&[&TokenType::LeftBracket]) {
-        expr = self.finish_call(expr.into())?;
+        expr = self.finish_call(expr.into(), start)?;
       } else if self.is_match(&[&TokenType::Dot]) {
         let name = self.consume(&TokenType::Identifier, "Expect property name after '.'.")?;
         expr = Expr::Get(
@@ -603,14 +992,40 @@ impl<'a> Parser<'a> {
           }
           .into(),
         );
+      } else if self.is_match(&[&TokenType::LeftSquareBracket]) {
+        expr = self.finish_index(expr.into())?;
       } else {
         break;
       }
     }
 
+    self.depth = depth;
+
+    if self.is_match(&[&TokenType::PlusPlus, &TokenType::MinusMinus]) {
+      return Ok(self.postfix(expr));
+    }
+
     Ok(expr)
   }
 
+  fn postfix(&mut self, target: Expr) -> Expr {
+    let operator = self.previous().clone();
+
+    if !matches!(target, Expr::Variable(_) | Expr::Get(_)) {
+      self.had_error = true;
+      LoxError::parse_error(&operator, "Invalid increment target.");
+      return target;
+    }
+
+    Expr::Postfix(
+      PostfixExpr {
+        target: target.into(),
+        operator,
+      }
+      .into(),
+    )
+  }
+
   fn primary(&mut self) -> Result<Expr, LoxError> {
     if self.is_match(&[&TokenType::False]) {
       return Ok(Expr::Literal(
@@ -648,6 +1063,27 @@ impl<'a> Parser<'a> {
       ));
     }
 
+    // in statement position `if` is always a statement, so this form is only
+    // reached where an expression is expected, and must have an `else`
+    if self.is_match(&[&TokenType::If]) {
+      let keyword = self.previous().clone();
+      self.consume(&TokenType::LeftBracket, "Expect '(' after 'if'.")?;
+      let condition = self.expression()?;
+      self.consume(&TokenType::RightBracket, "Expect ')' after if condition.")?;
+      let then_branch = self.expression()?;
+      self.consume(&TokenType::Else, "Expect 'else' in if expression.")?;
+      let else_branch = self.expression()?;
+      return Ok(Expr::If(
+        IfExpr {
+          keyword,
+          condition: condition.into(),
+          then_branch: then_branch.into(),
+          else_branch: else_branch.into(),
+        }
+        .into(),
+      ));
+    }
+
     if self.is_match(&[&TokenType::Super]) {
       let keyword = self.previous().clone();
       self.consume(&TokenType::Dot, "Expect '.' after 'super'.")?;
@@ -675,6 +1111,14 @@ impl<'a> Parser<'a> {
       ));
     }
 
+    if self.is_match(&[&TokenType::LeftBrace]) {
+      return self.map();
+    }
+
+    if self.is_match(&[&TokenType::LeftSquareBracket]) {
+      return self.array();
+    }
+
     if self.is_match(&[&TokenType::LeftBracket]) {
       let expr = self.expression()?;
       self.consume(&TokenType::RightBracket, "Expect ')' after expression.")?;
@@ -689,6 +1133,79 @@ impl<'a> Parser<'a> {
     Err(LoxError::parse_error(self.peek(), "Expect expression."))
   }
 
+  fn array(&mut self) -> Result<Expr, LoxError> {
+    let mut elements = Vec::new();
+
+    if !self.check(&TokenType::RightSquareBracket) {
+      loop {
+        elements.push(self.expression()?.into());
+
+        if !self.is_match(&[&TokenType::Comma]) {
+          break;
+        }
+      }
+    }
+
+    let bracket = self
+      .consume(
+        &TokenType::RightSquareBracket,
+        "Expect ']' after array elements.",
+      )?
+      .clone();
+    Ok(Expr::Array(
+      ArrayExpr {
+        bracket,
+        elements: elements.into(),
+      }
+      .into(),
+    ))
+  }
+
+  // a '{' can only start a map in expression position, as statements treat it as a block
+  fn map(&mut self) -> Result<Expr, LoxError> {
+    let brace = self.previous().clone();
+    let mut keys = Vec::new();
+    let mut values = Vec::new();
+
+    if !self.check(&TokenType::RightBrace) {
+      loop {
+        keys.push(self.expression()?.into());
+        self.consume(&TokenType::Colon, "Expect ':' after map key.")?;
+        values.push(self.expression()?.into());
+
+        if !self.is_match(&[&TokenType::Comma]) {
+          break;
+        }
+      }
+    }
+
+    self.consume(&TokenType::RightBrace, "Expect '}' after map entries.")?;
+    Ok(Expr::Map(
+      MapExpr {
+        brace,
+        keys: keys.into(),
+        values: values.into(),
+      }
+      .into(),
+    ))
+  }
+
+  fn nest(&mut self) -> Result<(), LoxError> {
+    self.nest_by(1)
+  }
+
+  fn nest_by(&mut self, depth: usize) -> Result<(), LoxError> {
+    if self.depth + depth > self.max_depth {
+      return Err(LoxError::parse_error(
+        self.peek(),
+        "Code is nested too deeply.",
+      ));
+    }
+
+    self.depth += depth;
+    Ok(())
+  }
+
   fn is_match(&mut self, token_types: &[&TokenType]) -> bool {
     for token_type in token_types {
       if self.check(token_type) {
@@ -713,7 +1230,14 @@ impl<'a> Parser<'a> {
       return false;
     }
 
-    return self.peek().is_type(token_type);
+    self.peek().is_type(token_type)
+  }
+
+  fn check_next(&self, token_type: &TokenType) -> bool {
+    self
+      .tokens
+      .get(self.current + 1)
+      .is_some_and(|t| t.is_type(token_type))
   }
 
   fn advance(&mut self) -> &Token {
@@ -728,20 +1252,17 @@ impl<'a> Parser<'a> {
     self.peek().is_type(&TokenType::Eof)
   }
 
+  // tokens that do not end in EOF, or none at all, read as if they did
   fn peek(&self) -> &Token {
-    if let Some(t) = self.tokens.get(self.current) {
-      return t;
-    }
-
-    unreachable!()
+    self.tokens.get(self.current).unwrap_or(&self.eof)
   }
 
   fn previous(&self) -> &Token {
-    if let Some(t) = self.tokens.get(self.current - 1) {
-      return t;
-    }
-
-    unreachable!()
+    self
+      .current
+      .checked_sub(1)
+      .and_then(|i| self.tokens.get(i))
+      .unwrap_or(&self.eof)
   }
 
   fn synchronise(&mut self) {
@@ -757,6 +1278,7 @@ impl<'a> Parser<'a> {
         &TokenType::Fun,
         &TokenType::Var,
         &TokenType::For,
+        &TokenType::Foreach,
         &TokenType::If,
         &TokenType::While,
         &TokenType::Print,
@@ -769,3 +1291,514 @@ impl<'a> Parser<'a> {
     }
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  use crate::scanner::*;
+
+  fn parse(source: &str) -> Result<Vec<Stmt>, LoxError> {
+    let mut scanner = Scanner::new(source);
+    let tokens = scanner.scan_tokens()?;
+    Parser::new(tokens).parse()
+  }
+
+  #[test]
+  fn test_unary_plus() -> Result<(), LoxError> {
+    let statements = parse("print +5;\nprint 1 + +2;")?;
+    assert!(matches!(
+      &statements[0],
+      Stmt::Print(p) if matches!(p.expression.as_ref(), Expr::Unary(u) if u.operator.is_type(&TokenType::Plus))
+    ));
+    assert!(matches!(
+      &statements[1],
+      Stmt::Print(p) if matches!(p.expression.as_ref(), Expr::Binary(b) if matches!(b.right.as_ref(), Expr::Unary(_)))
+    ));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_destructuring() -> Result<(), LoxError> {
+    let statements = parse("var [a, b] = [1, 2];\n[a, b] = [b, a];")?;
+    assert!(matches!(&statements[0], Stmt::Destructure(d) if d.names.len() == 2));
+    assert!(matches!(
+      &statements[1],
+      Stmt::Expression(e) if matches!(e.expression.as_ref(), Expr::Destructure(d) if d.targets.len() == 2)
+    ));
+
+    assert!(parse("var [a, b];").is_err());
+    assert!(parse("[a, 1] = [1, 2];").is_err());
+
+    let mut scanner = Scanner::new("class A { var [a, b] = [1, 2]; }");
+    let tokens = scanner.scan_tokens()?;
+    let res = Parser::new(tokens).declaration();
+    assert!(res.is_err_and(|e| e.get_message() == Some("Can't destructure into class fields.")));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_auto_print() -> Result<(), LoxError> {
+    let source = "2 + 2;\n{ 3; }\nfun f() { 4; }\nprint 5;";
+    let mut scanner = Scanner::new(source);
+    let tokens = scanner.scan_tokens()?;
+
+    let statements = Parser::new(tokens).with_auto_print(true).parse()?;
+    assert!(matches!(&statements[0], Stmt::Print(_)));
+    assert!(
+      matches!(&statements[1], Stmt::Block(b) if matches!(b.statements[0].as_ref(), Stmt::Expression(_)))
+    );
+    assert!(
+      matches!(&statements[2], Stmt::Function(f) if matches!(f.body[0].as_ref(), Stmt::Expression(_)))
+    );
+    assert!(matches!(&statements[3], Stmt::Print(_)));
+
+    let statements = parse(source)?;
+    assert!(matches!(&statements[0], Stmt::Expression(_)));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_expression_spans() -> Result<(), LoxError> {
+    let source = "print -a + f(1, 2) * 3;";
+    let statements = parse(source)?;
+    let Stmt::Print(p) = &statements[0] else {
+      panic!("expected a print statement");
+    };
+    let Expr::Binary(sum) = p.expression.as_ref() else {
+      panic!("expected a binary expression");
+    };
+    let (Expr::Unary(negation), Expr::Binary(product)) = (sum.left.as_ref(), sum.right.as_ref())
+    else {
+      panic!("expected unary and binary operands");
+    };
+    let Expr::Call(call) = product.left.as_ref() else {
+      panic!("expected a call");
+    };
+
+    let text = |(start, end): Span| {
+      source
+        .chars()
+        .skip(start)
+        .take(end - start)
+        .collect::<String>()
+    };
+    assert_eq!(text(sum.span), "-a + f(1, 2) * 3");
+    assert_eq!(text(negation.span), "-a");
+    assert_eq!(text(product.span), "f(1, 2) * 3");
+    assert_eq!(text(call.span), "f(1, 2)");
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_if_expression() -> Result<(), LoxError> {
+    let statements = parse("var x = if (c) 1 else 2;\nif (c) 1; else 2;")?;
+    assert!(matches!(
+      &statements[0],
+      Stmt::Var(v) if matches!(v.initialiser.as_deref(), Some(Expr::If(_)))
+    ));
+    assert!(matches!(&statements[1], Stmt::If(_)));
+
+    let mut scanner = Scanner::new("var x = if (c) 1;");
+    let tokens = scanner.scan_tokens()?;
+    let res = Parser::new(tokens).declaration();
+    assert!(res.is_err_and(|e| e.get_message() == Some("Expect 'else' in if expression.")));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_assign_to_this() -> Result<(), LoxError> {
+    let mut scanner = Scanner::new("this = 1;");
+    let tokens = scanner.scan_tokens()?;
+    let res = Parser::new(tokens).declaration();
+    assert!(res.is_err_and(|e| e.get_message() == Some("Cannot assign to 'this'.")));
+
+    assert!(parse("class A { m() { this = 1; } }").is_err());
+    assert!(parse("class A { m() { this.a = 1; } }").is_ok());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_parse_repl() -> Result<(), LoxError> {
+    let mut scanner = Scanner::new("1 + 2");
+    let tokens = scanner.scan_tokens()?;
+    let statements = Parser::new(tokens).parse_repl()?;
+    assert_eq!(statements.len(), 1);
+    assert!(matches!(&statements[0], Stmt::Print(_)));
+
+    // only the one without a semicolon is printed
+    let mut scanner = Scanner::new("var a = 1; a; a");
+    let tokens = scanner.scan_tokens()?;
+    let statements = Parser::new(tokens).parse_repl()?;
+    assert_eq!(statements.len(), 3);
+    assert!(matches!(&statements[1], Stmt::Expression(_)));
+    assert!(matches!(&statements[2], Stmt::Print(_)));
+
+    // only the last expression may leave it out, and files stay strict
+    let mut scanner = Scanner::new("1 2");
+    let tokens = scanner.scan_tokens()?;
+    assert!(Parser::new(tokens).parse_repl().is_err());
+    assert!(parse("1 + 2").is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_next_repl_statement() -> Result<(), LoxError> {
+    let mut scanner = Scanner::new("print 1; print ; print 3; 4");
+    let tokens = scanner.scan_tokens()?;
+    let mut parser = Parser::new(tokens);
+
+    assert!(parser.next_repl_statement().is_some_and(|s| s.is_ok()));
+    assert!(parser.next_repl_statement().is_some_and(|s| s.is_err()));
+    assert!(parser.had_error());
+    assert!(parser.next_repl_statement().is_some_and(|s| s.is_ok()));
+    assert!(parser.next_repl_statement().is_some_and(|s| s.is_ok()));
+    assert!(parser.next_repl_statement().is_none());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_tokens_without_eof() -> Result<(), LoxError> {
+    let mut scanner = Scanner::new("print 1;\nprint 2");
+    let tokens = scanner.scan_tokens()?;
+
+    let mut parser = Parser::new(&[]);
+    assert!(parser.parse().is_ok_and(|s| s.is_empty()));
+    assert!(!parser.had_error());
+
+    let mut parser = Parser::new(&tokens[..3]);
+    assert!(parser.parse().is_ok_and(|s| s.len() == 1));
+
+    // the missing semicolon is reported against the stand-in EOF
+    let mut parser = Parser::new(&tokens[..tokens.len() - 1]);
+    assert!(parser.parse().is_err());
+    assert!(parser.had_error());
+    assert!(Parser::new(&tokens[..1]).parse().is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_malformed_input_terminates() {
+    let sources = [
+      "}",
+      ")(",
+      "class",
+      "fun",
+      "var",
+      "else else else",
+      "for (;;",
+      "{ { {",
+      "a: b: c:",
+      "break continue ; ;",
+      "class A { class } fun (",
+      "print print print",
+      "1 + ;; ) } ] ...",
+    ];
+
+    // a hang shows up as a timeout instead of a test that never finishes
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+      for source in sources {
+        sender.send(parse(source).is_err()).ok();
+      }
+    });
+
+    for _ in sources {
+      let res = receiver.recv_timeout(std::time::Duration::from_secs(5));
+      assert_eq!(res, Ok(true));
+    }
+  }
+
+  #[test]
+  fn test_return_missing_semicolon() -> Result<(), LoxError> {
+    let mut scanner = Scanner::new("fun f() { return 1 }");
+    let tokens = scanner.scan_tokens()?;
+    let res = Parser::new(tokens).declaration();
+    assert!(res.is_err_and(|e| {
+      e.get_message() == Some("Expect ';' after return value (did you forget the semicolon?).")
+    }));
+
+    let mut scanner = Scanner::new("fun f() { return 1 2; }");
+    let tokens = scanner.scan_tokens()?;
+    let res = Parser::new(tokens).declaration();
+    assert!(res.is_err_and(|e| e.get_message() == Some("Expect ';' after return value.")));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_trailing_comma_in_call() -> Result<(), LoxError> {
+    let statements = parse("f(1, 2,);")?;
+
+    let [Stmt::Expression(s)] = statements.as_slice() else {
+      panic!("expected a single expression statement");
+    };
+    assert!(matches!(s.expression.as_ref(), Expr::Call(c) if c.arguments.len() == 2));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_trailing_comma_in_declaration() -> Result<(), LoxError> {
+    let statements = parse("fun g(a, b,) {}")?;
+
+    let [Stmt::Function(f)] = statements.as_slice() else {
+      panic!("expected a single function declaration");
+    };
+    assert_eq!(f.params.len(), 2);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_lone_comma_in_call() {
+    assert!(parse("f(,);").is_err());
+    assert!(parse("f(1,,);").is_err());
+  }
+
+  #[test]
+  fn test_chained_comparison() -> Result<(), LoxError> {
+    for source in ["print 1 < 2 < 3;", "print 1 >= 2 <= 3;"] {
+      let mut scanner = Scanner::new(source);
+      let tokens = scanner.scan_tokens()?;
+      let res = Parser::new(tokens).declaration();
+      assert!(res.is_err_and(|e| e.get_message()
+        == Some("Chained comparison is not allowed; use explicit parentheses or `and`.")));
+
+      assert!(parse(source).is_err());
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_parenthesised_comparison() -> Result<(), LoxError> {
+    assert_eq!(parse("print (1 < 2) == (2 < 3);")?.len(), 1);
+    assert_eq!(parse("print 1 < 2 and 2 < 3;")?.len(), 1);
+    Ok(())
+  }
+
+  #[test]
+  fn test_loop_label() -> Result<(), LoxError> {
+    let statements = parse("outer: while (true) break outer;")?;
+
+    let [Stmt::While(w)] = statements.as_slice() else {
+      panic!("expected a single while statement");
+    };
+    assert!(w.label.as_ref().is_some_and(|l| l.get_lexeme() == "outer"));
+
+    assert!(parse("outer: print 1;").is_err());
+    assert!(parse("continue;").is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_deeply_nested_grouping() -> Result<(), LoxError> {
+    // the default limit must hold on a main thread's stack, which is larger
+    // than a test thread's
+    std::thread::Builder::new()
+      .stack_size(8 * 1024 * 1024)
+      .spawn(|| {
+        let source = format!("print {}1{};", "(".repeat(10000), ")".repeat(10000));
+        assert!(parse(&source).is_err());
+
+        let source = format!("print 1{};", " + 1".repeat(10000));
+        assert!(parse(&source).is_err());
+
+        // a long chain is not nested the way brackets are
+        let source = format!("print \"\"{};", " + \"a\"".repeat(300));
+        assert!(parse(&source).is_ok());
+        let source = format!("print 1{};", " < 1 or 1".repeat(300));
+        assert!(parse(&source).is_ok());
+      })
+      .map_err(|_| LoxError::system_error("COULD NOT SPAWN THREAD."))?
+      .join()
+      .map_err(|_| LoxError::system_error("THREAD PANICKED."))?;
+
+    let mut scanner = Scanner::new("print ((1));");
+    let tokens = scanner.scan_tokens()?;
+    assert!(Parser::new(tokens)
+      .with_max_depth(3 * EXPRESSION_DEPTH - 1)
+      .parse()
+      .is_err());
+    assert!(Parser::new(tokens)
+      .with_max_depth(3 * EXPRESSION_DEPTH)
+      .parse()
+      .is_ok());
+
+    let mut scanner = Scanner::new("print 1 + 1 - 1;");
+    let tokens = scanner.scan_tokens()?;
+    assert!(Parser::new(tokens)
+      .with_max_depth(EXPRESSION_DEPTH + 1)
+      .parse()
+      .is_err());
+    assert!(Parser::new(tokens)
+      .with_max_depth(EXPRESSION_DEPTH + 2)
+      .parse()
+      .is_ok());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_deeply_nested_statements() -> Result<(), LoxError> {
+    std::thread::Builder::new()
+      .stack_size(8 * 1024 * 1024)
+      .spawn(|| {
+        let source = format!("{}{}", "{".repeat(3000), "}".repeat(3000));
+        assert!(parse(&source).is_err());
+
+        let source = format!("{}print 1;", "if (true) ".repeat(3000));
+        assert!(parse(&source).is_err());
+
+        let source = format!("{}print 1;", "while (false) ".repeat(3000));
+        assert!(parse(&source).is_err());
+
+        let source = format!("{}{}", "fun f() {".repeat(3000), "}".repeat(3000));
+        assert!(parse(&source).is_err());
+      })
+      .map_err(|_| LoxError::system_error("COULD NOT SPAWN THREAD."))?
+      .join()
+      .map_err(|_| LoxError::system_error("THREAD PANICKED."))?;
+
+    let mut scanner = Scanner::new("{ { print ((1)); } }");
+    let tokens = scanner.scan_tokens()?;
+    assert!(Parser::new(tokens)
+      .with_max_depth(2 + 3 * EXPRESSION_DEPTH - 1)
+      .parse()
+      .is_err());
+    assert!(Parser::new(tokens)
+      .with_max_depth(2 + 3 * EXPRESSION_DEPTH)
+      .parse()
+      .is_ok());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_rest_parameter() -> Result<(), LoxError> {
+    let statements = parse("fun f(a, ...rest,) {}")?;
+
+    let [Stmt::Function(f)] = statements.as_slice() else {
+      panic!("expected a single function declaration");
+    };
+    assert!(f.is_variadic);
+    assert_eq!(f.params.len(), 2);
+
+    assert!(parse("fun f(...rest, a) {}").is_err());
+    Ok(())
+  }
+
+  fn parse_expression(source: &str) -> Result<Rc<Expr>, LoxError> {
+    let statements = parse(source)?;
+
+    let [Stmt::Expression(s)] = statements.as_slice() else {
+      panic!("expected a single expression statement");
+    };
+    Ok(s.expression.clone())
+  }
+
+  #[test]
+  fn test_assignment_binds_looser_than_logical() -> Result<(), LoxError> {
+    let expr = parse_expression("x = a or b;")?;
+    let Expr::Assign(a) = expr.as_ref() else {
+      panic!("expected an assignment");
+    };
+    assert!(matches!(a.value.as_ref(), Expr::Logical(l) if l.operator.is_type(&TokenType::Or)));
+
+    let expr = parse_expression("x = y = z;")?;
+    let Expr::Assign(a) = expr.as_ref() else {
+      panic!("expected an assignment");
+    };
+    assert!(matches!(a.value.as_ref(), Expr::Assign(_)));
+
+    assert!(parse("print a or b = c;").is_err());
+    assert!(parse("print a and b = c;").is_err());
+    assert!(parse("print a or (b = c);").is_ok());
+    Ok(())
+  }
+
+  #[test]
+  fn test_logical_precedence_and_associativity() -> Result<(), LoxError> {
+    let expr = parse_expression("a or b and c;")?;
+    let Expr::Logical(l) = expr.as_ref() else {
+      panic!("expected a logical expression");
+    };
+    assert!(l.operator.is_type(&TokenType::Or));
+    assert!(matches!(l.right.as_ref(), Expr::Logical(r) if r.operator.is_type(&TokenType::And)));
+
+    let expr = parse_expression("a or b or c;")?;
+    let Expr::Logical(l) = expr.as_ref() else {
+      panic!("expected a logical expression");
+    };
+    assert!(matches!(l.left.as_ref(), Expr::Logical(_)));
+    assert!(matches!(l.right.as_ref(), Expr::Variable(_)));
+
+    let expr = parse_expression("a and b and c;")?;
+    let Expr::Logical(l) = expr.as_ref() else {
+      panic!("expected a logical expression");
+    };
+    assert!(matches!(l.left.as_ref(), Expr::Logical(_)));
+    Ok(())
+  }
+
+  #[test]
+  fn test_term_is_left_associative() -> Result<(), LoxError> {
+    let expr = parse_expression("1 - 2 - 3;")?;
+    let Expr::Binary(b) = expr.as_ref() else {
+      panic!("expected a binary expression");
+    };
+    assert!(matches!(b.left.as_ref(), Expr::Binary(_)));
+    assert!(matches!(b.right.as_ref(), Expr::Literal(_)));
+    Ok(())
+  }
+
+  #[test]
+  fn test_else_binds_to_nearest_if() -> Result<(), LoxError> {
+    let statements = parse("if (a) if (b) x; else y;")?;
+
+    let [Stmt::If(outer)] = statements.as_slice() else {
+      panic!("expected a single if statement");
+    };
+    assert!(outer.else_branch.is_none());
+    assert!(matches!(outer.then_branch.as_ref(), Stmt::If(inner) if inner.else_branch.is_some()));
+
+    let statements = parse("if (a) x; else if (b) y; else z;")?;
+
+    let [Stmt::If(outer)] = statements.as_slice() else {
+      panic!("expected a single if statement");
+    };
+    assert!(
+      matches!(outer.else_branch.as_deref(), Some(Stmt::If(inner)) if inner.else_branch.is_some())
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn test_invalid_increment_target() {
+    assert!(parse("1++;").is_err());
+    assert!(parse("f()++;").is_err());
+    assert!(parse("a++;\nb.c--;").is_ok());
+  }
+
+  #[test]
+  fn test_coalesce_is_right_associative() -> Result<(), LoxError> {
+    let statements = parse("a ?? b ?? c or d;")?;
+    let printed = statements
+      .iter()
+      .map(Stmt::to_string)
+      .collect::<Vec<String>>();
+    assert_eq!(printed, vec!["(; (?? a (?? b (or c d))))"]);
+
+    Ok(())
+  }
+}
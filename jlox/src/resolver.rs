@@ -1,27 +1,111 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+  cell::RefCell,
+  collections::{HashMap, HashSet},
+  rc::Rc,
+};
 
-use crate::{error::*, expr::*, interpreter::*, stmt::*, token::*};
+use crate::{error::*, expr::*, stmt::*, token::*};
+
+// receives the scope distance of every local variable the resolver finds
+pub trait ResolveSink {
+  fn resolve(&self, expr: &Rc<Expr>, depth: usize);
+}
+
+// collects resolutions so a program can be analysed without an interpreter
+#[derive(Default)]
+pub struct LocalsCollector {
+  locals: RefCell<HashMap<Rc<Expr>, usize>>,
+}
+
+impl LocalsCollector {
+  // expressions are keyed by identity, so their interior mutability is harmless
+  #[allow(clippy::mutable_key_type)]
+  pub fn into_map(self) -> HashMap<Rc<Expr>, usize> {
+    self.locals.into_inner()
+  }
+}
+
+impl ResolveSink for LocalsCollector {
+  fn resolve(&self, expr: &Rc<Expr>, depth: usize) {
+    self.locals.borrow_mut().insert(expr.clone(), depth);
+  }
+}
 
 pub struct Resolver<'a> {
-  interpreter: &'a Interpreter,
+  sink: &'a dyn ResolveSink,
   scopes: RefCell<Vec<RefCell<HashMap<String, bool>>>>,
   current_class_type: RefCell<Option<ClassType>>,
   current_function_type: RefCell<Option<FunctionType>>,
+  loop_labels: RefCell<Vec<Option<Token>>>,
   had_error: RefCell<bool>,
+  warnings: RefCell<Vec<String>>,
 }
 
 impl<'a> Resolver<'a> {
-  pub fn new(interpreter: &Interpreter) -> Resolver {
+  pub fn new(sink: &dyn ResolveSink) -> Resolver<'_> {
     Resolver {
-      interpreter,
+      sink,
       scopes: RefCell::new(Vec::new()),
       current_class_type: RefCell::new(None),
       current_function_type: RefCell::new(None),
+      loop_labels: RefCell::new(Vec::new()),
       had_error: RefCell::new(false),
+      warnings: RefCell::new(Vec::new()),
     }
   }
 
+  pub fn get_warnings(&self) -> Vec<String> {
+    self.warnings.borrow().clone()
+  }
+
+  #[allow(clippy::mutable_key_type)]
+  pub fn resolve_to_map(
+    statements: &Rc<&[Rc<Stmt>]>,
+  ) -> Result<HashMap<Rc<Expr>, usize>, LoxError> {
+    let collector = LocalsCollector::default();
+    Resolver::new(&collector).resolve(statements)?;
+    Ok(collector.into_map())
+  }
+
+  // one `[line l:c] name -> depth` entry per resolved expression, in source
+  // order, for tracking down closure and scoping bugs
+  #[allow(clippy::mutable_key_type)]
+  pub fn describe_locals(locals: &HashMap<Rc<Expr>, usize>) -> Vec<String> {
+    let mut resolved = locals
+      .iter()
+      .filter_map(|(expr, &depth)| {
+        let token = match expr.as_ref() {
+          Expr::Assign(e) => &e.name,
+          Expr::Super(e) => &e.keyword,
+          Expr::This(e) => &e.keyword,
+          Expr::Variable(e) => &e.name,
+          _ => return None,
+        };
+        Some((
+          token.get_line(),
+          token.get_column(),
+          token.get_lexeme(),
+          depth,
+        ))
+      })
+      .collect::<Vec<_>>();
+    resolved.sort();
+
+    resolved
+      .into_iter()
+      .map(|(line, column, lexeme, depth)| format!("[line {line}:{column}] {lexeme} -> {depth}"))
+      .collect()
+  }
+
   pub fn resolve(&self, statements: &Rc<&[Rc<Stmt>]>) -> Result<(), LoxError> {
+    // functions are hoisted, so they are in scope for the whole block
+    for statement in statements.iter() {
+      if let Stmt::Function(f) = statement.as_ref() {
+        self.declare(&f.name);
+        self.define(&f.name);
+      }
+    }
+
     for statement in statements.iter() {
       if self.resolve_stmt(statement).is_err() {
         self.had_error.replace(true);
@@ -35,6 +119,55 @@ impl<'a> Resolver<'a> {
     Err(LoxError::new_parse_failure())
   }
 
+  // literal conditions are usually leftover debug code, but still valid
+  fn check_constant_condition(&self, keyword: &Token, condition: &Rc<Expr>) {
+    if let Expr::Literal(l) = condition.as_ref() {
+      if let Some(Literal::Boolean(b)) = l.value {
+        let warning = LoxError::warning(keyword, &format!("Condition is always {b}."));
+        self.warnings.borrow_mut().push(warning);
+      }
+    }
+  }
+
+  fn resolve_loop_body(&self, label: &Option<Token>, body: &Rc<Stmt>) -> Result<(), LoxError> {
+    self.loop_labels.borrow_mut().push(label.clone());
+    let res = self.resolve_stmt(body);
+    self.loop_labels.borrow_mut().pop();
+    res
+  }
+
+  // the parser only sees that a jump is lexically inside a loop, which it
+  // still is inside a function declared in that loop's body; the loops
+  // here start afresh with each function, so that case is caught
+  fn resolve_jump(&self, token: &Token, label: &Option<Token>) {
+    if self.loop_labels.borrow().is_empty() {
+      self.had_error.replace(true);
+      LoxError::parse_error(
+        token,
+        &format!("Can't use '{}' outside of a loop.", token.get_lexeme()),
+      );
+      return;
+    }
+
+    let Some(label) = label else {
+      return;
+    };
+
+    if !self
+      .loop_labels
+      .borrow()
+      .iter()
+      .flatten()
+      .any(|l| l.get_lexeme().eq(label.get_lexeme()))
+    {
+      self.had_error.replace(true);
+      LoxError::parse_error(
+        label,
+        &format!("No enclosing loop labelled '{}'.", label.get_lexeme()),
+      );
+    }
+  }
+
   fn resolve_expr(&self, expr: &Rc<Expr>) -> Result<(), LoxError> {
     expr.accept(expr, self)
   }
@@ -71,7 +204,7 @@ impl<'a> Resolver<'a> {
   fn resolve_local(&self, expr: &Rc<Expr>, name: &Token) {
     for (scope, map) in self.scopes.borrow().iter().rev().enumerate() {
       if map.borrow().contains_key(name.get_lexeme()) {
-        self.interpreter.resolve(expr, scope);
+        self.sink.resolve(expr, scope);
         return;
       }
     }
@@ -83,6 +216,7 @@ impl<'a> Resolver<'a> {
     function_type: Option<FunctionType>,
   ) -> Result<(), LoxError> {
     let enclosing_function_type = self.current_function_type.replace(function_type);
+    let enclosing_loop_labels = self.loop_labels.take();
     self.begin_scope();
 
     for param in &function.params {
@@ -90,14 +224,55 @@ impl<'a> Resolver<'a> {
       self.define(param);
     }
 
-    self.resolve(&function.body.as_slice().into())?;
+    // restored even after an error, so the rest is resolved in the right context
+    let res = self.resolve(&function.body.as_slice().into());
     self.end_scope();
+    self.loop_labels.replace(enclosing_loop_labels);
     self.current_function_type.replace(enclosing_function_type);
-    Ok(())
+    res
+  }
+
+  // a later method would silently replace the earlier one of the same name
+  fn check_duplicate_method<'b>(&self, seen: &mut HashSet<&'b str>, name: &'b Token) {
+    if !seen.insert(name.get_lexeme()) {
+      self.had_error.replace(true);
+      LoxError::parse_error(
+        name,
+        &format!("Duplicate method '{}' in class.", name.get_lexeme()),
+      );
+    }
+  }
+
+  // a field initialiser runs like the body of a method without parameters,
+  // so that `this` resolves to the same depth in both
+  fn resolve_field(&self, field: &VarStmt) -> Result<(), LoxError> {
+    let enclosing_function_type = self
+      .current_function_type
+      .replace(Some(FunctionType::Method));
+    let enclosing_loop_labels = self.loop_labels.take();
+    self.begin_scope();
+
+    let res = field
+      .initialiser
+      .as_ref()
+      .map_or(Ok(()), |i| self.resolve_expr(i));
+
+    self.end_scope();
+    self.loop_labels.replace(enclosing_loop_labels);
+    self.current_function_type.replace(enclosing_function_type);
+    res
   }
 }
 
 impl<'a> ExprVisitor<()> for Resolver<'a> {
+  fn visit_array_expr(&self, _wrapper: &Rc<Expr>, expr: &ArrayExpr) -> Result<(), LoxError> {
+    for element in expr.elements.iter() {
+      self.resolve_expr(element)?;
+    }
+
+    Ok(())
+  }
+
   fn visit_assign_expr(&self, wrapper: &Rc<Expr>, expr: &AssignExpr) -> Result<(), LoxError> {
     self.resolve_expr(&expr.value)?;
     self.resolve_local(wrapper, &expr.name);
@@ -120,6 +295,21 @@ impl<'a> ExprVisitor<()> for Resolver<'a> {
     Ok(())
   }
 
+  fn visit_destructure_expr(
+    &self,
+    _wrapper: &Rc<Expr>,
+    expr: &DestructureExpr,
+  ) -> Result<(), LoxError> {
+    self.resolve_expr(&expr.value)?;
+    for target in expr.targets.iter() {
+      if let Expr::Variable(v) = target.as_ref() {
+        self.resolve_local(target, &v.name);
+      }
+    }
+
+    Ok(())
+  }
+
   fn visit_get_expr(&self, _wrapper: &Rc<Expr>, expr: &GetExpr) -> Result<(), LoxError> {
     self.resolve_expr(&expr.object)
   }
@@ -128,6 +318,35 @@ impl<'a> ExprVisitor<()> for Resolver<'a> {
     self.resolve_expr(&expr.expression)
   }
 
+  fn visit_if_expr(&self, _wrapper: &Rc<Expr>, expr: &IfExpr) -> Result<(), LoxError> {
+    self.check_constant_condition(&expr.keyword, &expr.condition);
+    self.resolve_expr(&expr.condition)?;
+    self.resolve_expr(&expr.then_branch)?;
+    self.resolve_expr(&expr.else_branch)?;
+    Ok(())
+  }
+
+  fn visit_index_expr(&self, _wrapper: &Rc<Expr>, expr: &IndexExpr) -> Result<(), LoxError> {
+    self.resolve_expr(&expr.object)?;
+
+    if let Some(i) = &expr.index {
+      self.resolve_expr(i)?;
+    }
+
+    if let Some(e) = &expr.end {
+      self.resolve_expr(e)?;
+    }
+
+    Ok(())
+  }
+
+  fn visit_index_set_expr(&self, _wrapper: &Rc<Expr>, expr: &IndexSetExpr) -> Result<(), LoxError> {
+    self.resolve_expr(&expr.value)?;
+    self.resolve_expr(&expr.object)?;
+    self.resolve_expr(&expr.index)?;
+    Ok(())
+  }
+
   fn visit_literal_expr(&self, _wrapper: &Rc<Expr>, _expr: &LiteralExpr) -> Result<(), LoxError> {
     Ok(())
   }
@@ -138,6 +357,19 @@ impl<'a> ExprVisitor<()> for Resolver<'a> {
     Ok(())
   }
 
+  fn visit_map_expr(&self, _wrapper: &Rc<Expr>, expr: &MapExpr) -> Result<(), LoxError> {
+    for (key, value) in expr.keys.iter().zip(expr.values.iter()) {
+      self.resolve_expr(key)?;
+      self.resolve_expr(value)?;
+    }
+
+    Ok(())
+  }
+
+  fn visit_postfix_expr(&self, _wrapper: &Rc<Expr>, expr: &PostfixExpr) -> Result<(), LoxError> {
+    self.resolve_expr(&expr.target)
+  }
+
   fn visit_set_expr(&self, _wrapper: &Rc<Expr>, expr: &SetExpr) -> Result<(), LoxError> {
     self.resolve_expr(&expr.value)?;
     self.resolve_expr(&expr.object)?;
@@ -200,12 +432,18 @@ impl<'a> ExprVisitor<()> for Resolver<'a> {
 impl<'a> StmtVisitor<()> for Resolver<'a> {
   fn visit_block_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &BlockStmt) -> Result<(), LoxError> {
     self.begin_scope();
-    self.resolve(&stmt.statements.as_slice().into())?;
+    let res = self.resolve(&stmt.statements.as_slice().into());
     self.end_scope();
+    res
+  }
+
+  fn visit_break_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &BreakStmt) -> Result<(), LoxError> {
+    self.resolve_jump(&stmt.token, &stmt.label);
     Ok(())
   }
 
-  fn visit_break_stmt(&self, _wrapper: &Rc<Stmt>, _stmt: &BreakStmt) -> Result<(), LoxError> {
+  fn visit_continue_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &ContinueStmt) -> Result<(), LoxError> {
+    self.resolve_jump(&stmt.token, &stmt.label);
     Ok(())
   }
 
@@ -238,8 +476,16 @@ impl<'a> StmtVisitor<()> for Resolver<'a> {
       s.borrow_mut().insert("this".to_string(), true);
     }
 
+    for field in stmt.fields.iter() {
+      if let Stmt::Var(field) = field.as_ref() {
+        self.resolve_field(field)?;
+      }
+    }
+
+    let mut seen = HashSet::new();
     for method in stmt.methods.iter() {
       if let Stmt::Function(method) = method.as_ref() {
+        self.check_duplicate_method(&mut seen, &method.name);
         self.resolve_function(
           method,
           if method.name.get_lexeme().eq("init") {
@@ -258,6 +504,36 @@ impl<'a> StmtVisitor<()> for Resolver<'a> {
     }
 
     self.current_class_type.replace(enclosing_class);
+
+    // static methods have no instance, so they are resolved outside the class;
+    // they are looked up apart from instance methods, so may share their names
+    let mut seen = HashSet::new();
+    for method in stmt.static_methods.iter() {
+      if let Stmt::Function(method) = method.as_ref() {
+        self.check_duplicate_method(&mut seen, &method.name);
+        self.resolve_function(method, Some(FunctionType::Function))?;
+      }
+    }
+
+    Ok(())
+  }
+
+  // like `var`, none of the names can be read by the initialiser
+  fn visit_destructure_stmt(
+    &self,
+    _wrapper: &Rc<Stmt>,
+    stmt: &DestructureStmt,
+  ) -> Result<(), LoxError> {
+    for name in stmt.names.iter() {
+      self.declare(name);
+    }
+
+    self.resolve_expr(&stmt.initialiser)?;
+
+    for name in stmt.names.iter() {
+      self.define(name);
+    }
+
     Ok(())
   }
 
@@ -269,13 +545,24 @@ impl<'a> StmtVisitor<()> for Resolver<'a> {
     self.resolve_expr(&stmt.expression)
   }
 
-  fn visit_function_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &FunctionStmt) -> Result<(), LoxError> {
+  fn visit_foreach_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &ForeachStmt) -> Result<(), LoxError> {
+    self.resolve_expr(&stmt.iterable)?;
+
+    self.begin_scope();
     self.declare(&stmt.name);
     self.define(&stmt.name);
+    self.resolve_loop_body(&stmt.label, &stmt.body)?;
+    self.end_scope();
+    Ok(())
+  }
+
+  fn visit_function_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &FunctionStmt) -> Result<(), LoxError> {
+    // already declared when the enclosing block was hoisted
     self.resolve_function(stmt, Some(FunctionType::Function))
   }
 
   fn visit_if_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &IfStmt) -> Result<(), LoxError> {
+    self.check_constant_condition(&stmt.keyword, &stmt.condition);
     self.resolve_expr(&stmt.condition)?;
     self.resolve_stmt(&stmt.then_branch)?;
 
@@ -320,8 +607,17 @@ impl<'a> StmtVisitor<()> for Resolver<'a> {
   }
 
   fn visit_while_stmt(&self, _wrapper: &Rc<Stmt>, stmt: &WhileStmt) -> Result<(), LoxError> {
-    self.resolve_expr(&stmt.condition)?;
-    self.resolve_stmt(&stmt.body)?;
+    if let Some(c) = &stmt.condition {
+      self.check_constant_condition(&stmt.keyword, c);
+      self.resolve_expr(c)?;
+    }
+
+    self.resolve_loop_body(&stmt.label, &stmt.body)?;
+
+    if let Some(i) = &stmt.increment {
+      self.resolve_expr(i)?;
+    }
+
     Ok(())
   }
 }
@@ -364,3 +660,151 @@ impl CheckFunctionType for Option<FunctionType> {
     false
   }
 }
+
+#[cfg(test)]
+mod test {
+  use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+  };
+
+  use super::*;
+
+  use crate::{parser::*, scanner::*};
+
+  #[test]
+  fn test_describe_locals() -> Result<(), LoxError> {
+    let mut scanner = Scanner::new(
+      "fun outer() {\n  var a = 1;\n  fun inner() { a = a + 1; }\n}\nclass A { m() { this; } }",
+    );
+    let tokens = scanner.scan_tokens()?;
+    let statements = Parser::new(tokens)
+      .parse()?
+      .into_iter()
+      .map(Rc::new)
+      .collect::<Vec<Rc<Stmt>>>();
+
+    let locals =
+      Resolver::describe_locals(&Resolver::resolve_to_map(&statements.as_slice().into())?);
+    assert_eq!(
+      locals,
+      vec![
+        "[line 3:17] a -> 1",
+        "[line 3:21] a -> 1",
+        "[line 5:17] this -> 1",
+      ]
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_constant_condition_warning() -> Result<(), LoxError> {
+    let mut scanner = Scanner::new("if (true) print 1;\nwhile (false) {}\nif (1 > 2) print 2;");
+    let tokens = scanner.scan_tokens()?;
+    let statements = Parser::new(tokens)
+      .parse()?
+      .into_iter()
+      .map(Rc::new)
+      .collect::<Vec<Rc<Stmt>>>();
+
+    let collector = LocalsCollector::default();
+    let resolver = Resolver::new(&collector);
+    resolver.resolve(&statements.as_slice().into())?;
+    assert_eq!(
+      resolver.get_warnings(),
+      vec![
+        "[line 1] Warning at 'if': Condition is always true.",
+        "[line 2] Warning at 'while': Condition is always false.",
+      ]
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_resolve_to_map() -> Result<(), LoxError> {
+    let mut scanner = Scanner::new("var a = 1;\n{ var b = a; { print b; } }");
+    let tokens = scanner.scan_tokens()?;
+    let statements = Parser::new(tokens)
+      .parse()?
+      .into_iter()
+      .map(Rc::new)
+      .collect::<Vec<Rc<Stmt>>>();
+
+    let depths = Resolver::resolve_to_map(&statements.as_slice().into())?
+      .into_values()
+      .collect::<Vec<usize>>();
+
+    // `a` is a global, so only the use of `b` is resolved, one scope out
+    assert_eq!(depths, vec![1]);
+
+    Ok(())
+  }
+
+  fn resolve(source: &str) -> Result<(), LoxError> {
+    let mut scanner = Scanner::new(source);
+    let tokens = scanner.scan_tokens()?;
+    let statements = Parser::new(tokens)
+      .parse()?
+      .into_iter()
+      .map(Rc::new)
+      .collect::<Vec<Rc<Stmt>>>();
+    Resolver::resolve_to_map(&statements.as_slice().into()).map(|_| ())
+  }
+
+  #[test]
+  fn test_duplicate_methods() -> Result<(), LoxError> {
+    assert!(resolve("class A { foo() {} foo() {} }").is_err());
+    assert!(resolve("class A { class foo() {} class foo() {} }").is_err());
+    assert!(resolve("class A { foo() {} bar() {} class foo() {} }").is_ok());
+    // overriding an inherited method is not a duplicate
+    assert!(resolve("class A { foo() {} }\nclass B < A { foo() {} }").is_ok());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_duplicate_parameters() {
+    // parameters share the function's scope, so `declare` already catches these
+    assert!(resolve("fun f(a, a) {}").is_err());
+    assert!(resolve("class A { m(a, b, a) {} }").is_err());
+    assert!(resolve("fun f(a, ...a) {}").is_err());
+    assert!(resolve("fun f(a) { var a = 1; }").is_err());
+    assert!(resolve("fun f(a, b) {}").is_ok());
+  }
+
+  #[test]
+  fn test_jump_in_function_inside_loop() {
+    assert!(resolve("while (true) { fun f() { break; } break; }").is_err());
+    assert!(resolve("for (;;) { fun f() { continue; } break; }").is_err());
+    assert!(resolve("while (true) { class A { m() { break; } } break; }").is_err());
+    assert!(resolve("fun f() { while (true) { break; } }").is_ok());
+    assert!(resolve("while (true) { fun f() { while (true) continue; } break; }").is_ok());
+  }
+
+  #[test]
+  fn test_expressions_hash_by_identity() {
+    // structurally identical, so only their addresses can tell them apart
+    let exprs = (0..1000)
+      .map(|_| {
+        Rc::new(Expr::Variable(
+          VariableExpr {
+            name: Token::new(TokenType::Identifier, "a", None, 1),
+          }
+          .into(),
+        ))
+      })
+      .collect::<Vec<Rc<Expr>>>();
+
+    let hashes = exprs
+      .iter()
+      .map(|e| {
+        let mut hasher = DefaultHasher::new();
+        e.hash(&mut hasher);
+        hasher.finish()
+      })
+      .collect::<HashSet<u64>>();
+    assert_eq!(hashes.len(), exprs.len());
+  }
+}
@@ -6,6 +6,12 @@ pub struct Scanner {
   start: usize,
   current: usize,
   line: usize,
+  line_start: usize,
+  tab_width: usize,
+  keep_trivia: bool,
+  trivia: Vec<Token>,
+  errors: Vec<LoxError>,
+  report_errors: bool,
 }
 
 impl Scanner {
@@ -16,20 +22,90 @@ impl Scanner {
       start: 0,
       current: 0,
       line: 1,
+      line_start: 0,
+      tab_width: 1,
+      keep_trivia: false,
+      trivia: Vec::new(),
+      errors: Vec::new(),
+      report_errors: true,
     }
   }
 
+  // how many columns a tab counts for, so columns match the editor's
+  pub fn with_tab_width(mut self, tab_width: usize) -> Scanner {
+    self.tab_width = tab_width;
+    self
+  }
+
+  // comments are kept apart from the tokens, so a formatter can put them back
+  // without the parser ever seeing them
+  pub fn with_trivia(mut self, keep_trivia: bool) -> Scanner {
+    self.keep_trivia = keep_trivia;
+    self
+  }
+
+  // without reporting, errors are only collected, for a caller that merely
+  // looks at the tokens
+  pub fn with_error_reporting(mut self, report_errors: bool) -> Scanner {
+    self.report_errors = report_errors;
+    self
+  }
+
+  // every error is reported as it is found, but only the first is returned
   pub fn scan_tokens(&mut self) -> Result<&Vec<Token>, LoxError> {
+    self.skip_shebang();
+
     while !self.is_at_end() {
       self.start = self.current;
-      self.scan_token()?;
+      if let Err(e) = self.scan_token() {
+        self.errors.push(e);
+      }
     }
 
-    self.tokens.push(Token::new_eof(self.line));
+    self
+      .tokens
+      .push(Token::new_eof(self.line).with_offset(self.source.len()));
+
+    if let Some(e) = self.errors.first() {
+      return Err(e.clone());
+    }
 
     Ok(&self.tokens)
   }
 
+  // everything scanned so far, including tokens after an error
+  pub fn get_tokens(&self) -> &[Token] {
+    &self.tokens
+  }
+
+  pub fn get_trivia(&self) -> &[Token] {
+    &self.trivia
+  }
+
+  pub fn get_errors(&self) -> &[LoxError] {
+    &self.errors
+  }
+
+  fn error(&self, message: &str) -> LoxError {
+    if self.report_errors {
+      return LoxError::general_error(self.line, message);
+    }
+
+    LoxError::new_general_error(self.line, message)
+  }
+
+  // `#!/usr/bin/env jlox` on the very first line; its newline is left to be
+  // scanned as usual, which keeps the line count right
+  fn skip_shebang(&mut self) {
+    if self.current != 0 || !self.source.starts_with(&['#', '!']) {
+      return;
+    }
+
+    while self.peek().is_some_and(|&c| c != '\n') {
+      self.current += 1;
+    }
+  }
+
   fn is_at_end(&self) -> bool {
     self.current >= self.source.len()
   }
@@ -41,10 +117,41 @@ impl Scanner {
       ')' => self.add_token(TokenType::RightBracket),
       '{' => self.add_token(TokenType::LeftBrace),
       '}' => self.add_token(TokenType::RightBrace),
+      '[' => self.add_token(TokenType::LeftSquareBracket),
+      ']' => self.add_token(TokenType::RightSquareBracket),
+      ':' => self.add_token(TokenType::Colon),
       ',' => self.add_token(TokenType::Comma),
-      '.' => self.add_token(TokenType::Dot),
-      '+' => self.add_token(TokenType::Plus),
-      '-' => self.add_token(TokenType::Minus),
+      '.' if self.peek().is_some_and(char::is_ascii_digit) && !self.follows_operand() => {
+        self.number()?;
+      }
+      '.' => {
+        let token = if self.peek().eq(&Some(&'.')) && self.peek_next().eq(&Some(&'.')) {
+          self.advance();
+          self.advance();
+          TokenType::Ellipsis
+        } else {
+          TokenType::Dot
+        };
+        self.add_token(token);
+      }
+      '+' => {
+        let token = if self.is_postfix('+') {
+          self.advance();
+          TokenType::PlusPlus
+        } else {
+          TokenType::Plus
+        };
+        self.add_token(token);
+      }
+      '-' => {
+        let token = if self.is_postfix('-') {
+          self.advance();
+          TokenType::MinusMinus
+        } else {
+          TokenType::Minus
+        };
+        self.add_token(token);
+      }
       '*' => self.add_token(TokenType::Star),
       ';' => self.add_token(TokenType::Semicolon),
       '!' => {
@@ -79,7 +186,12 @@ impl Scanner {
         };
         self.add_token(token);
       }
+      // `//` already starts a comment, so integer division is spelled `~/`
+      '~' if self.is_match('/') => self.add_token(TokenType::TildeSlash),
+      '?' if self.is_match('?') => self.add_token(TokenType::QuestionQuestion),
       '/' => {
+        // a block comment can end lines later, so note where it starts
+        let (line, column) = (self.line, self.column());
         if self.is_match('/') {
           while let Some(&c) = self.peek() {
             if c.ne(&'\n') {
@@ -88,18 +200,20 @@ impl Scanner {
               break;
             }
           }
+          self.add_trivia(line, column);
         } else if self.is_match('*') {
           self.comment()?;
+          self.add_trivia(line, column);
         } else {
           self.add_token(TokenType::Slash);
         }
       }
       ' ' | '\r' | '\t' => (),
-      '\n' => self.line += 1,
+      '\n' => self.new_line(),
       '"' => self.string()?,
       '0'..='9' => self.number()?,
       _ if c.is_valid_for_lox_identifier() => self.identifier(),
-      _ => return Err(LoxError::general_error(self.line, "Unexpected character.")),
+      _ => return Err(self.error("Unexpected character.")),
     }
 
     Ok(())
@@ -122,12 +236,39 @@ impl Scanner {
     );
   }
 
+  // a dot after one of these is property access, so `a.5` is not `a` followed by `0.5`
+  fn follows_operand(&self) -> bool {
+    self.tokens.last().is_some_and(|t| {
+      t.is_types(&[
+        &TokenType::Identifier,
+        &TokenType::RightBracket,
+        &TokenType::RightSquareBracket,
+        &TokenType::This,
+      ])
+    })
+  }
+
+  // a doubled `+` or `-` is an increment only straight after an operand and
+  // with no operand right behind it, so `a--b` and `--a` still scan as minuses
+  fn is_postfix(&self, operator: char) -> bool {
+    self.peek().eq(&Some(&operator))
+      && self.follows_operand()
+      && !self
+        .peek_next()
+        .is_some_and(|&c| c.is_alphanumeric() || ['_', '(', '"', '.'].contains(&c))
+  }
+
+  // a trailing dot is not part of the number, so `5.` scans as `5` followed by `.`
   fn number(&mut self) -> Result<(), LoxError> {
     while self.peek().is_some_and(char::is_ascii_digit) {
       self.advance();
     }
 
-    if self.peek().eq(&Some(&'.')) && self.peek_next().is_some_and(char::is_ascii_digit) {
+    let has_fraction = self.source.get(self.start).eq(&Some(&'.'));
+    if !has_fraction
+      && self.peek().eq(&Some(&'.'))
+      && self.peek_next().is_some_and(char::is_ascii_digit)
+    {
       self.advance();
 
       while self.peek().is_some_and(char::is_ascii_digit) {
@@ -143,7 +284,7 @@ impl Scanner {
           .get(self.start..self.current)
           .map_or_else(String::new, |s| s.iter().collect())
           .parse::<f64>()
-          .map_err(|_| LoxError::general_error(self.line, "COULD NOT PARSE NUMBER."))?,
+          .map_err(|_| self.error("COULD NOT PARSE NUMBER."))?,
       )),
     );
 
@@ -151,30 +292,98 @@ impl Scanner {
   }
 
   fn string(&mut self) -> Result<(), LoxError> {
+    let mut value = String::new();
+
     while let Some(&c) = self.peek() {
-      match c {
-        '\n' => self.line += 1,
-        '"' => break,
-        _ => (),
+      if c.eq(&'"') {
+        break;
       }
+
       self.advance();
+      match c {
+        // keep going so the rest of the string is not scanned as code
+        // a backslash before a real newline continues the string on the next line
+        '\\' if self.is_match('\n') => self.new_line(),
+        '\\' => match self.escape() {
+          Ok(c) => value.push(c),
+          Err(e) => self.errors.push(e),
+        },
+        '\n' => {
+          self.new_line();
+          value.push(c);
+        }
+        _ => value.push(c),
+      }
     }
 
     if self.is_at_end() {
-      return Err(LoxError::general_error(self.line, "Unterminated string."));
+      return Err(self.error("Unterminated string."));
     }
 
     self.advance();
-
-    let value = self
-      .source
-      .get(self.start + 1..self.current - 1)
-      .map_or_else(String::new, |s| s.iter().collect());
     self.add_token_and_literal(TokenType::String, Some(Literal::String(value)));
 
     Ok(())
   }
 
+  fn escape(&mut self) -> Result<char, LoxError> {
+    if self.is_at_end() {
+      return Err(self.error("Unterminated string."));
+    }
+
+    match self.advance() {
+      'n' => Ok('\n'),
+      't' => Ok('\t'),
+      'r' => Ok('\r'),
+      '\\' => Ok('\\'),
+      '"' => Ok('"'),
+      'u' => self.unicode_escape(),
+      'x' => self.hex_escape(),
+      c => Err(self.error(&format!("Invalid escape sequence '\\{c}'."))),
+    }
+  }
+
+  // exactly two hex digits, so `"\x41BC"` is `"ABC"`
+  fn hex_escape(&mut self) -> Result<char, LoxError> {
+    let mut digits = String::new();
+    while digits.len().lt(&2) && self.peek().is_some_and(char::is_ascii_hexdigit) {
+      digits.push(self.advance());
+    }
+
+    if digits.len().ne(&2) {
+      return Err(self.error("Expect two hex digits after '\\x'."));
+    }
+
+    u8::from_str_radix(&digits, 16)
+      .map(char::from)
+      .map_err(|_| self.error("Invalid hex escape."))
+  }
+
+  fn unicode_escape(&mut self) -> Result<char, LoxError> {
+    if !self.is_match('{') {
+      return Err(self.error("Expect '{' after '\\u'."));
+    }
+
+    let mut digits = String::new();
+    while let Some(&c) = self.peek() {
+      if !c.is_ascii_hexdigit() {
+        break;
+      }
+
+      self.advance();
+      digits.push(c);
+    }
+
+    if !self.is_match('}') {
+      return Err(self.error("Expect '}' after unicode escape."));
+    }
+
+    u32::from_str_radix(&digits, 16)
+      .ok()
+      .and_then(char::from_u32)
+      .ok_or_else(|| self.error(&format!("Invalid unicode escape '\\u{{{digits}}}'.")))
+  }
+
   fn comment(&mut self) -> Result<(), LoxError> {
     // consume the '*'
     self.advance();
@@ -202,13 +411,10 @@ impl Scanner {
         }
         Some('\n') => {
           self.advance();
-          self.line += 1;
+          self.new_line();
         }
         None => {
-          return Err(LoxError::general_error(
-            self.line,
-            "Unterminated block comment.",
-          ));
+          return Err(self.error("Unterminated block comment."));
         }
         _ => {
           self.advance();
@@ -219,6 +425,12 @@ impl Scanner {
     Ok(())
   }
 
+  // must be called after consuming the '\n'
+  fn new_line(&mut self) {
+    self.line += 1;
+    self.line_start = self.current;
+  }
+
   fn is_match(&mut self, expected: char) -> bool {
     let res = matches!(self.source.get(self.current), Some(&c) if c.eq(&expected));
     if res {
@@ -249,13 +461,38 @@ impl Scanner {
   }
 
   fn add_token_and_literal(&mut self, token_type: TokenType, literal: Option<Literal>) {
-    let text = self
+    let text = self.lexeme();
+    let column = self.column();
+    self.tokens.push(
+      Token::new_with_column(token_type, &text, literal, self.line, column).with_offset(self.start),
+    );
+  }
+
+  fn add_trivia(&mut self, line: usize, column: usize) {
+    if !self.keep_trivia {
+      return;
+    }
+
+    let text = self.lexeme();
+    self.trivia.push(
+      Token::new_with_column(TokenType::Comment, &text, None, line, column).with_offset(self.start),
+    );
+  }
+
+  fn lexeme(&self) -> String {
+    self
       .source
       .get(self.start..self.current)
-      .map_or_else(String::new, |s| s.iter().collect());
-    self
-      .tokens
-      .push(Token::new(token_type, &text, literal, self.line));
+      .map_or_else(String::new, |s| s.iter().collect())
+  }
+
+  fn column(&self) -> usize {
+    // tokens spanning several lines (multi-line strings) start before the current line
+    self.source.get(self.line_start..self.start).map_or(0, |s| {
+      s.iter()
+        .map(|&c| if c == '\t' { self.tab_width } else { 1 })
+        .sum()
+    }) + 1
   }
 }
 
@@ -275,26 +512,10 @@ trait MatchIdentifier {
 
 impl MatchIdentifier for str {
   fn match_lox_keyword(&self) -> TokenType {
-    match self {
-      "and" => TokenType::And,
-      "class" => TokenType::Class,
-      "else" => TokenType::Else,
-      "false" => TokenType::False,
-      "for" => TokenType::For,
-      "fun" => TokenType::Fun,
-      "if" => TokenType::If,
-      "nil" => TokenType::Nil,
-      "or" => TokenType::Or,
-      "print" => TokenType::Print,
-      "return" => TokenType::Return,
-      "super" => TokenType::Super,
-      "this" => TokenType::This,
-      "true" => TokenType::True,
-      "var" => TokenType::Var,
-      "while" => TokenType::While,
-      "break" => TokenType::Break,
-      _ => TokenType::Identifier,
-    }
+    KEYWORDS
+      .iter()
+      .find(|(keyword, _)| keyword.eq(&self))
+      .map_or(TokenType::Identifier, |(_, t)| t.clone())
   }
 }
 
@@ -314,4 +535,277 @@ mod test {
 
     Ok(())
   }
+
+  #[test]
+  fn test_tab_width() -> Result<(), LoxError> {
+    let source = "\t\tprint a;\n\t a;";
+    let columns = |scanner: &mut Scanner| -> Result<Vec<usize>, LoxError> {
+      Ok(
+        scanner
+          .scan_tokens()?
+          .iter()
+          .map(|t| t.get_column())
+          .collect(),
+      )
+    };
+
+    assert_eq!(columns(&mut Scanner::new(source))?, vec![3, 9, 10, 3, 4, 0]);
+    assert_eq!(
+      columns(&mut Scanner::new(source).with_tab_width(4))?,
+      vec![9, 15, 16, 6, 7, 0]
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_trivia() -> Result<(), LoxError> {
+    let source = "// leading\nvar a = 1; // trailing\n/* a\n   block */ print a;";
+    let mut scanner = Scanner::new(source).with_trivia(true);
+    let tokens = scanner.scan_tokens()?.clone();
+
+    let comments = scanner
+      .get_trivia()
+      .iter()
+      .map(|t| {
+        assert!(t.is_type(&TokenType::Comment));
+        (t.get_lexeme().as_str(), t.get_line(), t.get_column())
+      })
+      .collect::<Vec<(&str, usize, usize)>>();
+    assert_eq!(
+      comments,
+      vec![
+        ("// leading", 1, 1),
+        ("// trailing", 2, 12),
+        ("/* a\n   block */", 3, 1),
+      ]
+    );
+
+    // the parser gets exactly the tokens it would without trivia
+    let mut plain = Scanner::new(source);
+    assert_eq!(
+      tokens
+        .iter()
+        .map(|t| t.to_string())
+        .collect::<Vec<String>>(),
+      plain
+        .scan_tokens()?
+        .iter()
+        .map(|t| t.to_string())
+        .collect::<Vec<String>>()
+    );
+    assert!(plain.get_trivia().is_empty());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_shebang() -> Result<(), LoxError> {
+    let mut scanner = Scanner::new("#!/usr/bin/env jlox\nprint 1;");
+    let tokens = scanner.scan_tokens()?;
+    assert!(tokens[0].is_type(&TokenType::Print) && tokens[0].get_line() == 2);
+    assert_eq!(tokens.len(), 4);
+
+    assert!(Scanner::new("#!/usr/bin/env jlox")
+      .scan_tokens()
+      .is_ok_and(|t| t.len() == 1));
+    // only the very first line may be a shebang
+    assert!(Scanner::new(" #!/usr/bin/env jlox").scan_tokens().is_err());
+    assert!(Scanner::new("print 1;\n#!/usr/bin/env jlox")
+      .scan_tokens()
+      .is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_token_columns() -> Result<(), LoxError> {
+    let source = "var a = 1;\n  print a;";
+    let mut scanner = Scanner::new(source);
+    let tokens = scanner.scan_tokens()?;
+
+    let positions = tokens
+      .iter()
+      .map(|t| (t.get_line(), t.get_column()))
+      .collect::<Vec<(usize, usize)>>();
+    assert_eq!(
+      positions,
+      vec![
+        (1, 1),
+        (1, 5),
+        (1, 7),
+        (1, 9),
+        (1, 10),
+        (2, 3),
+        (2, 9),
+        (2, 10),
+        (2, 0)
+      ]
+    );
+
+    Ok(())
+  }
+
+  fn scan_string(source: &str) -> Result<Literal, LoxError> {
+    let mut scanner = Scanner::new(source);
+    let tokens = scanner.scan_tokens()?;
+
+    tokens
+      .first()
+      .and_then(|t| t.get_literal().clone())
+      .ok_or_else(|| LoxError::general_error(0, "Expect a string literal."))
+  }
+
+  #[test]
+  fn test_string_escapes() -> Result<(), LoxError> {
+    assert!(
+      matches!(scan_string(r#""a\tb\n\"c\"\\""#)?, Literal::String(s) if s == "a\tb\n\"c\"\\")
+    );
+    assert!(matches!(scan_string(r#""\u{41}\u{62}""#)?, Literal::String(s) if s == "Ab"));
+    assert!(matches!(scan_string(r#""\u{1F600}""#)?, Literal::String(s) if s == "\u{1F600}"));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_invalid_unicode_escapes() {
+    assert!(scan_string(r#""\u{}""#).is_err());
+    assert!(scan_string(r#""\u41""#).is_err());
+    assert!(scan_string(r#""\u{41""#).is_err());
+    assert!(scan_string(r#""\u{D800}""#).is_err());
+    assert!(scan_string(r#""\u{110000}""#).is_err());
+    assert!(scan_string(r#""\q""#).is_err());
+  }
+
+  #[test]
+  fn test_collects_errors() {
+    let mut scanner = Scanner::new("var a = @;\nvar b = \"\\q\";\nvar c = #;");
+    assert!(scanner.scan_tokens().is_err());
+    assert_eq!(scanner.get_errors().len(), 3);
+
+    let lexemes = scanner
+      .tokens
+      .iter()
+      .map(|t| t.get_lexeme().as_str())
+      .collect::<Vec<&str>>();
+    assert_eq!(
+      lexemes,
+      vec!["var", "a", "=", ";", "var", "b", "=", "\"\\q\"", ";", "var", "c", "=", ";", ""]
+    );
+  }
+
+  #[test]
+  fn test_collects_errors_without_reporting() {
+    let mut scanner = Scanner::new("var a = @;\nvar b = \"a").with_error_reporting(false);
+    assert!(scanner
+      .scan_tokens()
+      .is_err_and(|e| e.get_message() == Some("Unexpected character.")));
+
+    let messages = scanner
+      .get_errors()
+      .iter()
+      .map(|e| e.get_message())
+      .collect::<Vec<Option<&str>>>();
+    assert_eq!(
+      messages,
+      vec![Some("Unexpected character."), Some("Unterminated string.")]
+    );
+  }
+
+  fn scan_types(source: &str) -> Result<Vec<TokenType>, LoxError> {
+    let mut scanner = Scanner::new(source);
+    let tokens = scanner.scan_tokens()?;
+
+    Ok(tokens.iter().map(|t| t.get_type().clone()).collect())
+  }
+
+  #[test]
+  fn test_leading_dot_number() -> Result<(), LoxError> {
+    assert!(matches!(scan_string(".5")?, Literal::Number(n) if n == 0.5));
+    assert_eq!(
+      scan_types(".5.5")?,
+      vec![TokenType::Number, TokenType::Number, TokenType::Eof]
+    );
+    assert_eq!(
+      scan_types("a.5")?,
+      vec![
+        TokenType::Identifier,
+        TokenType::Dot,
+        TokenType::Number,
+        TokenType::Eof
+      ]
+    );
+    assert_eq!(
+      scan_types("f().5")?,
+      vec![
+        TokenType::Identifier,
+        TokenType::LeftBracket,
+        TokenType::RightBracket,
+        TokenType::Dot,
+        TokenType::Number,
+        TokenType::Eof
+      ]
+    );
+    assert_eq!(
+      scan_types("- .5")?,
+      vec![TokenType::Minus, TokenType::Number, TokenType::Eof]
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_increment_only_after_operand() -> Result<(), LoxError> {
+    use TokenType::*;
+
+    assert_eq!(
+      scan_types("a++;")?,
+      vec![Identifier, PlusPlus, Semicolon, Eof]
+    );
+    assert_eq!(scan_types("a[0]--")?[4..], [MinusMinus, Eof]);
+    assert_eq!(
+      scan_types("a--b")?,
+      vec![Identifier, Minus, Minus, Identifier, Eof]
+    );
+    assert_eq!(scan_types("--a")?, vec![Minus, Minus, Identifier, Eof]);
+    assert_eq!(
+      scan_types("1 - -1")?,
+      vec![Number, Minus, Minus, Number, Eof]
+    );
+    assert_eq!(scan_types("1--1")?, vec![Number, Minus, Minus, Number, Eof]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_trailing_dot_is_not_part_of_number() -> Result<(), LoxError> {
+    assert_eq!(
+      scan_types("5.")?,
+      vec![TokenType::Number, TokenType::Dot, TokenType::Eof]
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_hex_escapes() -> Result<(), LoxError> {
+    assert!(matches!(scan_string(r#""\x41\x62C""#)?, Literal::String(s) if s == "AbC"));
+    assert!(scan_string(r#""\x4""#).is_err());
+    assert!(scan_string(r#""\xg1""#).is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_multiline_string_line_numbers() {
+    // one real newline, one escaped newline and one continued line inside the literal
+    let source = "var s = \"a\\nb\n c\\\n d\";\nvar t = @;";
+    let mut scanner = Scanner::new(source);
+    let res = scanner.scan_tokens();
+
+    assert!(res.is_err_and(|e| e.get_line() == Some(4)));
+    assert!(scanner.tokens.iter().any(|t| {
+      matches!(t.get_literal(), Some(Literal::String(s)) if s == "a\nb\n c d") && t.get_line() == 3
+    }));
+  }
 }
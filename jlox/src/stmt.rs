@@ -7,12 +7,15 @@ use crate::error::*;
 use crate::expr::*;
 use crate::token::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Stmt {
   Block(Rc<BlockStmt>),
   Break(Rc<BreakStmt>),
+  Continue(Rc<ContinueStmt>),
   Class(Rc<ClassStmt>),
+  Destructure(Rc<DestructureStmt>),
   Expression(Rc<ExpressionStmt>),
+  Foreach(Rc<ForeachStmt>),
   Function(Rc<FunctionStmt>),
   If(Rc<IfStmt>),
   Print(Rc<PrintStmt>),
@@ -26,8 +29,11 @@ impl Stmt {
     match self {
       Stmt::Block(stmt) => stmt_visitor.visit_block_stmt(wrapper, stmt),
       Stmt::Break(stmt) => stmt_visitor.visit_break_stmt(wrapper, stmt),
+      Stmt::Continue(stmt) => stmt_visitor.visit_continue_stmt(wrapper, stmt),
       Stmt::Class(stmt) => stmt_visitor.visit_class_stmt(wrapper, stmt),
+      Stmt::Destructure(stmt) => stmt_visitor.visit_destructure_stmt(wrapper, stmt),
       Stmt::Expression(stmt) => stmt_visitor.visit_expression_stmt(wrapper, stmt),
+      Stmt::Foreach(stmt) => stmt_visitor.visit_foreach_stmt(wrapper, stmt),
       Stmt::Function(stmt) => stmt_visitor.visit_function_stmt(wrapper, stmt),
       Stmt::If(stmt) => stmt_visitor.visit_if_stmt(wrapper, stmt),
       Stmt::Print(stmt) => stmt_visitor.visit_print_stmt(wrapper, stmt),
@@ -43,8 +49,11 @@ impl PartialEq for Stmt {
     match (self, other) {
       (Stmt::Block(l0), Stmt::Block(r0)) => Rc::ptr_eq(l0, r0),
       (Stmt::Break(l0), Stmt::Break(r0)) => Rc::ptr_eq(l0, r0),
+      (Stmt::Continue(l0), Stmt::Continue(r0)) => Rc::ptr_eq(l0, r0),
       (Stmt::Class(l0), Stmt::Class(r0)) => Rc::ptr_eq(l0, r0),
+      (Stmt::Destructure(l0), Stmt::Destructure(r0)) => Rc::ptr_eq(l0, r0),
       (Stmt::Expression(l0), Stmt::Expression(r0)) => Rc::ptr_eq(l0, r0),
+      (Stmt::Foreach(l0), Stmt::Foreach(r0)) => Rc::ptr_eq(l0, r0),
       (Stmt::Function(l0), Stmt::Function(r0)) => Rc::ptr_eq(l0, r0),
       (Stmt::If(l0), Stmt::If(r0)) => Rc::ptr_eq(l0, r0),
       (Stmt::Print(l0), Stmt::Print(r0)) => Rc::ptr_eq(l0, r0),
@@ -61,6 +70,21 @@ impl Eq for Stmt {}
 impl Hash for Stmt {
   fn hash<H: Hasher>(&self, state: &mut H) {
     discriminant(self).hash(state);
+    match self {
+      Stmt::Block(n0) => Rc::as_ptr(n0).hash(state),
+      Stmt::Break(n0) => Rc::as_ptr(n0).hash(state),
+      Stmt::Continue(n0) => Rc::as_ptr(n0).hash(state),
+      Stmt::Class(n0) => Rc::as_ptr(n0).hash(state),
+      Stmt::Destructure(n0) => Rc::as_ptr(n0).hash(state),
+      Stmt::Expression(n0) => Rc::as_ptr(n0).hash(state),
+      Stmt::Foreach(n0) => Rc::as_ptr(n0).hash(state),
+      Stmt::Function(n0) => Rc::as_ptr(n0).hash(state),
+      Stmt::If(n0) => Rc::as_ptr(n0).hash(state),
+      Stmt::Print(n0) => Rc::as_ptr(n0).hash(state),
+      Stmt::Return(n0) => Rc::as_ptr(n0).hash(state),
+      Stmt::Var(n0) => Rc::as_ptr(n0).hash(state),
+      Stmt::While(n0) => Rc::as_ptr(n0).hash(state),
+    }
   }
 }
 
@@ -72,13 +96,29 @@ pub struct BlockStmt {
 #[derive(Debug)]
 pub struct BreakStmt {
   pub token: Token,
+  pub label: Option<Token>,
+}
+
+#[derive(Debug)]
+pub struct ContinueStmt {
+  pub token: Token,
+  pub label: Option<Token>,
 }
 
 #[derive(Debug)]
 pub struct ClassStmt {
   pub name: Token,
   pub superclass: Option<Rc<Expr>>,
+  pub fields: Rc<Vec<Rc<Stmt>>>,
   pub methods: Rc<Vec<Rc<Stmt>>>,
+  pub static_methods: Rc<Vec<Rc<Stmt>>>,
+}
+
+#[derive(Debug)]
+pub struct DestructureStmt {
+  pub bracket: Token,
+  pub names: Vec<Token>,
+  pub initialiser: Rc<Expr>,
 }
 
 #[derive(Debug)]
@@ -86,15 +126,25 @@ pub struct ExpressionStmt {
   pub expression: Rc<Expr>,
 }
 
+#[derive(Debug)]
+pub struct ForeachStmt {
+  pub label: Option<Token>,
+  pub name: Token,
+  pub iterable: Rc<Expr>,
+  pub body: Rc<Stmt>,
+}
+
 #[derive(Debug)]
 pub struct FunctionStmt {
   pub name: Token,
   pub params: Vec<Token>,
+  pub is_variadic: bool,
   pub body: Rc<Vec<Rc<Stmt>>>,
 }
 
 #[derive(Debug)]
 pub struct IfStmt {
+  pub keyword: Token,
   pub condition: Rc<Expr>,
   pub then_branch: Rc<Stmt>,
   pub else_branch: Option<Rc<Stmt>>,
@@ -119,15 +169,22 @@ pub struct VarStmt {
 
 #[derive(Debug)]
 pub struct WhileStmt {
-  pub condition: Rc<Expr>,
+  pub label: Option<Token>,
+  pub keyword: Token,
+  pub condition: Option<Rc<Expr>>,
   pub body: Rc<Stmt>,
+  pub increment: Option<Rc<Expr>>,
+  pub per_iteration: bool,
 }
 
 pub trait StmtVisitor<T> {
   fn visit_block_stmt(&self, wrapper: &Rc<Stmt>, stmt: &BlockStmt) -> Result<T, LoxError>;
   fn visit_break_stmt(&self, wrapper: &Rc<Stmt>, stmt: &BreakStmt) -> Result<T, LoxError>;
+  fn visit_continue_stmt(&self, wrapper: &Rc<Stmt>, stmt: &ContinueStmt) -> Result<T, LoxError>;
   fn visit_class_stmt(&self, wrapper: &Rc<Stmt>, stmt: &ClassStmt) -> Result<T, LoxError>;
+  fn visit_destructure_stmt(&self, wrapper: &Rc<Stmt>, stmt: &DestructureStmt) -> Result<T, LoxError>;
   fn visit_expression_stmt(&self, wrapper: &Rc<Stmt>, stmt: &ExpressionStmt) -> Result<T, LoxError>;
+  fn visit_foreach_stmt(&self, wrapper: &Rc<Stmt>, stmt: &ForeachStmt) -> Result<T, LoxError>;
   fn visit_function_stmt(&self, wrapper: &Rc<Stmt>, stmt: &FunctionStmt) -> Result<T, LoxError>;
   fn visit_if_stmt(&self, wrapper: &Rc<Stmt>, stmt: &IfStmt) -> Result<T, LoxError>;
   fn visit_print_stmt(&self, wrapper: &Rc<Stmt>, stmt: &PrintStmt) -> Result<T, LoxError>;
@@ -1,4 +1,10 @@
-use std::{fmt, rc::Rc};
+use std::{
+  cell::RefCell,
+  collections::{HashMap, HashSet},
+  fmt,
+  hash::{Hash, Hasher},
+  rc::Rc,
+};
 
 use crate::{error::*, lox_class::*, lox_function::*, lox_instance::*, lox_native_function::*};
 
@@ -8,24 +14,49 @@ pub struct Token {
   lexeme: String,
   literal: Option<Literal>,
   line: usize,
+  column: usize,
+  offset: usize,
 }
 
+// a half-open range of char offsets into the source
+pub type Span = (usize, usize);
+
 impl Token {
   pub fn new(token_type: TokenType, lexeme: &str, literal: Option<Literal>, line: usize) -> Token {
+    Token::new_with_column(token_type, lexeme, literal, line, 0)
+  }
+
+  pub fn new_with_column(
+    token_type: TokenType,
+    lexeme: &str,
+    literal: Option<Literal>,
+    line: usize,
+    column: usize,
+  ) -> Token {
     Token {
       token_type,
       lexeme: lexeme.to_string(),
       literal,
       line,
+      column,
+      offset: 0,
     }
   }
 
+  // where the token starts in the whole source, counted in chars
+  pub fn with_offset(mut self, offset: usize) -> Token {
+    self.offset = offset;
+    self
+  }
+
   pub fn new_eof(line: usize) -> Token {
     Token {
       token_type: TokenType::Eof,
       lexeme: String::new(),
       literal: None,
       line,
+      column: 0,
+      offset: 0,
     }
   }
 
@@ -45,6 +76,19 @@ impl Token {
     self.line
   }
 
+  // 1-based, or 0 when the token did not come from the scanner
+  pub fn get_column(&self) -> usize {
+    self.column
+  }
+
+  pub fn get_offset(&self) -> usize {
+    self.offset
+  }
+
+  pub fn get_end_offset(&self) -> usize {
+    self.offset + self.lexeme.chars().count()
+  }
+
   pub fn is_type(&self, token_type: &TokenType) -> bool {
     self.token_type.eq(token_type)
   }
@@ -75,7 +119,7 @@ impl fmt::Display for Token {
   }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum Literal {
   Number(f64),
   String(String),
@@ -84,6 +128,8 @@ pub enum Literal {
   NativeFunction(Rc<LoxNativeFunction>),
   Class(Rc<LoxClass>),
   Instance(Rc<LoxInstance>),
+  Map(Rc<RefCell<HashMap<MapKey, Literal>>>),
+  Array(Rc<RefCell<Vec<Literal>>>),
   Nil,
 }
 
@@ -99,19 +145,412 @@ impl Literal {
   pub fn is_truthy(&self) -> bool {
     !matches!(self, Literal::Nil | Literal::Boolean(false))
   }
+
+  // unlike IEEE, NaN equals itself, so `x == x` always holds
+  pub fn numbers_equal(left: f64, right: f64) -> bool {
+    (left.is_nan() && right.is_nan()) || left.eq(&right) || (left - right).abs() < f64::EPSILON
+  }
+
+  // the one way numbers are written out, whether printed or concatenated;
+  // like the reference jlox, negative zero keeps its sign and prints as `-0`
+  pub fn format_number(n: f64) -> String {
+    if n.is_nan() {
+      "NaN".to_string()
+    } else if n == 0.0 && n.is_sign_negative() {
+      "-0".to_string()
+    } else if n.is_infinite() && n.is_sign_positive() {
+      "Infinity".to_string()
+    } else if n.is_infinite() {
+      "-Infinity".to_string()
+    } else {
+      n.to_string()
+    }
+  }
+
+  // the one conversion bitwise operators are to share; past 2^53 not every
+  // integer has an exact f64, and `as i64` would quietly saturate instead
+  pub fn to_bitwise_operand(operator: &Token, n: f64) -> Result<i64, LoxError> {
+    if !n.is_finite() || n.abs() > MAX_SAFE_INTEGER {
+      return Err(LoxError::runtime_error(
+        operator,
+        "Number too large for bitwise operation.",
+      ));
+    }
+
+    Ok(n.trunc() as i64)
+  }
+
+  // by `type_name`, so user and native functions count as the same type
+  pub fn same_type(&self, other: &Literal) -> bool {
+    self.type_name() == other.type_name()
+  }
+
+  // the name used for this value's type in error messages
+  pub fn type_name(&self) -> &'static str {
+    match self {
+      Literal::Number(_) => "number",
+      Literal::String(_) => "string",
+      Literal::Boolean(_) => "boolean",
+      Literal::Function(_) | Literal::NativeFunction(_) => "function",
+      Literal::Class(_) => "class",
+      Literal::Instance(_) => "instance",
+      Literal::Map(_) => "map",
+      Literal::Array(_) => "array",
+      Literal::Nil => "nil",
+    }
+  }
+}
+
+impl PartialEq for Literal {
+  fn eq(&self, other: &Literal) -> bool {
+    self.equals(other, &mut HashSet::new())
+  }
+}
+
+impl Literal {
+  // `comparing` holds the pairs of containers already being compared; a pair
+  // met again cycles back the same way on both sides, so counts as equal
+  fn equals(&self, other: &Literal, comparing: &mut HashSet<(usize, usize)>) -> bool {
+    match (self, other) {
+      (Literal::Number(l), Literal::Number(r)) => Literal::numbers_equal(*l, *r),
+      (Literal::String(l), Literal::String(r)) => l.eq(r),
+      (Literal::Boolean(l), Literal::Boolean(r)) => l.eq(r),
+      (Literal::Function(l), Literal::Function(r)) => l.eq(r),
+      (Literal::NativeFunction(l), Literal::NativeFunction(r)) => l.eq(r),
+      (Literal::Class(l), Literal::Class(r)) => l.eq(r),
+      // identity, as `==` is when there is no `equals` method to call
+      (Literal::Instance(l), Literal::Instance(r)) => Rc::ptr_eq(l, r),
+      (Literal::Map(l), Literal::Map(r)) => {
+        let pair = (Rc::as_ptr(l) as usize, Rc::as_ptr(r) as usize);
+        if Rc::ptr_eq(l, r) || !comparing.insert(pair) {
+          return true;
+        }
+
+        let (l, r) = (l.borrow(), r.borrow());
+        l.len() == r.len()
+          && l
+            .iter()
+            .all(|(k, a)| r.get(k).is_some_and(|b| a.equals(b, comparing)))
+      }
+      (Literal::Array(l), Literal::Array(r)) => {
+        let pair = (Rc::as_ptr(l) as usize, Rc::as_ptr(r) as usize);
+        if Rc::ptr_eq(l, r) || !comparing.insert(pair) {
+          return true;
+        }
+
+        let (l, r) = (l.borrow(), r.borrow());
+        l.len() == r.len() && l.iter().zip(r.iter()).all(|(a, b)| a.equals(b, comparing))
+      }
+      (Literal::Nil, Literal::Nil) => true,
+      _ => false,
+    }
+  }
 }
 
 impl fmt::Display for Literal {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    self.write_with(&PlainFormatter, f, &mut Vec::new())
+  }
+}
+
+impl Literal {
+  // `visiting` holds the containers being written out around this value, so
+  // one that contains itself goes to `visit_cycle` the second time
+  pub fn write_with<F: LiteralFormatter + ?Sized>(
+    &self,
+    formatter: &F,
+    f: &mut fmt::Formatter,
+    visiting: &mut Vec<usize>,
+  ) -> fmt::Result {
+    let address = match self {
+      Literal::Map(m) => Some(Rc::as_ptr(m) as usize),
+      Literal::Array(a) => Some(Rc::as_ptr(a) as usize),
+      _ => None,
+    };
+    if let Some(address) = address {
+      if visiting.contains(&address) {
+        return formatter.visit_cycle(f, self);
+      }
+      visiting.push(address);
+    }
+
+    let res = match self {
+      Literal::Number(n) => formatter.visit_number(f, *n),
+      Literal::String(s) => formatter.visit_string(f, s),
+      Literal::Boolean(b) => formatter.visit_boolean(f, *b),
+      Literal::Function(fun) => formatter.visit_function(f, fun),
+      Literal::NativeFunction(fun) => formatter.visit_native_function(f, fun),
+      Literal::Class(c) => formatter.visit_class(f, c),
+      Literal::Instance(i) => formatter.visit_instance(f, i),
+      Literal::Map(m) => formatter.visit_map(f, &m.borrow(), visiting),
+      Literal::Array(a) => formatter.visit_array(f, &a.borrow(), visiting),
+      Literal::Nil => formatter.visit_nil(f),
+    };
+
+    if address.is_some() {
+      visiting.pop();
+    }
+    res
+  }
+
+  pub fn formatted<'a, F: LiteralFormatter>(&'a self, formatter: &'a F) -> Formatted<'a, F> {
+    Formatted {
+      literal: self,
+      formatter,
+    }
+  }
+}
+
+// one method per kind of value; `Literal::write_with` does the dispatch and
+// the cycle detection, so a formatter only decides how each kind looks
+pub trait LiteralFormatter {
+  fn visit_number(&self, f: &mut fmt::Formatter, n: f64) -> fmt::Result;
+  fn visit_string(&self, f: &mut fmt::Formatter, s: &str) -> fmt::Result;
+  fn visit_boolean(&self, f: &mut fmt::Formatter, b: bool) -> fmt::Result;
+  fn visit_function(&self, f: &mut fmt::Formatter, fun: &LoxFunction) -> fmt::Result;
+  fn visit_native_function(&self, f: &mut fmt::Formatter, fun: &LoxNativeFunction) -> fmt::Result;
+  fn visit_class(&self, f: &mut fmt::Formatter, c: &LoxClass) -> fmt::Result;
+  fn visit_instance(&self, f: &mut fmt::Formatter, i: &LoxInstance) -> fmt::Result;
+  fn visit_map(
+    &self,
+    f: &mut fmt::Formatter,
+    m: &HashMap<MapKey, Literal>,
+    visiting: &mut Vec<usize>,
+  ) -> fmt::Result;
+  fn visit_array(
+    &self,
+    f: &mut fmt::Formatter,
+    a: &[Literal],
+    visiting: &mut Vec<usize>,
+  ) -> fmt::Result;
+  fn visit_nil(&self, f: &mut fmt::Formatter) -> fmt::Result;
+
+  fn visit_cycle(&self, f: &mut fmt::Formatter, literal: &Literal) -> fmt::Result {
+    match literal {
+      Literal::Map(_) => write!(f, "{{...}}"),
+      _ => write!(f, "[...]"),
+    }
+  }
+}
+
+// lets a formatter be used anywhere a `Display` is expected
+pub struct Formatted<'a, F: LiteralFormatter> {
+  literal: &'a Literal,
+  formatter: &'a F,
+}
+
+impl<F: LiteralFormatter> fmt::Display for Formatted<'_, F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    self.literal.write_with(self.formatter, f, &mut Vec::new())
+  }
+}
+
+// what `print` shows
+pub struct PlainFormatter;
+
+impl LiteralFormatter for PlainFormatter {
+  fn visit_number(&self, f: &mut fmt::Formatter, n: f64) -> fmt::Result {
+    write!(f, "{}", Literal::format_number(n))
+  }
+
+  fn visit_string(&self, f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "{s}")
+  }
+
+  fn visit_boolean(&self, f: &mut fmt::Formatter, b: bool) -> fmt::Result {
+    write!(f, "{b}")
+  }
+
+  fn visit_function(&self, f: &mut fmt::Formatter, fun: &LoxFunction) -> fmt::Result {
+    write!(f, "{fun}")
+  }
+
+  fn visit_native_function(&self, f: &mut fmt::Formatter, fun: &LoxNativeFunction) -> fmt::Result {
+    write!(f, "{fun}")
+  }
+
+  fn visit_class(&self, f: &mut fmt::Formatter, c: &LoxClass) -> fmt::Result {
+    write!(f, "{c}")
+  }
+
+  fn visit_instance(&self, f: &mut fmt::Formatter, i: &LoxInstance) -> fmt::Result {
+    write!(f, "{i}")
+  }
+
+  fn visit_map(
+    &self,
+    f: &mut fmt::Formatter,
+    m: &HashMap<MapKey, Literal>,
+    visiting: &mut Vec<usize>,
+  ) -> fmt::Result {
+    write!(f, "{{")?;
+    for (i, (k, v)) in m.iter().enumerate() {
+      if i > 0 {
+        write!(f, ", ")?;
+      }
+      write!(f, "{k}: ")?;
+      v.write_with(self, f, visiting)?;
+    }
+    write!(f, "}}")
+  }
+
+  fn visit_array(
+    &self,
+    f: &mut fmt::Formatter,
+    a: &[Literal],
+    visiting: &mut Vec<usize>,
+  ) -> fmt::Result {
+    write!(f, "[")?;
+    for (i, e) in a.iter().enumerate() {
+      if i > 0 {
+        write!(f, ", ")?;
+      }
+      e.write_with(self, f, visiting)?;
+    }
+    write!(f, "]")
+  }
+
+  fn visit_nil(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "nil")
+  }
+}
+
+// wraps every value in its type, e.g. `number(5)` or `string("hi")`, so that
+// `5` and `"5"` can be told apart
+pub struct DebugFormatter;
+
+impl LiteralFormatter for DebugFormatter {
+  fn visit_number(&self, f: &mut fmt::Formatter, n: f64) -> fmt::Result {
+    write!(f, "number({})", Literal::format_number(n))
+  }
+
+  fn visit_string(&self, f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "string({s:?})")
+  }
+
+  fn visit_boolean(&self, f: &mut fmt::Formatter, b: bool) -> fmt::Result {
+    write!(f, "boolean({b})")
+  }
+
+  fn visit_function(&self, f: &mut fmt::Formatter, fun: &LoxFunction) -> fmt::Result {
+    write!(f, "function({fun})")
+  }
+
+  fn visit_native_function(&self, f: &mut fmt::Formatter, fun: &LoxNativeFunction) -> fmt::Result {
+    write!(f, "function({fun})")
+  }
+
+  fn visit_class(&self, f: &mut fmt::Formatter, c: &LoxClass) -> fmt::Result {
+    write!(f, "class({c})")
+  }
+
+  fn visit_instance(&self, f: &mut fmt::Formatter, i: &LoxInstance) -> fmt::Result {
+    write!(f, "instance({i})")
+  }
+
+  fn visit_map(
+    &self,
+    f: &mut fmt::Formatter,
+    m: &HashMap<MapKey, Literal>,
+    visiting: &mut Vec<usize>,
+  ) -> fmt::Result {
+    write!(f, "map({{")?;
+    for (i, (k, v)) in m.iter().enumerate() {
+      if i > 0 {
+        write!(f, ", ")?;
+      }
+      match k {
+        MapKey::Number(n) => self.visit_number(f, *n)?,
+        MapKey::String(s) => self.visit_string(f, s)?,
+        MapKey::Boolean(b) => self.visit_boolean(f, *b)?,
+      }
+      write!(f, ": ")?;
+      v.write_with(self, f, visiting)?;
+    }
+    write!(f, "}})")
+  }
+
+  fn visit_array(
+    &self,
+    f: &mut fmt::Formatter,
+    a: &[Literal],
+    visiting: &mut Vec<usize>,
+  ) -> fmt::Result {
+    write!(f, "array([")?;
+    for (i, e) in a.iter().enumerate() {
+      if i > 0 {
+        write!(f, ", ")?;
+      }
+      e.write_with(self, f, visiting)?;
+    }
+    write!(f, "])")
+  }
+
+  fn visit_nil(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "nil")
+  }
+}
+
+pub const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+#[derive(Debug, Clone)]
+pub enum MapKey {
+  Number(f64),
+  String(String),
+  Boolean(bool),
+}
+
+impl MapKey {
+  pub fn from_literal(literal: &Literal) -> Option<MapKey> {
+    match literal {
+      Literal::Number(n) => Some(MapKey::Number(*n)),
+      Literal::String(s) => Some(MapKey::String(s.clone())),
+      Literal::Boolean(b) => Some(MapKey::Boolean(*b)),
+      _ => None,
+    }
+  }
+
+  fn number_bits(n: f64) -> u64 {
+    // -0 and 0 compare equal, so they must hash equally
+    if n.eq(&0.0) {
+      return 0_f64.to_bits();
+    }
+
+    n.to_bits()
+  }
+}
+
+impl PartialEq for MapKey {
+  fn eq(&self, other: &Self) -> bool {
+    match (self, other) {
+      (MapKey::Number(l), MapKey::Number(r)) => {
+        MapKey::number_bits(*l).eq(&MapKey::number_bits(*r))
+      }
+      (MapKey::String(l), MapKey::String(r)) => l.eq(r),
+      (MapKey::Boolean(l), MapKey::Boolean(r)) => l.eq(r),
+      _ => false,
+    }
+  }
+}
+
+impl Eq for MapKey {}
+
+impl Hash for MapKey {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    match self {
+      MapKey::Number(n) => MapKey::number_bits(*n).hash(state),
+      MapKey::String(s) => s.hash(state),
+      MapKey::Boolean(b) => b.hash(state),
+    }
+  }
+}
+
+impl fmt::Display for MapKey {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
-      Literal::Number(n) => write!(f, "{n}"),
-      Literal::String(s) => write!(f, "{s}"),
-      Literal::Boolean(b) => write!(f, "{b}"),
-      Literal::Function(fun) => write!(f, "{fun}"),
-      Literal::NativeFunction(fun) => write!(f, "{fun}"),
-      Literal::Class(c) => write!(f, "{c}"),
-      Literal::Instance(i) => write!(f, "{i}"),
-      Literal::Nil => write!(f, "nil"),
+      MapKey::Number(n) => write!(f, "{}", Literal::format_number(*n)),
+      MapKey::String(s) => write!(f, "{s}"),
+      MapKey::Boolean(b) => write!(f, "{b}"),
     }
   }
 }
@@ -122,10 +561,16 @@ pub enum TokenType {
   RightBracket,
   LeftBrace,
   RightBrace,
+  LeftSquareBracket,
+  RightSquareBracket,
+  Colon,
   Comma,
   Dot,
+  Ellipsis,
   Plus,
+  PlusPlus,
   Minus,
+  MinusMinus,
   Star,
   Semicolon,
   Eof,
@@ -138,6 +583,8 @@ pub enum TokenType {
   GreaterEqual,
   Greater,
   Slash,
+  TildeSlash,
+  QuestionQuestion,
   String,
   Number,
   Identifier,
@@ -158,11 +605,49 @@ pub enum TokenType {
   Var,
   While,
   Break,
+  Continue,
+  Foreach,
+  In,
+  Instanceof,
+  // only ever in `Scanner::get_trivia`, never handed to the parser
+  Comment,
+}
+
+impl TokenType {
+  pub fn is_keyword(&self) -> bool {
+    KEYWORDS.iter().any(|(_, t)| t.eq(self))
+  }
 }
 
+// the one keyword table, used by the scanner and by tooling such as highlighters
+pub const KEYWORDS: &[(&str, TokenType)] = &[
+  ("and", TokenType::And),
+  ("class", TokenType::Class),
+  ("else", TokenType::Else),
+  ("false", TokenType::False),
+  ("for", TokenType::For),
+  ("fun", TokenType::Fun),
+  ("if", TokenType::If),
+  ("nil", TokenType::Nil),
+  ("or", TokenType::Or),
+  ("print", TokenType::Print),
+  ("return", TokenType::Return),
+  ("super", TokenType::Super),
+  ("this", TokenType::This),
+  ("true", TokenType::True),
+  ("var", TokenType::Var),
+  ("while", TokenType::While),
+  ("break", TokenType::Break),
+  ("continue", TokenType::Continue),
+  ("foreach", TokenType::Foreach),
+  ("in", TokenType::In),
+  ("instanceof", TokenType::Instanceof),
+];
+
 #[cfg(test)]
 mod test {
   use super::*;
+  use crate::{environment::Environment, stmt::FunctionStmt};
 
   #[test]
   fn test_token_is_type() {
@@ -170,4 +655,199 @@ mod test {
     assert!(token.is_type(&TokenType::Number));
     assert!(!token.is_type(&TokenType::Nil));
   }
+
+  #[test]
+  fn test_is_keyword() {
+    assert!(TokenType::While.is_keyword());
+    assert!(TokenType::Instanceof.is_keyword());
+    assert!(!TokenType::Plus.is_keyword());
+    assert!(!TokenType::Identifier.is_keyword());
+
+    assert!(KEYWORDS.iter().all(|(_, t)| t.is_keyword()));
+    assert!(KEYWORDS
+      .iter()
+      .any(|(k, t)| *k == "while" && t.eq(&TokenType::While)));
+  }
+
+  #[test]
+  fn test_negative_zero() {
+    assert_eq!(Literal::format_number(-0.0), "-0");
+    assert_eq!(Literal::format_number(0.0), "0");
+    assert_eq!(Literal::Number(-0.0), Literal::Number(0.0));
+    assert_eq!(MapKey::Number(-0.0), MapKey::Number(0.0));
+  }
+
+  #[test]
+  fn test_display_nested() {
+    let inner = Literal::Array(RefCell::new(vec![Literal::Number(2.0), Literal::Nil]).into());
+    let mut map = HashMap::new();
+    map.insert(MapKey::String("a".to_string()), inner.clone());
+    let outer = Literal::Array(
+      RefCell::new(vec![
+        Literal::Number(1.0),
+        inner.clone(),
+        inner,
+        Literal::Map(RefCell::new(map).into()),
+      ])
+      .into(),
+    );
+    // the same array twice is shared, not cyclic
+    assert_eq!(outer.to_string(), "[1, [2, nil], [2, nil], {a: [2, nil]}]");
+
+    let array = Rc::new(RefCell::new(vec![Literal::Number(1.0)]));
+    array.borrow_mut().push(Literal::Array(array.clone()));
+    let map = Rc::new(RefCell::new(HashMap::new()));
+    map
+      .borrow_mut()
+      .insert(MapKey::Boolean(true), Literal::Map(map.clone()));
+    array.borrow_mut().push(Literal::Map(map.clone()));
+
+    assert_eq!(
+      Literal::Array(array.clone()).to_string(),
+      "[1, [...], {true: {...}}]"
+    );
+
+    // break the cycles so both are freed
+    array.borrow_mut().clear();
+    map.borrow_mut().clear();
+  }
+
+  #[test]
+  fn test_plain_formatter() {
+    let array = Literal::Array(
+      RefCell::new(vec![
+        Literal::Number(1.5),
+        Literal::String("hi".to_string()),
+        Literal::Boolean(false),
+        Literal::Nil,
+      ])
+      .into(),
+    );
+
+    assert_eq!(
+      array.formatted(&PlainFormatter).to_string(),
+      "[1.5, hi, false, nil]"
+    );
+    assert_eq!(
+      array.formatted(&PlainFormatter).to_string(),
+      array.to_string()
+    );
+  }
+
+  #[test]
+  fn test_debug_formatter() {
+    let mut map = HashMap::new();
+    map.insert(MapKey::String("k".to_string()), Literal::Number(5.0));
+    let array = Rc::new(RefCell::new(vec![
+      Literal::Number(5.0),
+      Literal::String("say \"hi\"".to_string()),
+      Literal::Boolean(true),
+      Literal::Nil,
+      Literal::Map(RefCell::new(map).into()),
+    ]));
+    array.borrow_mut().push(Literal::Array(array.clone()));
+
+    assert_eq!(
+      Literal::Array(array.clone())
+        .formatted(&DebugFormatter)
+        .to_string(),
+      r#"array([number(5), string("say \"hi\""), boolean(true), nil, map({string("k"): number(5)}), [...]])"#
+    );
+
+    array.borrow_mut().clear();
+  }
+
+  #[test]
+  fn test_to_bitwise_operand() -> Result<(), LoxError> {
+    let operator = Token::new(TokenType::Minus, "-", None, 1);
+    let convert = |n| Literal::to_bitwise_operand(&operator, n);
+    assert_eq!(convert(5.0)?, 5);
+    assert_eq!(convert(-5.7)?, -5);
+    assert_eq!(convert(MAX_SAFE_INTEGER)?, 1 << 53);
+    assert_eq!(convert(-MAX_SAFE_INTEGER)?, -(1 << 53));
+
+    let message = Some("Number too large for bitwise operation.");
+    for n in [MAX_SAFE_INTEGER + 2.0, -1e300, f64::INFINITY, f64::NAN] {
+      assert!(convert(n).is_err_and(|e| e.is_runtime_error() && e.get_message() == message));
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_instance_equality_is_identity() {
+    let class = Rc::new(LoxClass::new("A", &None, &HashMap::new()));
+    let instance = Rc::new(LoxInstance::new(&class));
+
+    assert_eq!(
+      Literal::Instance(instance.clone()),
+      Literal::Instance(instance)
+    );
+    assert_ne!(
+      Literal::Instance(LoxInstance::new(&class).into()),
+      Literal::Instance(LoxInstance::new(&class).into())
+    );
+  }
+
+  #[test]
+  fn test_type_name_and_same_type() {
+    let env = Rc::new(RefCell::new(Environment::new()));
+    let declaration = FunctionStmt {
+      name: Token::new(TokenType::Identifier, "f", None, 1),
+      params: Vec::new(),
+      is_variadic: false,
+      body: Vec::new().into(),
+    };
+    let class = Rc::new(LoxClass::new("A", &None, &HashMap::new()));
+    let literals = [
+      (Literal::Number(1.0), "number"),
+      (Literal::String("a".to_string()), "string"),
+      (Literal::Boolean(true), "boolean"),
+      (
+        Literal::Function(LoxFunction::new(&env, &declaration, false).into()),
+        "function",
+      ),
+      (
+        Literal::NativeFunction(
+          LoxNativeFunction {
+            fun: Rc::new(Clock),
+          }
+          .into(),
+        ),
+        "function",
+      ),
+      (Literal::Class(class.clone()), "class"),
+      (
+        Literal::Instance(LoxInstance::new(&class).into()),
+        "instance",
+      ),
+      (Literal::Map(RefCell::new(HashMap::new()).into()), "map"),
+      (Literal::Array(RefCell::new(Vec::new()).into()), "array"),
+      (Literal::Nil, "nil"),
+    ];
+
+    // user and native functions share a type, so compare by name
+    for (left, name) in literals.iter() {
+      assert_eq!(left.type_name(), *name);
+      for (right, other) in literals.iter() {
+        assert_eq!(left.same_type(right), name == other);
+      }
+    }
+
+    assert!(Literal::Number(1.0).same_type(&Literal::Number(f64::NAN)));
+  }
+
+  #[test]
+  fn test_map_key() {
+    assert_eq!(
+      MapKey::from_literal(&Literal::Number(0.0)),
+      Some(MapKey::Number(-0.0))
+    );
+    assert_eq!(
+      MapKey::from_literal(&Literal::String("a".to_string())),
+      Some(MapKey::String("a".to_string()))
+    );
+    assert_ne!(MapKey::Number(1.0), MapKey::Boolean(true));
+    assert!(MapKey::from_literal(&Literal::Nil).is_none());
+  }
 }